@@ -87,7 +87,7 @@ impl App for DemoApp {
     }
 
     fn on_event(&mut self, window: &Window, event: &WindowEvent) {
-        let egui_response = self.egui_winit.on_window_event(&window, event);
+        let egui_response = self.egui_winit.on_window_event(window, event);
         if !egui_response.consumed {
             match event {
                 WindowEvent::Resized(new_size) => self.resize(new_size),
@@ -109,6 +109,8 @@ impl DemoApp {
                 egui_directx11::split_output(egui_output);
             self.egui_winit
                 .handle_platform_output(window, platform_output);
+            self.egui_renderer
+                .discard_target(&self.device_context, render_target);
             unsafe {
                 self.device_context.ClearRenderTargetView(
                     render_target,
@@ -132,7 +134,7 @@ impl DemoApp {
         if let Err(err) = self.resize_swap_chain_and_render_target(
             new_size.width,
             new_size.height,
-            DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            self.egui_renderer.recommended_target_format(),
         ) {
             panic!("Failed to resize framebuffers: {err:?}");
         }
@@ -19,3 +19,96 @@ float4 ps_main(
     in const float4 i_color: COLOR): SV_TARGET {
     return i_color * g_tex.Sample(g_sampler, i_uv);
 }
+
+// The pixel shader for `Renderer::render_to_multiple_targets` with two render
+// targets, compiled into `egui_ps_mrt.bin` with
+// `fxc /T ps_5_0 /E ps_main_mrt`. The second output holds the coverage of
+// `egui` as a premultiplied mask, as it is blended in the same way as the
+// color.
+struct PsOutputMrt {
+    float4 color: SV_TARGET0;
+    float4 mask : SV_TARGET1;
+};
+
+PsOutputMrt ps_main_mrt(
+    in const float4 i_pos  : SV_POSITION,
+    in const float2 i_uv   : TEXCOORD,
+    in const float4 i_color: COLOR) {
+    PsOutputMrt output;
+    output.color = i_color * g_tex.Sample(g_sampler, i_uv);
+    output.mask  = output.color.aaaa;
+    return output;
+}
+
+// The vertex shader meshes are drawn with, compiled into
+// `egui_vs_transform.bin` with `fxc /T vs_5_0 /E vs_main_transform`. Positions
+// are received in points, and transformed into normalized device coordinates
+// by the matrix the renderer computes once per frame. `vs_main` above is only
+// used to blit internal render targets.
+cbuffer Transform: register(b0) {
+    row_major float4x4 g_transform;
+};
+
+void vs_main_transform(
+    in const float2 i_pos  : POSITION,
+    in const float2 i_uv   : TEXCOORD,
+    in const float4 i_color: COLOR,
+    out      float4 o_pos  : SV_POSITION,
+    out      float2 o_uv   : TEXCOORD,
+    out      float4 o_color: COLOR) {
+    o_pos   = mul(float4(i_pos, 0.0, 1.0), g_transform);
+    o_uv    = i_uv;
+    o_color = i_color;
+}
+
+// The pixel shader for `RenderOptions::rounded_clip`, compiled into
+// `egui_ps_rounded_clip.bin` with `fxc /T ps_5_0 /E ps_main_rounded_clip`.
+// Pixels outside the clip rectangle with rounded corners are masked with an
+// anti-aliased edge.
+cbuffer RoundedClip: register(b0) {
+    float4 g_clip_rect;   // left, top, right and bottom in pixels.
+    float  g_clip_radius; // in pixels.
+};
+
+float4 ps_main_rounded_clip(
+    in const float4 i_pos  : SV_POSITION,
+    in const float2 i_uv   : TEXCOORD,
+    in const float4 i_color: COLOR): SV_TARGET {
+    float2 center    = (g_clip_rect.xy + g_clip_rect.zw) * 0.5;
+    float2 half_size = (g_clip_rect.zw - g_clip_rect.xy) * 0.5;
+    float  radius    = min(g_clip_radius, min(half_size.x, half_size.y));
+    float2 q         = abs(i_pos.xy - center) - half_size + radius;
+    float  dist      = length(max(q, 0.0)) + min(max(q.x, q.y), 0.0) - radius;
+    float  coverage  = saturate(0.5 - dist);
+    return i_color * g_tex.Sample(g_sampler, i_uv) * coverage;
+}
+
+// The pixel shader for `RenderOptions::straight_alpha_output`, compiled into
+// `egui_ps_unpremultiply.bin` with `fxc /T ps_5_0 /E ps_main_unpremultiply`.
+// The internal render target holds premultiplied colors, which are divided by
+// the accumulated coverage of `egui` to write straight alpha.
+float4 ps_main_unpremultiply(
+    in const float4 i_pos  : SV_POSITION,
+    in const float2 i_uv   : TEXCOORD,
+    in const float4 i_color: COLOR): SV_TARGET {
+    float4 color = g_tex.Sample(g_sampler, i_uv);
+    return color.a > 0.0 ? float4(color.rgb / color.a, color.a) : 0.0;
+}
+
+// The pixel shader for `RenderOptions::dual_source_blending`, compiled into
+// `egui_ps_dual_source.bin` with `fxc /T ps_5_0 /E ps_main_dual_source`.
+// `SV_TARGET1` holds the factor the render target is multiplied by when
+// blending over white in gamma space, converted back to linear, so that the
+// blend state `src0 + dst * src1` matches gamma-space compositing over black
+// and white and approximates it in between.
+void ps_main_dual_source(
+    in const float4 i_pos  : SV_POSITION,
+    in const float2 i_uv   : TEXCOORD,
+    in const float4 i_color: COLOR,
+    out float4 o_color     : SV_TARGET0,
+    out float4 o_factor    : SV_TARGET1) {
+    float4 color = g_tex.Sample(g_sampler, i_uv) * i_color;
+    float3 gamma = pow(color.rgb, 1.0 / 2.2) - color.a + 1.0;
+    o_color = color;
+    o_factor = float4(saturate(pow(gamma, 2.2) - color.rgb), color.a);
+}
@@ -0,0 +1,56 @@
+#[cfg(feature = "annotations")]
+use windows::core::Interface;
+#[cfg(feature = "annotations")]
+use windows::core::PCWSTR;
+use windows::Win32::Graphics::Direct3D11::*;
+
+/// A named event shown in graphics debuggers and frame profilers (e.g. PIX,
+/// RenderDoc or Tracy) around the calls issued while it is alive, emitted
+/// with `ID3DUserDefinedAnnotation` if the `annotations` feature is enabled.
+/// The event is ended when this is dropped, so begin and end are always
+/// balanced.
+pub struct Annotation {
+    #[cfg(feature = "annotations")]
+    annotation: Option<ID3DUserDefinedAnnotation>,
+}
+
+impl Annotation {
+    /// Begin an event named `name` on `device_context`, prefixed with
+    /// `prefix` and a slash if provided. This does nothing if the
+    /// `annotations` feature is disabled or the device context does not
+    /// support annotations.
+    #[cfg_attr(not(feature = "annotations"), allow(unused_variables))]
+    pub fn begin(
+        device_context: &ID3D11DeviceContext,
+        prefix: Option<&str>,
+        name: &str,
+    ) -> Self {
+        #[cfg(feature = "annotations")]
+        {
+            let annotation =
+                device_context.cast::<ID3DUserDefinedAnnotation>().ok();
+            if let Some(annotation) = &annotation {
+                let name: Vec<u16> = prefix
+                    .into_iter()
+                    .flat_map(|prefix| [prefix, "/"])
+                    .chain([name])
+                    .flat_map(str::encode_utf16)
+                    .chain([0])
+                    .collect();
+                unsafe { annotation.BeginEvent(PCWSTR(name.as_ptr())) };
+            }
+            Self { annotation }
+        }
+        #[cfg(not(feature = "annotations"))]
+        Self {}
+    }
+}
+
+#[cfg(feature = "annotations")]
+impl Drop for Annotation {
+    fn drop(&mut self) {
+        if let Some(annotation) = &self.annotation {
+            unsafe { annotation.EndEvent() };
+        }
+    }
+}
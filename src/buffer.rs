@@ -0,0 +1,221 @@
+use crate::{IndexData, MeshData, Renderer, VertexData};
+
+use std::{mem, ptr};
+
+use windows::{
+    core::Result,
+    Win32::Graphics::{Direct3D11::*, Dxgi::Common::*},
+};
+
+/// A dynamic buffer persisting across frames, sub-allocated by bumping a
+/// write cursor, and recreated with a larger capacity when it is too small
+/// for the data of a single draw.
+///
+/// The first allocation after [`DynamicBuffer::reset`] maps the buffer with
+/// `D3D11_MAP_WRITE_DISCARD`, orphaning the memory previous draws may still
+/// read from. Later allocations map it with `D3D11_MAP_WRITE_NO_OVERWRITE`
+/// and only write past the cursor, which no draw issued so far reads from.
+/// When the buffer is exhausted, it is orphaned again and the cursor starts
+/// over from the beginning.
+pub struct DynamicBuffer {
+    buffer: ID3D11Buffer,
+    capacity: usize,
+    cursor: Option<usize>,
+}
+
+impl DynamicBuffer {
+    const MIN_CAPACITY: usize = 64 * 1024;
+
+    /// The alignment of each allocation, which suits both vertices and
+    /// indices of either format.
+    const ALIGNMENT: usize = 16;
+
+    fn new(
+        device: &ID3D11Device,
+        capacity: usize,
+        bind_flags: D3D11_BIND_FLAG,
+    ) -> Result<Self> {
+        let capacity = capacity.next_power_of_two().max(Self::MIN_CAPACITY);
+        let mut buffer = None;
+        unsafe {
+            device.CreateBuffer(
+                &D3D11_BUFFER_DESC {
+                    ByteWidth: capacity as _,
+                    Usage: D3D11_USAGE_DYNAMIC,
+                    BindFlags: bind_flags.0 as _,
+                    CPUAccessFlags: D3D11_CPU_ACCESS_WRITE.0 as _,
+                    ..D3D11_BUFFER_DESC::default()
+                },
+                None,
+                Some(&mut buffer),
+            )
+        }?;
+        Ok(Self {
+            buffer: buffer.unwrap(),
+            capacity,
+            cursor: None,
+        })
+    }
+
+    /// Make sure `slot` holds a buffer of at least `size` bytes.
+    fn reserve<'a>(
+        slot: &'a mut Option<Self>,
+        device: &ID3D11Device,
+        size: usize,
+        bind_flags: D3D11_BIND_FLAG,
+    ) -> Result<&'a mut Self> {
+        if slot.as_ref().is_none_or(|b| b.capacity < size) {
+            *slot = None;
+            *slot = Some(Self::new(device, size, bind_flags)?);
+        }
+        Ok(slot.as_mut().unwrap())
+    }
+
+    /// Start a new frame, orphaning the buffer on the next allocation.
+    pub(crate) fn reset(&mut self) {
+        self.cursor = None;
+    }
+
+    /// Allocate `size` bytes, map them and call `f` with a pointer to the
+    /// mapped memory. Returns the offset of the allocation in bytes.
+    fn write(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        size: usize,
+        f: impl FnOnce(*mut u8),
+    ) -> Result<usize> {
+        let (offset, map_type) = match self.cursor {
+            Some(cursor) if cursor + size <= self.capacity => {
+                (cursor, D3D11_MAP_WRITE_NO_OVERWRITE)
+            },
+            _ => (0, D3D11_MAP_WRITE_DISCARD),
+        };
+        let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+        unsafe {
+            device_context.Map(&self.buffer, 0, map_type, 0, Some(&mut mapped))
+        }?;
+        f(unsafe { (mapped.pData as *mut u8).add(offset) });
+        unsafe { device_context.Unmap(&self.buffer, 0) };
+        self.cursor = Some((offset + size).next_multiple_of(Self::ALIGNMENT));
+        Ok(offset)
+    }
+}
+
+impl Renderer {
+    /// Start a new frame for the dynamic buffers of
+    /// [`BufferStrategy::SingleDynamic`](crate::BufferStrategy::SingleDynamic),
+    /// so that their memory is orphaned once instead of overwritten while
+    /// the GPU may still read from it.
+    pub(crate) fn reset_dynamic_buffers(&mut self) {
+        for buffer in [
+            &mut self.dynamic_vertex_buffer,
+            &mut self.dynamic_index_buffer,
+        ]
+        .into_iter()
+        .flatten()
+        {
+            buffer.reset();
+        }
+    }
+
+    /// Upload all `meshes` into a single pair of dynamic vertex and index
+    /// buffers and draw them with offsets into these buffers.
+    pub(crate) fn draw_meshes_single_dynamic(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        meshes: &[MeshData],
+    ) -> Result<()> {
+        if meshes.is_empty() {
+            return Ok(());
+        }
+
+        // all meshes share a single index buffer, so 32-bit indices are used
+        // for all of them if any of them needs 32-bit indices.
+        let use_u32 = meshes
+            .iter()
+            .any(|mesh| matches!(mesh.idx, IndexData::U32(_)));
+        let (idx_size, idx_format) = if use_u32 {
+            (mem::size_of::<u32>(), DXGI_FORMAT_R32_UINT)
+        } else {
+            (mem::size_of::<u16>(), DXGI_FORMAT_R16_UINT)
+        };
+        let vtx_count: usize = meshes.iter().map(|mesh| mesh.vtx.len()).sum();
+        let idx_count: usize = meshes.iter().map(|mesh| mesh.idx.len()).sum();
+        let vtx_bytes = vtx_count * mem::size_of::<VertexData>();
+        let idx_bytes = idx_count * idx_size;
+
+        let vb = DynamicBuffer::reserve(
+            &mut self.dynamic_vertex_buffer,
+            &self.device,
+            vtx_bytes,
+            D3D11_BIND_VERTEX_BUFFER,
+        )?;
+        let vb_offset = vb.write(device_context, vtx_bytes, |data| {
+            let mut data = data as *mut VertexData;
+            for mesh in meshes {
+                unsafe {
+                    ptr::copy_nonoverlapping(
+                        mesh.vtx.as_ptr(),
+                        data,
+                        mesh.vtx.len(),
+                    );
+                    data = data.add(mesh.vtx.len());
+                }
+            }
+        })?;
+        let vb = vb.buffer.clone();
+
+        let ib = DynamicBuffer::reserve(
+            &mut self.dynamic_index_buffer,
+            &self.device,
+            idx_bytes,
+            D3D11_BIND_INDEX_BUFFER,
+        )?;
+        let ib_offset = ib.write(device_context, idx_bytes, |data| {
+            let mut data = data;
+            for mesh in meshes {
+                unsafe {
+                    match &mesh.idx {
+                        IndexData::U16(idx) if use_u32 => {
+                            let dst = data as *mut u32;
+                            for (i, &index) in idx.iter().enumerate() {
+                                dst.add(i).write_unaligned(index as u32);
+                            }
+                        },
+                        IndexData::U16(idx) => ptr::copy_nonoverlapping(
+                            idx.as_ptr(),
+                            data as *mut u16,
+                            idx.len(),
+                        ),
+                        IndexData::U32(idx) => ptr::copy_nonoverlapping(
+                            idx.as_ptr(),
+                            data as *mut u32,
+                            idx.len(),
+                        ),
+                    }
+                    data = data.add(mesh.idx.len() * idx_size);
+                }
+            }
+        })?;
+        let ib = ib.buffer.clone();
+
+        unsafe {
+            device_context.IASetVertexBuffers(
+                0,
+                1,
+                Some(&Some(vb)),
+                Some(&(mem::size_of::<VertexData>() as _)),
+                Some(&(vb_offset as _)),
+            );
+            device_context.IASetIndexBuffer(&ib, idx_format, ib_offset as _);
+        }
+        let mut start_index = 0;
+        let mut base_vertex = 0;
+        for mesh in meshes {
+            self.draw_mesh(device_context, mesh, start_index, base_vertex)?;
+            start_index += mesh.idx.len() as u32;
+            base_vertex += mesh.vtx.len() as i32;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,214 @@
+use crate::{
+    background_mesh, clipped_meshes, offscreen::OffscreenMode, Renderer,
+    RendererOutput,
+};
+
+use egui::{epaint::Primitive, ClippedPrimitive, PaintCallbackInfo};
+
+use std::{
+    any::{Any, TypeId},
+    mem,
+};
+
+use windows::{core::Result, Win32::Graphics::Direct3D11::*};
+
+/// A handler of paint callbacks registered with
+/// [`Renderer::register_callback`], invoked with the payload of the
+/// callback.
+pub(crate) type CallbackHandler =
+    Box<dyn FnMut(&CallbackContext<'_>, &dyn Any) + Send + Sync>;
+
+/// What a handler registered with [`Renderer::register_callback`] draws
+/// with.
+pub struct CallbackContext<'a> {
+    /// The device context `egui` is rendered with.
+    pub device_context: &'a ID3D11DeviceContext,
+
+    /// The render target `egui` is rendered to.
+    pub render_target: &'a ID3D11RenderTargetView,
+
+    /// The rectangle to paint into and the clip rectangle of the callback,
+    /// along with the number of physical pixels per point and the size of
+    /// the render target.
+    pub info: PaintCallbackInfo,
+}
+
+/// A paint callback requested by `egui`, collected by
+/// [`Renderer::render_collecting_callbacks`] instead of being dropped.
+#[derive(Clone)]
+pub struct CollectedCallback {
+    /// The position of the callback among the primitives tessellated by
+    /// `egui`, in draw order. Meshes at lower positions were painted before
+    /// the callback was requested, and those at higher positions after.
+    pub index: usize,
+
+    /// The clip rectangle of the callback in points.
+    pub clip_rect: egui::Rect,
+
+    /// The callback itself, holding the rectangle it paints into in points
+    /// and the data of the integration it is intended for.
+    pub callback: egui::PaintCallback,
+}
+
+impl Renderer {
+    /// Register `handler` to be invoked for each paint callback requested by
+    /// `egui` (see [`egui::PaintCallback`]) whose payload is of type `T`,
+    /// replacing the handler previously registered for `T`, if any.
+    ///
+    /// When any handler is registered, the functions drawing the shapes in
+    /// [`RendererOutput`], such as [`Renderer::render`], draw the meshes
+    /// painted before each callback, invoke the handler with the payload
+    /// downcast to `T`, and set up the pipeline state again before drawing
+    /// the following meshes, so that callbacks are drawn in the order
+    /// `egui` paints them. The handler may change any pipeline state, and
+    /// should draw directly into [`CallbackContext::render_target`] using
+    /// [`egui::PaintCallbackInfo::viewport_in_pixels`] and
+    /// [`egui::PaintCallbackInfo::clip_rect_in_pixels`], regardless of
+    /// [`RenderOptions::display_rotation`](crate::RenderOptions::display_rotation)
+    /// or an internal render target.
+    ///
+    /// Like with [`Renderer::draw`], each run of meshes between callbacks is
+    /// drawn on its own, so [`Renderer::stats`] and damage tracking only
+    /// cover the meshes after the last callback. Callbacks without a
+    /// matching handler are dropped with a warning, and
+    /// [`Renderer::render_collecting_callbacks`] collects all callbacks
+    /// instead of invoking the handlers.
+    pub fn register_callback<T: Any>(
+        &mut self,
+        mut handler: impl FnMut(&CallbackContext<'_>, &T) + Send + Sync + 'static,
+    ) {
+        let handler: CallbackHandler = Box::new(move |context, payload| {
+            if let Some(payload) = payload.downcast_ref::<T>() {
+                handler(context, payload);
+            }
+        });
+        let type_id = TypeId::of::<T>();
+        match self
+            .callback_handlers
+            .iter_mut()
+            .find(|(t, _)| *t == type_id)
+        {
+            Some((_, h)) => *h = handler,
+            None => self.callback_handlers.push((type_id, handler)),
+        }
+    }
+
+    /// Remove the handler registered with [`Renderer::register_callback`]
+    /// for payloads of type `T`, if any.
+    pub fn unregister_callback<T: Any>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        self.callback_handlers.retain(|(t, _)| *t != type_id);
+    }
+
+    /// Whether paint callbacks are dispatched to registered handlers instead
+    /// of being collected or dropped.
+    pub(crate) fn dispatches_callbacks(&self) -> bool {
+        !self.callback_handlers.is_empty() && self.collected_callbacks.is_none()
+    }
+
+    /// Draw `primitives`, invoking the registered handler of each paint
+    /// callback between the meshes painted before and after it.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn draw_dispatching_callbacks(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        primitives: Vec<ClippedPrimitive>,
+        scale_factor: f32,
+        mode: Option<OffscreenMode>,
+        bind_render_target: bool,
+    ) -> Result<()> {
+        let zoom_factor = egui_ctx.zoom_factor();
+        let screen_size = Self::get_render_target_size(render_target)?;
+        let mut segment = Vec::new();
+        if self.options.fill_background {
+            segment.push(background_mesh(egui_ctx));
+        }
+        let mut primitives = primitives.into_iter();
+        loop {
+            let primitive = primitives.next();
+            let callback = match primitive {
+                Some(ClippedPrimitive {
+                    primitive: Primitive::Callback(callback),
+                    clip_rect,
+                }) => Some((callback, clip_rect)),
+                Some(primitive) => {
+                    segment.extend(clipped_meshes([primitive], None));
+                    continue;
+                },
+                None => None,
+            };
+            // the last run of meshes is drawn even if empty, to update the
+            // statistics and damage of the frame.
+            if !segment.is_empty() || callback.is_none() {
+                self.draw_in_mode(
+                    device_context,
+                    render_target,
+                    mem::take(&mut segment),
+                    scale_factor,
+                    zoom_factor,
+                    mode,
+                    bind_render_target,
+                )?;
+            }
+            let Some((callback, clip_rect)) = callback else {
+                return Ok(());
+            };
+
+            let type_id = (*callback.callback).type_id();
+            let Some((_, handler)) = self
+                .callback_handlers
+                .iter_mut()
+                .find(|(t, _)| *t == type_id)
+            else {
+                warn!(concat!(
+                    "egui wants to paint a callback without a registered ",
+                    "handler. this request will be ignored."
+                ));
+                continue;
+            };
+            handler(
+                &CallbackContext {
+                    device_context,
+                    render_target,
+                    info: PaintCallbackInfo {
+                        viewport: callback.rect,
+                        clip_rect,
+                        pixels_per_point: scale_factor * zoom_factor,
+                        screen_size_px: [screen_size.0, screen_size.1],
+                    },
+                },
+                &*callback.callback,
+            );
+        }
+    }
+
+    /// Same as [`Renderer::render`], but returns the paint callbacks
+    /// requested by `egui` (see [`egui::PaintCallback`]) in draw order
+    /// instead of warning about and dropping them.
+    ///
+    /// The callbacks are not executed. All meshes are drawn before this
+    /// function returns, so *you* may execute the callbacks afterwards,
+    /// e.g. by downcasting [`egui::PaintCallback::callback`] to a type of
+    /// your own and drawing into the clip rectangle on top of `egui`.
+    pub fn render_collecting_callbacks(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<Vec<CollectedCallback>> {
+        self.collected_callbacks = Some(Vec::new());
+        let result = self.render(
+            device_context,
+            render_target,
+            egui_ctx,
+            egui_output,
+            scale_factor,
+        );
+        let callbacks = self.collected_callbacks.take().unwrap_or_default();
+        result.map(|_| callbacks)
+    }
+}
@@ -0,0 +1,295 @@
+use crate::{Renderer, RendererOutput};
+
+use egui::ecolor::{gamma_u8_from_linear_f32, linear_f32_from_gamma_u8};
+
+use windows::{
+    core::{Error, Result},
+    Win32::{
+        Foundation::E_INVALIDARG,
+        Graphics::{Direct3D11::*, Dxgi::Common::*},
+    },
+};
+
+impl Renderer {
+    /// Render the output of `egui` into a new image of `size` in pixels and
+    /// encode it as a PNG file, e.g. to attach a screenshot to a bug report.
+    /// Available with the `png` feature.
+    ///
+    /// `egui` is rendered into a temporary `DXGI_FORMAT_R8G8B8A8_UNORM_SRGB`
+    /// texture cleared to transparent black with [`Renderer::render`], which
+    /// is then read back to the CPU, waiting for the GPU to finish. The
+    /// colors are converted to straight alpha as PNG expects, and encoded
+    /// without compression to keep this crate free of further dependencies,
+    /// so the files are large but decode with any PNG decoder.
+    ///
+    /// An error with `E_INVALIDARG` is returned if `size` is empty.
+    pub fn capture_png(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        size: (u32, u32),
+        scale_factor: f32,
+    ) -> Result<Vec<u8>> {
+        let (width, height) = size;
+        if width == 0 || height == 0 {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "the size of the image must not be empty.",
+            ));
+        }
+
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: width,
+            Height: height,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_RENDER_TARGET.0 as _,
+            ..Default::default()
+        };
+        let mut texture = None;
+        let mut staging = None;
+        unsafe {
+            self.device
+                .CreateTexture2D(&desc, None, Some(&mut texture))?;
+            self.device.CreateTexture2D(
+                &D3D11_TEXTURE2D_DESC {
+                    Usage: D3D11_USAGE_STAGING,
+                    BindFlags: 0,
+                    CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as _,
+                    ..desc
+                },
+                None,
+                Some(&mut staging),
+            )?;
+        }
+        let (texture, staging) = (texture.unwrap(), staging.unwrap());
+
+        let mut render_target = None;
+        unsafe {
+            self.device.CreateRenderTargetView(
+                &texture,
+                None,
+                Some(&mut render_target),
+            )
+        }?;
+        let render_target = render_target.unwrap();
+        unsafe {
+            device_context.ClearRenderTargetView(&render_target, &[0.; 4])
+        };
+        self.render(
+            device_context,
+            &render_target,
+            egui_ctx,
+            egui_output,
+            scale_factor,
+        )?;
+        unsafe { device_context.CopyResource(&staging, &texture) };
+
+        let row_len = width as usize * 4;
+        let mut pixels = Vec::with_capacity(row_len * height as usize);
+        unsafe {
+            let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+            device_context.Map(
+                &staging,
+                0,
+                D3D11_MAP_READ,
+                0,
+                Some(&mut mapped),
+            )?;
+            for y in 0..height as usize {
+                let row = std::slice::from_raw_parts(
+                    (mapped.pData as *const u8)
+                        .add(y * mapped.RowPitch as usize),
+                    row_len,
+                );
+                pixels.extend_from_slice(row);
+            }
+            device_context.Unmap(&staging, 0);
+        }
+        for pixel in pixels.chunks_exact_mut(4) {
+            unpremultiply(pixel);
+        }
+        Ok(encode_png(width, height, &pixels))
+    }
+}
+
+/// Convert a gamma-encoded pixel holding linear premultiplied colors into a
+/// pixel with straight alpha.
+fn unpremultiply(pixel: &mut [u8]) {
+    let a = pixel[3];
+    if a == 0 || a == 255 {
+        return;
+    }
+    let a = a as f32 / 255.;
+    for c in &mut pixel[..3] {
+        *c = gamma_u8_from_linear_f32(linear_f32_from_gamma_u8(*c) / a);
+    }
+}
+
+/// Encode 8-bit RGBA pixels as a PNG file whose image data is stored in
+/// uncompressed deflate blocks.
+fn encode_png(width: u32, height: u32, pixels: &[u8]) -> Vec<u8> {
+    // each row is prefixed with filter type 0 (none).
+    let row_len = width as usize * 4;
+    let mut raw = Vec::with_capacity((row_len + 1) * height as usize);
+    for row in pixels.chunks_exact(row_len) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+
+    let mut zlib = vec![0x78, 0x01];
+    let mut blocks = raw.chunks(u16::MAX as usize).peekable();
+    while let Some(block) = blocks.next() {
+        let len = block.len() as u16;
+        zlib.push(blocks.peek().is_none() as u8);
+        zlib.extend_from_slice(&len.to_le_bytes());
+        zlib.extend_from_slice(&(!len).to_le_bytes());
+        zlib.extend_from_slice(block);
+    }
+    zlib.extend_from_slice(&adler32(&raw).to_be_bytes());
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // 8 bits per channel, RGBA, default compression, filtering and no
+    // interlacing.
+    ihdr.extend_from_slice(&[8, 6, 0, 0, 0]);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    write_chunk(&mut png, b"IHDR", &ihdr);
+    write_chunk(&mut png, b"IDAT", &zlib);
+    write_chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn write_chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend_from_slice(kind);
+    png.extend_from_slice(data);
+    let crc = crc32(&png[start..]);
+    png.extend_from_slice(&crc.to_be_bytes());
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = u32::MAX;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for &byte in data {
+        a = (a + byte as u32) % 65521;
+        b = (b + a) % 65521;
+    }
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Decode a PNG file written by `encode_png`, checking every checksum.
+    /// Only stored deflate blocks and filter type 0 are supported.
+    fn decode_png(png: &[u8]) -> (u32, u32, Vec<u8>) {
+        assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+        let be32 = |b: &[u8]| u32::from_be_bytes(b[..4].try_into().unwrap());
+        let (mut ihdr, mut idat, mut rest) = (None, Vec::new(), &png[8..]);
+        loop {
+            let len = be32(rest) as usize;
+            let (kind, data) = (&rest[4..8], &rest[8..8 + len]);
+            assert_eq!(be32(&rest[8 + len..]), crc32(&rest[4..8 + len]));
+            rest = &rest[12 + len..];
+            match kind {
+                b"IHDR" => ihdr = Some(data.to_vec()),
+                b"IDAT" => idat.extend_from_slice(data),
+                b"IEND" => break,
+                _ => panic!("unexpected chunk {kind:?}"),
+            }
+        }
+        assert!(rest.is_empty());
+        let ihdr = ihdr.unwrap();
+        let (width, height) = (be32(&ihdr), be32(&ihdr[4..]));
+        assert_eq!(ihdr[8..], [8, 6, 0, 0, 0]);
+
+        // zlib header, a compression method of 8 and a valid check value.
+        assert_eq!(idat[0] & 0xf, 8);
+        assert_eq!(u16::from_be_bytes([idat[0], idat[1]]) % 31, 0);
+        let (mut raw, mut pos) = (Vec::new(), 2);
+        loop {
+            let last = idat[pos] & 1 != 0;
+            assert_eq!(idat[pos] >> 1, 0, "only stored blocks are supported");
+            let len = u16::from_le_bytes([idat[pos + 1], idat[pos + 2]]);
+            let nlen = u16::from_le_bytes([idat[pos + 3], idat[pos + 4]]);
+            assert_eq!(len, !nlen);
+            pos += 5;
+            raw.extend_from_slice(&idat[pos..pos + len as usize]);
+            pos += len as usize;
+            if last {
+                break;
+            }
+        }
+        assert_eq!(be32(&idat[pos..]), adler32(&raw));
+        assert_eq!(idat.len(), pos + 4);
+
+        let row_len = width as usize * 4;
+        assert_eq!(raw.len(), (row_len + 1) * height as usize);
+        let mut pixels = Vec::new();
+        for row in raw.chunks_exact(row_len + 1) {
+            assert_eq!(row[0], 0);
+            pixels.extend_from_slice(&row[1..]);
+        }
+        (width, height, pixels)
+    }
+
+    #[test]
+    fn checksums_match_reference_values() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b""), 1);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+
+    #[test]
+    fn encode_png_round_trips() {
+        let pixels: Vec<u8> = (0..3 * 2 * 4).map(|i| i as u8 * 10).collect();
+        assert_eq!(decode_png(&encode_png(3, 2, &pixels)), (3, 2, pixels));
+    }
+
+    #[test]
+    fn encode_png_splits_large_images_into_blocks() {
+        // more than one stored deflate block of at most 65535 bytes.
+        let (width, height) = (200, 100);
+        let pixels: Vec<u8> =
+            (0..width * height * 4).map(|i| (i % 251) as u8).collect();
+        let png = encode_png(width, height, &pixels);
+        assert_eq!(decode_png(&png), (width, height, pixels));
+    }
+
+    #[test]
+    fn unpremultiply_keeps_opaque_and_transparent_pixels() {
+        let mut opaque = [10, 20, 30, 255];
+        unpremultiply(&mut opaque);
+        assert_eq!(opaque, [10, 20, 30, 255]);
+        let mut transparent = [0, 0, 0, 0];
+        unpremultiply(&mut transparent);
+        assert_eq!(transparent, [0; 4]);
+    }
+}
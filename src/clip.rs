@@ -0,0 +1,95 @@
+use crate::{DebugMode, Renderer};
+
+use egui::Rect;
+
+use windows::{
+    core::Result,
+    Win32::{Foundation::RECT, Graphics::Direct3D11::*},
+};
+
+/// The constant buffer bound to slot 0 of the pixel shader stage with
+/// [`RenderOptions::rounded_clip`](crate::RenderOptions::rounded_clip), see
+/// `ps_main_rounded_clip` in `shaders/egui.hlsl`.
+#[repr(C)]
+struct RoundedClip {
+    rect: [f32; 4],
+    radius: f32,
+    _padding: [f32; 3],
+}
+
+impl Renderer {
+    /// Whether clip rectangles are bound for the pixel shader to round their
+    /// corners. The built-in pixel shader is used in debug modes.
+    pub(crate) fn rounded_clip_enabled(&self) -> bool {
+        !self.options.rounded_clip.is_empty()
+            && self.options.debug_mode == DebugMode::None
+    }
+
+    /// Get the corner radius in points of `clip_rect`, which is `0.0` unless
+    /// it is listed in
+    /// [`RenderOptions::rounded_clip`](crate::RenderOptions::rounded_clip).
+    pub(crate) fn clip_radius(&self, clip_rect: Rect) -> f32 {
+        self.options
+            .rounded_clip
+            .iter()
+            .find(|&&(rect, _)| rect == clip_rect)
+            .map_or(0., |&(_, radius)| radius.max(0.))
+    }
+
+    /// Upload the clip rectangle `scissor` with corner `radius` into the
+    /// constant buffer of the pixel shader and bind it, unless it is already
+    /// bound.
+    pub(crate) fn bind_rounded_clip(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        scissor: RECT,
+        radius: f32,
+    ) -> Result<()> {
+        if self.rounded_clip_bound == Some((scissor, radius)) {
+            return Ok(());
+        }
+        if self.rounded_clip_buffer.is_none() {
+            let mut buffer = None;
+            unsafe {
+                self.device.CreateBuffer(
+                    &D3D11_BUFFER_DESC {
+                        ByteWidth: size_of::<RoundedClip>() as _,
+                        Usage: D3D11_USAGE_DYNAMIC,
+                        BindFlags: D3D11_BIND_CONSTANT_BUFFER.0 as _,
+                        CPUAccessFlags: D3D11_CPU_ACCESS_WRITE.0 as _,
+                        ..D3D11_BUFFER_DESC::default()
+                    },
+                    None,
+                    Some(&mut buffer),
+                )
+            }?;
+            self.rounded_clip_buffer = buffer;
+        }
+        let buffer = self.rounded_clip_buffer.as_ref().unwrap();
+        unsafe {
+            let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+            device_context.Map(
+                buffer,
+                0,
+                D3D11_MAP_WRITE_DISCARD,
+                0,
+                Some(&mut mapped),
+            )?;
+            (mapped.pData as *mut RoundedClip).write(RoundedClip {
+                rect: [
+                    scissor.left as _,
+                    scissor.top as _,
+                    scissor.right as _,
+                    scissor.bottom as _,
+                ],
+                radius,
+                _padding: [0.; 3],
+            });
+            device_context.Unmap(buffer, 0);
+            device_context
+                .PSSetConstantBuffers(0, Some(&[Some(buffer.clone())]));
+        }
+        self.rounded_clip_bound = Some((scissor, radius));
+        Ok(())
+    }
+}
@@ -0,0 +1,331 @@
+use crate::{rect_size, IndexData, MeshData, Renderer, Transform};
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use egui::TextureId;
+
+use windows::Win32::Foundation::RECT;
+
+/// A mesh drawn in a frame, identified by a hash of everything affecting its
+/// appearance.
+struct DrawnMesh {
+    hash: u64,
+    bounds: RECT,
+    tex: TextureId,
+}
+
+/// Tracks the meshes drawn in the previous frame to find the region of the
+/// render target which changed since then.
+#[derive(Default)]
+pub struct DamageTracker {
+    viewport: RECT,
+    meshes: Vec<DrawnMesh>,
+    changed_textures: Vec<TextureId>,
+    damage: Option<RECT>,
+}
+
+impl DamageTracker {
+    /// Mark textures as changed, so that all meshes sampling them are
+    /// considered damaged in the next update.
+    pub fn invalidate_textures(
+        &mut self,
+        textures: impl IntoIterator<Item = TextureId>,
+    ) {
+        self.changed_textures.extend(textures);
+    }
+
+    /// Update the damage rectangle with the meshes drawn into `viewport` in
+    /// this frame, whose vertex positions are mapped into normalized device
+    /// coordinates by `transform`. If `enabled` is `false`, the whole
+    /// viewport is considered damaged.
+    pub fn update(
+        &mut self,
+        viewport: RECT,
+        meshes: &[MeshData],
+        transform: Transform,
+        enabled: bool,
+    ) {
+        let changed_textures = std::mem::take(&mut self.changed_textures);
+        if !enabled || viewport != self.viewport {
+            self.viewport = viewport;
+            self.meshes.clear();
+            self.damage = Some(viewport);
+            if !enabled {
+                return;
+            }
+        } else {
+            self.damage = None;
+        }
+
+        let new: Vec<_> = meshes
+            .iter()
+            .map(|mesh| DrawnMesh {
+                hash: mesh_hash(mesh),
+                bounds: mesh_bounds(mesh, transform, viewport),
+                tex: mesh.tex,
+            })
+            .collect();
+
+        // a mesh is damaged if it is not drawn the same number of times in
+        // both frames. reordering identical meshes is not detected, which is
+        // not expected to happen with the output of `egui`.
+        let count = |meshes: &[DrawnMesh]| {
+            let mut counts = HashMap::<u64, usize>::new();
+            for mesh in meshes {
+                *counts.entry(mesh.hash).or_default() += 1;
+            }
+            counts
+        };
+        let (old_counts, new_counts) = (count(&self.meshes), count(&new));
+        let mut damage = self.damage;
+        for mesh in self.meshes.iter().chain(&new) {
+            if old_counts.get(&mesh.hash) != new_counts.get(&mesh.hash)
+                || changed_textures.contains(&mesh.tex)
+            {
+                damage = union(damage, mesh.bounds);
+            }
+        }
+        self.damage = damage;
+        self.meshes = new;
+    }
+
+    /// Scale the damage rectangle down by `scale` and move it into
+    /// `viewport`, for a frame rendered into an internal render target scaled
+    /// by `scale` relative to `viewport`.
+    pub fn downscale(&mut self, scale: f32, viewport: RECT) {
+        self.damage = self.damage.map(|rect| RECT {
+            left: viewport.left + (rect.left as f32 / scale).floor() as i32,
+            top: viewport.top + (rect.top as f32 / scale).floor() as i32,
+            right: (viewport.left + (rect.right as f32 / scale).ceil() as i32)
+                .min(viewport.right),
+            bottom: (viewport.top + (rect.bottom as f32 / scale).ceil() as i32)
+                .min(viewport.bottom),
+        });
+    }
+}
+
+fn mesh_hash(mesh: &MeshData) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for vtx in &mesh.vtx {
+        for f in [vtx.pos.x, vtx.pos.y, vtx.uv.x, vtx.uv.y] {
+            f.to_bits().hash(&mut hasher);
+        }
+        for f in vtx.color.to_array() {
+            f.to_bits().hash(&mut hasher);
+        }
+    }
+    match &mesh.idx {
+        IndexData::U16(idx) => idx.hash(&mut hasher),
+        IndexData::U32(idx) => idx.hash(&mut hasher),
+    }
+    mesh.tex.hash(&mut hasher);
+    mesh.topology.hash(&mut hasher);
+    let RECT {
+        left,
+        top,
+        right,
+        bottom,
+    } = mesh.scissor;
+    (left, top, right, bottom).hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Get the bounds of a mesh drawn into `viewport` in pixels, clipped by its
+/// scissor rectangle.
+fn mesh_bounds(mesh: &MeshData, transform: Transform, viewport: RECT) -> RECT {
+    let (width, height) = rect_size(&viewport);
+    let (mut min_x, mut min_y) = (f32::INFINITY, f32::INFINITY);
+    let (mut max_x, mut max_y) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+    for vtx in &mesh.vtx {
+        let pos = transform.apply(vtx.pos);
+        let x = viewport.left as f32 + (pos.x + 1.) / 2. * width as f32;
+        let y = viewport.top as f32 + (1. - pos.y) / 2. * height as f32;
+        (min_x, min_y) = (min_x.min(x), min_y.min(y));
+        (max_x, max_y) = (max_x.max(x), max_y.max(y));
+    }
+    RECT {
+        left: (min_x.floor() as i32).max(mesh.scissor.left),
+        top: (min_y.floor() as i32).max(mesh.scissor.top),
+        right: (max_x.ceil() as i32).min(mesh.scissor.right),
+        bottom: (max_y.ceil() as i32).min(mesh.scissor.bottom),
+    }
+}
+
+fn union(rect: Option<RECT>, other: RECT) -> Option<RECT> {
+    if other.left >= other.right || other.top >= other.bottom {
+        return rect;
+    }
+    Some(match rect {
+        Some(rect) => RECT {
+            left: rect.left.min(other.left),
+            top: rect.top.min(other.top),
+            right: rect.right.max(other.right),
+            bottom: rect.bottom.max(other.bottom),
+        },
+        None => other,
+    })
+}
+
+impl Renderer {
+    /// Get the region of the render target which changed in the last frame
+    /// rendered compared to the frame before it, in pixels, or `None` if
+    /// nothing changed. The caller may restrict the clearing and presenting
+    /// of the next frame to this region, e.g. with `Present1`.
+    ///
+    /// If [`RenderOptions::damage_tracking`](crate::RenderOptions::damage_tracking)
+    /// is disabled, which is the default, the whole render target (or
+    /// [`RenderOptions::viewport`](crate::RenderOptions::viewport), if set) is
+    /// always reported. The first frame, and any frame whose render target or
+    /// viewport differs from the previous one, are also reported as fully
+    /// damaged.
+    ///
+    /// Changes are detected by comparing the meshes drawn in both frames, and
+    /// meshes sampling a texture updated by `egui` are always considered
+    /// changed. Changes *not* detected include changes to the contents of
+    /// user textures and to options or shaders affecting all meshes alike,
+    /// e.g. [`Renderer::set_pixel_shader`], after which you should redraw the
+    /// whole render target yourself.
+    pub fn damage_rect(&self) -> Option<RECT> {
+        self.damage.damage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DisplayRotation, MeshTopology, VertexData};
+
+    use egui::{Pos2, Rgba};
+
+    // a power of two, so that mapping positions into normalized device
+    // coordinates and back is exact.
+    const VIEWPORT: RECT = RECT {
+        left: 0,
+        top: 0,
+        right: 128,
+        bottom: 128,
+    };
+
+    /// A triangle covering the pixels from `min` to `max`.
+    fn mesh(min: (f32, f32), max: (f32, f32), tex: TextureId) -> MeshData {
+        let vtx = |x, y| VertexData {
+            pos: Pos2::new(x, y),
+            uv: Pos2::ZERO,
+            color: Rgba::WHITE,
+        };
+        MeshData {
+            vtx: vec![vtx(min.0, min.1), vtx(max.0, min.1), vtx(min.0, max.1)],
+            idx: IndexData::U16(vec![0, 1, 2]),
+            tex,
+            scissor: VIEWPORT,
+            clip_radius: 0.,
+            topology: MeshTopology::TriangleList,
+        }
+    }
+
+    fn update(tracker: &mut DamageTracker, meshes: &[MeshData], enabled: bool) {
+        let transform =
+            Transform::to_ndc(1., DisplayRotation::None, (128., 128.));
+        tracker.update(VIEWPORT, meshes, transform, enabled);
+    }
+
+    #[test]
+    fn first_frame_is_fully_damaged() {
+        let mut tracker = DamageTracker::default();
+        update(
+            &mut tracker,
+            &[mesh((10., 10.), (20., 20.), TextureId::default())],
+            true,
+        );
+        assert_eq!(tracker.damage, Some(VIEWPORT));
+    }
+
+    #[test]
+    fn unchanged_frame_is_not_damaged() {
+        let mut tracker = DamageTracker::default();
+        let tex = TextureId::default();
+        update(&mut tracker, &[mesh((10., 10.), (20., 20.), tex)], true);
+        update(&mut tracker, &[mesh((10., 10.), (20., 20.), tex)], true);
+        assert_eq!(tracker.damage, None);
+    }
+
+    #[test]
+    fn moved_mesh_damages_both_bounds() {
+        let mut tracker = DamageTracker::default();
+        let tex = TextureId::default();
+        update(&mut tracker, &[mesh((10., 10.), (20., 20.), tex)], true);
+        update(&mut tracker, &[mesh((30., 40.), (50., 60.), tex)], true);
+        assert_eq!(
+            tracker.damage,
+            Some(RECT {
+                left: 10,
+                top: 10,
+                right: 50,
+                bottom: 60,
+            })
+        );
+    }
+
+    #[test]
+    fn changed_texture_damages_its_meshes() {
+        let mut tracker = DamageTracker::default();
+        let (font, user) = (TextureId::default(), TextureId::User(0));
+        let meshes = [
+            mesh((10., 10.), (20., 20.), font),
+            mesh((60., 60.), (70., 80.), user),
+        ];
+        update(&mut tracker, &meshes, true);
+        tracker.invalidate_textures([user]);
+        update(&mut tracker, &meshes, true);
+        assert_eq!(
+            tracker.damage,
+            Some(RECT {
+                left: 60,
+                top: 60,
+                right: 70,
+                bottom: 80,
+            })
+        );
+    }
+
+    #[test]
+    fn disabled_tracking_damages_everything() {
+        let mut tracker = DamageTracker::default();
+        let meshes = [mesh((10., 10.), (20., 20.), TextureId::default())];
+        update(&mut tracker, &meshes, false);
+        update(&mut tracker, &meshes, false);
+        assert_eq!(tracker.damage, Some(VIEWPORT));
+    }
+
+    #[test]
+    fn downscale_covers_scaled_damage() {
+        let mut tracker = DamageTracker {
+            damage: Some(RECT {
+                left: 3,
+                top: 4,
+                right: 9,
+                bottom: 200,
+            }),
+            ..Default::default()
+        };
+        let viewport = RECT {
+            left: 10,
+            top: 20,
+            right: 60,
+            bottom: 70,
+        };
+        tracker.downscale(2., viewport);
+        assert_eq!(
+            tracker.damage,
+            Some(RECT {
+                left: 11,
+                top: 22,
+                right: 15,
+                bottom: 70,
+            })
+        );
+    }
+}
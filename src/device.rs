@@ -0,0 +1,53 @@
+use crate::Renderer;
+
+use windows::{core::Interface, Win32::Graphics::Direct3D11::*};
+
+/// The newest `ID3D11Device` interface implemented by the device a
+/// [`Renderer`] was created with, as detected by [`Renderer::new`].
+/// Variants are ordered by version, so that e.g.
+/// `version >= DeviceVersion::Device1` checks for Direct3D 11.1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DeviceVersion {
+    /// `ID3D11Device`, i.e. Direct3D 11.0.
+    Device,
+    /// `ID3D11Device1`, i.e. Direct3D 11.1, with `ID3D11DeviceContext1`
+    /// providing `DiscardView`.
+    Device1,
+    /// `ID3D11Device2`, i.e. Direct3D 11.2.
+    Device2,
+    /// `ID3D11Device3`, i.e. Direct3D 11.3, providing conservative
+    /// rasterization if the hardware supports it.
+    Device3,
+    /// `ID3D11Device4`.
+    Device4,
+    /// `ID3D11Device5`.
+    Device5,
+}
+
+impl DeviceVersion {
+    pub(crate) fn detect(device: &ID3D11Device) -> Self {
+        if device.cast::<ID3D11Device5>().is_ok() {
+            Self::Device5
+        } else if device.cast::<ID3D11Device4>().is_ok() {
+            Self::Device4
+        } else if device.cast::<ID3D11Device3>().is_ok() {
+            Self::Device3
+        } else if device.cast::<ID3D11Device2>().is_ok() {
+            Self::Device2
+        } else if device.cast::<ID3D11Device1>().is_ok() {
+            Self::Device1
+        } else {
+            Self::Device
+        }
+    }
+}
+
+impl Renderer {
+    /// Get the newest `ID3D11Device` interface implemented by the device,
+    /// which determines the features available on top of Direct3D 11.0.
+    /// Features requiring a newer interface fall back as documented where
+    /// it is unavailable.
+    pub fn device_version(&self) -> DeviceVersion {
+        self.device_version
+    }
+}
@@ -0,0 +1,130 @@
+use crate::Renderer;
+
+use egui::TextureId;
+
+use windows::{
+    core::{Error, Result},
+    Win32::{
+        Foundation::E_INVALIDARG,
+        Graphics::{Direct3D11::*, Dxgi::Common::*},
+    },
+};
+
+/// A pair of render targets for feeding the output of `egui` back into it,
+/// e.g. for feedback trails. Each frame is rendered into one target while the
+/// other one, holding the previous frame, is sampled as a user texture, and
+/// the targets are swapped afterwards.
+///
+/// [`Renderer`] never keeps a texture bound as a shader resource while
+/// binding a render target, so the targets can be ping-ponged without
+/// read-write hazards. Make sure your own pipeline does the same when e.g.
+/// presenting the targets.
+pub struct FeedbackTargets {
+    targets: [(ID3D11RenderTargetView, ID3D11ShaderResourceView); 2],
+    current: usize,
+    texture_id: TextureId,
+}
+
+impl FeedbackTargets {
+    /// Create a pair of render targets of `size` and `format`, cleared to
+    /// transparent black, and register the one holding the previous frame
+    /// with `renderer` as a user texture holding premultiplied colors, like
+    /// the output of `egui`. Unregister it with
+    /// [`Renderer::unregister_user_texture`] when the targets are dropped.
+    ///
+    /// An error with `E_INVALIDARG` is returned if `size` is empty.
+    pub fn new(
+        renderer: &mut Renderer,
+        device_context: &ID3D11DeviceContext,
+        size: (u32, u32),
+        format: DXGI_FORMAT,
+    ) -> Result<Self> {
+        if size.0 == 0 || size.1 == 0 {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "the size of the feedback targets must not be empty.",
+            ));
+        }
+        let targets = [
+            Self::create_target(&renderer.device, size, format)?,
+            Self::create_target(&renderer.device, size, format)?,
+        ];
+        for (rtv, _) in &targets {
+            unsafe { device_context.ClearRenderTargetView(rtv, &[0.; 4]) };
+        }
+        let texture_id =
+            renderer.register_user_texture_premultiplied(targets[1].1.clone());
+        Ok(Self {
+            targets,
+            current: 0,
+            texture_id,
+        })
+    }
+
+    fn create_target(
+        device: &ID3D11Device,
+        size: (u32, u32),
+        format: DXGI_FORMAT,
+    ) -> Result<(ID3D11RenderTargetView, ID3D11ShaderResourceView)> {
+        let mut tex = None;
+        unsafe {
+            device.CreateTexture2D(
+                &D3D11_TEXTURE2D_DESC {
+                    Width: size.0,
+                    Height: size.1,
+                    MipLevels: 1,
+                    ArraySize: 1,
+                    Format: format,
+                    SampleDesc: DXGI_SAMPLE_DESC {
+                        Count: 1,
+                        Quality: 0,
+                    },
+                    Usage: D3D11_USAGE_DEFAULT,
+                    BindFlags: (D3D11_BIND_RENDER_TARGET.0
+                        | D3D11_BIND_SHADER_RESOURCE.0)
+                        as _,
+                    ..Default::default()
+                },
+                None,
+                Some(&mut tex),
+            )
+        }?;
+        let tex = tex.unwrap();
+
+        let mut rtv = None;
+        let mut srv = None;
+        unsafe {
+            device.CreateRenderTargetView(&tex, None, Some(&mut rtv))?;
+            device.CreateShaderResourceView(&tex, None, Some(&mut srv))?;
+        }
+        Ok((rtv.unwrap(), srv.unwrap()))
+    }
+
+    /// Get the render target to render the current frame into.
+    pub fn render_target(&self) -> &ID3D11RenderTargetView {
+        &self.targets[self.current].0
+    }
+
+    /// Get the shader resource view of the current frame, e.g. to present it
+    /// after rendering.
+    pub fn current_srv(&self) -> &ID3D11ShaderResourceView {
+        &self.targets[self.current].1
+    }
+
+    /// Get the id of the user texture sampling the previous frame, to be
+    /// displayed in `egui` (e.g. with [`egui::Image`]).
+    pub fn texture_id(&self) -> TextureId {
+        self.texture_id
+    }
+
+    /// Swap the targets after rendering a frame, so that the frame just
+    /// rendered is sampled through [`FeedbackTargets::texture_id`] while the
+    /// next frame is rendered into the other target.
+    pub fn swap(&mut self, renderer: &mut Renderer) {
+        renderer.texture_pool.replace_user_texture(
+            self.texture_id,
+            self.targets[self.current].1.clone(),
+        );
+        self.current ^= 1;
+    }
+}
@@ -0,0 +1,87 @@
+use crate::{Annotation, Renderer};
+
+use egui::{epaint::ClippedShape, TexturesDelta};
+
+use windows::{
+    core::{Error, Result},
+    Win32::{Foundation::E_INVALIDARG, Graphics::Direct3D11::*},
+};
+
+impl Renderer {
+    /// Begin a frame issuing several draws sharing one update of textures,
+    /// e.g. to draw different layers of `egui` to different render targets,
+    /// applying `textures_delta` once. Draw with [`Renderer::draw`] and
+    /// finish the frame with [`Renderer::end_frame`].
+    ///
+    /// An error with `E_INVALIDARG` is returned if a frame has already
+    /// begun.
+    pub fn begin_frame(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        textures_delta: &TexturesDelta,
+    ) -> Result<()> {
+        if self.in_frame {
+            return Err(Error::new(E_INVALIDARG, "a frame has already begun."));
+        }
+        self.update_textures(device_context, textures_delta)
+            .inspect_err(|_| self.notify_device_lost())?;
+        self.in_frame = true;
+        Ok(())
+    }
+
+    /// Draw `shapes` to the provided render target within a frame begun with
+    /// [`Renderer::begin_frame`], without updating textures. Other than
+    /// that, this function behaves like [`Renderer::render`], including the
+    /// pipeline state it overrides. `pixels_per_point` should be
+    /// [`RendererOutput::pixels_per_point`](crate::RendererOutput::pixels_per_point).
+    ///
+    /// An error with `E_INVALIDARG` is returned if no frame has begun.
+    pub fn draw(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        shapes: Vec<ClippedShape>,
+        pixels_per_point: f32,
+        scale_factor: f32,
+    ) -> Result<()> {
+        if !self.in_frame {
+            return Err(Error::new(E_INVALIDARG, "no frame has begun."));
+        }
+        let _annotation = Annotation::begin(
+            device_context,
+            self.annotation_prefix.as_deref(),
+            "egui-directx11: draw",
+        );
+        self.draw_shapes(
+            device_context,
+            render_target,
+            egui_ctx,
+            shapes,
+            pixels_per_point,
+            scale_factor,
+            true,
+        )
+        .inspect_err(|_| self.notify_device_lost())
+    }
+
+    /// Finish a frame begun with [`Renderer::begin_frame`], unbinding the
+    /// texture sampled last from the pixel shader stage so that it can be
+    /// bound as a render target afterwards.
+    ///
+    /// An error with `E_INVALIDARG` is returned if no frame has begun.
+    pub fn end_frame(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+    ) -> Result<()> {
+        if !self.in_frame {
+            return Err(Error::new(E_INVALIDARG, "no frame has begun."));
+        }
+        self.in_frame = false;
+        unsafe {
+            device_context
+                .PSSetShaderResources(self.options.srv_slot, Some(&[None]))
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,183 @@
+use crate::{Renderer, RendererOutput};
+
+use std::mem;
+
+use windows::{
+    core::Result,
+    Win32::{
+        Foundation::RECT,
+        Graphics::{Direct3D::*, Direct3D11::*, Dxgi::Common::*},
+    },
+};
+
+/// An intermediate render target holding gamma-encoded colors, which `egui`
+/// is rendered into by [`Renderer::render_to_srgb_target`].
+pub struct GammaTarget {
+    rtv: ID3D11RenderTargetView,
+    srv: ID3D11ShaderResourceView,
+    size: (u32, u32),
+}
+
+impl GammaTarget {
+    fn new(device: &ID3D11Device, size: (u32, u32)) -> Result<Self> {
+        let mut tex = None;
+        unsafe {
+            device.CreateTexture2D(
+                &D3D11_TEXTURE2D_DESC {
+                    Width: size.0,
+                    Height: size.1,
+                    MipLevels: 1,
+                    ArraySize: 1,
+                    Format: DXGI_FORMAT_R8G8B8A8_TYPELESS,
+                    SampleDesc: DXGI_SAMPLE_DESC {
+                        Count: 1,
+                        Quality: 0,
+                    },
+                    Usage: D3D11_USAGE_DEFAULT,
+                    BindFlags: (D3D11_BIND_RENDER_TARGET.0
+                        | D3D11_BIND_SHADER_RESOURCE.0)
+                        as _,
+                    ..Default::default()
+                },
+                None,
+                Some(&mut tex),
+            )
+        }?;
+        let tex = tex.unwrap();
+
+        // colors are written as is through the `_UNORM` view, and decoded
+        // into linear colors when sampled through the `_SRGB` view.
+        let mut rtv = None;
+        let mut srv = None;
+        unsafe {
+            device.CreateRenderTargetView(
+                &tex,
+                Some(&D3D11_RENDER_TARGET_VIEW_DESC {
+                    Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                    ViewDimension: D3D11_RTV_DIMENSION_TEXTURE2D,
+                    ..Default::default()
+                }),
+                Some(&mut rtv),
+            )?;
+            device.CreateShaderResourceView(
+                &tex,
+                Some(&D3D11_SHADER_RESOURCE_VIEW_DESC {
+                    Format: DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+                    ViewDimension: D3D_SRV_DIMENSION_TEXTURE2D,
+                    Anonymous: D3D11_SHADER_RESOURCE_VIEW_DESC_0 {
+                        Texture2D: D3D11_TEX2D_SRV {
+                            MostDetailedMip: 0,
+                            MipLevels: 1,
+                        },
+                    },
+                }),
+                Some(&mut srv),
+            )?;
+        }
+        Ok(Self {
+            rtv: rtv.unwrap(),
+            srv: srv.unwrap(),
+            size,
+        })
+    }
+}
+
+impl Renderer {
+    /// Same as [`Renderer::render`], but blends `egui` in gamma space like
+    /// the reference backends of `egui` do on non-sRGB render targets, and
+    /// composites the result into an `_SRGB` render target.
+    ///
+    /// `egui` is rendered into an intermediate render target in gamma color
+    /// space, with vertex colors and textures managed by `egui` kept
+    /// gamma-encoded, so that translucent shapes and anti-aliased edges
+    /// blend exactly as in `egui`'s own backends. The intermediate is then
+    /// blitted onto `render_target` through an `_SRGB` view with premultiplied
+    /// alpha blending. The intermediate has the size of `render_target` and
+    /// is cached across frames, being recreated whenever the size changes.
+    ///
+    /// [`RenderOptions::transfer_function`](crate::RenderOptions::transfer_function)
+    /// is ignored here. Once this function is used, a copy of each texture
+    /// managed by `egui` is kept in a non-sRGB format, doubling their memory
+    /// usage, until [`ColorSpace::Linear`](crate::ColorSpace::Linear) is set
+    /// with [`Renderer::set_color_space`] or [`Renderer::reset`] is called.
+    /// User textures are sampled as is and should thus be viewed in a
+    /// non-`_SRGB` format to keep their colors gamma-encoded.
+    pub fn render_to_srgb_target(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<()> {
+        self.render_to_gamma_target(
+            device_context,
+            render_target,
+            egui_ctx,
+            egui_output,
+            scale_factor,
+        )
+        .inspect_err(|_| self.notify_device_lost())
+    }
+
+    fn render_to_gamma_target(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<()> {
+        self.texture_pool.enable_gamma_views(device_context)?;
+        let size = Self::get_render_target_size(render_target)?;
+        if self.gamma_target.as_ref().is_none_or(|t| t.size != size) {
+            // drop the old target first to keep the peak memory usage low.
+            self.gamma_target = None;
+            self.gamma_target = Some(GammaTarget::new(&self.device, size)?);
+        }
+        let (gamma_rtv, gamma_srv) = {
+            let target = self.gamma_target.as_ref().unwrap();
+            (target.rtv.clone(), target.srv.clone())
+        };
+        unsafe { device_context.ClearRenderTargetView(&gamma_rtv, &[0.; 4]) };
+
+        // the stencil and color write masks are only applied when blitting,
+        // as in `Renderer::render_offscreen`, and so is unpremultiplying.
+        let stencil_mask = self.stencil_mask.take();
+        let color_write_mask = mem::replace(
+            &mut self.options.color_write_mask,
+            D3D11_COLOR_WRITE_ENABLE_ALL,
+        );
+        let straight_alpha_output =
+            mem::replace(&mut self.options.straight_alpha_output, false);
+        self.gamma_space = true;
+        let result = self.render_impl(
+            device_context,
+            &gamma_rtv,
+            egui_ctx,
+            &egui_output.textures_delta,
+            egui_output.shapes,
+            egui_output.pixels_per_point,
+            scale_factor,
+            true,
+        );
+        self.gamma_space = false;
+        self.options.straight_alpha_output = straight_alpha_output;
+        self.options.color_write_mask = color_write_mask;
+        self.stencil_mask = stencil_mask;
+        result?;
+        self.blit(
+            device_context,
+            render_target,
+            RECT {
+                left: 0,
+                top: 0,
+                right: size.0 as _,
+                bottom: size.1 as _,
+            },
+            &gamma_srv,
+            D3D11_FILTER_MIN_MAG_MIP_POINT,
+            true,
+        )
+    }
+}
@@ -23,42 +23,235 @@
 //! crate. You can also take a look at the [`egui-demo`](https://github.com/Nekomaru-PKU/egui-directx11/blob/main/examples/egui-demo.rs) example, which demonstrates all you need to do to set up a minimal application
 //! with Direct3D11 and `egui`. This example uses `winit` for window management
 //! and event handling, while native Win32 APIs should also work well.
+//!
+//! ## Features
+//!
+//! + `log` (enabled by default): emit warnings on invalid requests from
+//!   `egui` or invalid options through the [`log`](https://crates.io/crates/log)
+//!   crate. Disable it to drop the dependency.
+//! + `swap-chain`: provide the optional `SwapChain` helper.
+//! + `shared-texture`: provide `Renderer::render_to_shared_texture`.
+//! + `png`: provide `Renderer::capture_png` to render a screenshot into a
+//!   PNG file.
+//! + `win32`: provide the `win32` module with helpers for
+//!   applications using native Win32 APIs, including `InputCollector`
+//!   translating window messages into input for `egui` without
+//!   `egui_winit`.
+//! + `annotations`: wrap the calls issued by this crate in named events with
+//!   `ID3DUserDefinedAnnotation`, which show up in graphics debuggers and
+//!   frame profilers like PIX or RenderDoc.
+
+/// Log a warning with `log::warn!` if the `log` feature is enabled, or do
+/// nothing otherwise.
+macro_rules! warn {
+    ($($arg:tt)*) => {{
+        #[cfg(feature = "log")]
+        log::warn!($($arg)*);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)*);
+    }};
+}
+
+mod annotation;
+use annotation::Annotation;
+
+mod buffer;
+use buffer::DynamicBuffer;
+
+mod callback;
+use callback::CallbackHandler;
+pub use callback::{CallbackContext, CollectedCallback};
+
+#[cfg(feature = "png")]
+mod capture;
+
+mod clip;
+
+mod damage;
+use damage::DamageTracker;
+
+mod device;
+pub use device::DeviceVersion;
+
+mod feedback;
+pub use feedback::FeedbackTargets;
+
+mod frame;
+
+mod gamma;
+use gamma::GammaTarget;
+
+mod offscreen;
+use offscreen::{BlitStates, OffscreenMode, OffscreenTarget};
+
+mod options;
+pub use options::{
+    BlendMode, BufferStrategy, ColorSpace, DebugMode, DisplayRotation,
+    IndexFormat, MeshTopology, RenderOptions, TextureUploadStrategy,
+    TransferFunction,
+};
+
+mod pool;
+use pool::BufferPool;
+
+mod premultiplied;
+
+mod query;
+use query::OcclusionQueries;
+
+mod record;
+
+mod region;
+
+#[cfg(feature = "shared-texture")]
+mod shared;
+
+mod resolve;
+
+mod scratch;
+pub use scratch::RenderScratch;
+
+mod states;
+
+mod stats;
+pub use stats::RenderStats;
+
+mod stencil;
+pub use stencil::StencilMask;
+
+mod stepwise;
+
+#[cfg(feature = "swap-chain")]
+mod swap_chain;
+#[cfg(feature = "swap-chain")]
+pub use swap_chain::{SwapChain, SwapChainOptions};
+
+#[cfg(feature = "win32")]
+pub mod win32;
+
+mod transform;
+use transform::Transform;
+
+mod upload;
+pub use upload::{TextureUploader, TextureUploads};
 
 mod texture;
-use texture::TexturePool;
+pub use texture::{
+    MemoryReport, TextureCreatedCallback, TextureFreedCallback, TextureKind,
+    TexturePool, TextureUploadFailedCallback, TextureUploadPolicy,
+};
 
-use std::mem;
+use std::{any::TypeId, fmt, mem};
 
 const fn zeroed<T>() -> T {
     unsafe { mem::zeroed() }
 }
 
 use egui::{
-    epaint::{textures::TexturesDelta, ClippedShape, Primitive, Vertex},
+    epaint::{
+        textures::TexturesDelta, ClippedShape, Mesh, Mesh16, Primitive, Vertex,
+    },
     ClippedPrimitive, Pos2, Rgba,
 };
 
 use windows::{
-    core::{Interface, Result},
+    core::{Error, Interface, Result, HRESULT},
     Win32::{
-        Foundation::{BOOL, RECT},
+        Foundation::{BOOL, E_INVALIDARG, RECT},
         Graphics::{Direct3D::*, Direct3D11::*, Dxgi::Common::*},
     },
 };
 
 /// The core of this crate. You can set up a renderer via [`Renderer::new`]
 /// and render the output from `egui` with [`Renderer::render`].
+///
+/// When a [`Renderer`] is dropped, all textures managed by `egui` are freed
+/// explicitly, invoking the callback set with
+/// [`Renderer::set_on_texture_freed`], if any, for each of them. Note that
+/// Direct3D11 only releases the GPU memory of a texture once it is no longer
+/// bound to any device context, so *you* should not keep using a device
+/// context with stale bindings left by [`Renderer::render`] (see its
+/// documentation for the pipeline state it overrides), e.g. by calling
+/// `ClearState` on it after dropping the [`Renderer`].
 pub struct Renderer {
     device: ID3D11Device,
+    device_version: DeviceVersion,
 
     input_layout: ID3D11InputLayout,
     vertex_shader: ID3D11VertexShader,
+    transform_vertex_shader: ID3D11VertexShader,
     pixel_shader: ID3D11PixelShader,
+    mrt_pixel_shader: ID3D11PixelShader,
+    custom_pixel_shader: Option<ID3D11PixelShader>,
+    custom_vertex_shader: Option<ID3D11VertexShader>,
+    transform_buffer: Option<ID3D11Buffer>,
+    rounded_clip_pixel_shader: ID3D11PixelShader,
+    unpremultiply_pixel_shader: ID3D11PixelShader,
+    dual_source_pixel_shader: ID3D11PixelShader,
+    rounded_clip_buffer: Option<ID3D11Buffer>,
+    rounded_clip_bound: Option<(RECT, f32)>,
+    stencil_mask: Option<StencilMask>,
+    depth_stencil_state: Option<ID3D11DepthStencilState>,
+    custom_depth_stencil_state: Option<ID3D11DepthStencilState>,
     rasterizer_state: ID3D11RasterizerState,
+    rasterizer_desc: D3D11_RASTERIZER_DESC,
+    rasterizer_conservative: bool,
     sampler_state: ID3D11SamplerState,
+    sampler_desc: D3D11_SAMPLER_DESC,
+    custom_sampler_state: Option<ID3D11SamplerState>,
+    secondary_texture: Option<(u32, ID3D11ShaderResourceView)>,
+    point_sampler_state: ID3D11SamplerState,
+    texture_samplers: Vec<(egui::TextureOptions, ID3D11SamplerState)>,
     blend_state: ID3D11BlendState,
+    straight_blend_state: ID3D11BlendState,
+    straight_bound: bool,
+    topology_bound: MeshTopology,
+    blend_desc: D3D11_BLEND_DESC,
 
     texture_pool: TexturePool,
+
+    options: RenderOptions,
+    offscreen_target: Option<OffscreenTarget>,
+    gamma_target: Option<GammaTarget>,
+    gamma_space: bool,
+    /// Whether more than one render target is bound by
+    /// [`Renderer::render_to_multiple_targets`].
+    multiple_targets: bool,
+    collected_callbacks: Option<Vec<CollectedCallback>>,
+    callback_handlers: Vec<(TypeId, CallbackHandler)>,
+    in_frame: bool,
+    annotation_prefix: Option<String>,
+    blit_states: Option<BlitStates>,
+    dynamic_vertex_buffer: Option<DynamicBuffer>,
+    dynamic_index_buffer: Option<DynamicBuffer>,
+    buffer_pool: BufferPool,
+    occlusion_queries: OcclusionQueries,
+    damage: DamageTracker,
+    stats: RenderStats,
+    textures_changed: bool,
+    scratch: RenderScratch,
+
+    on_device_lost: Option<DeviceLostCallback>,
+}
+
+/// Callback invoked with the reason of device removal when
+/// [`Renderer::render`] fails because the device has been removed. See
+/// [`Renderer::set_on_device_lost`].
+pub type DeviceLostCallback = Box<dyn FnMut(HRESULT) + Send + Sync>;
+
+impl fmt::Debug for Renderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Renderer")
+            .field("textures", &self.texture_pool.len())
+            .field("options", &self.options)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Drop for Renderer {
+    fn drop(&mut self) {
+        self.texture_pool.clear_managed();
+    }
 }
 
 /// Part of [`egui::FullOutput`] that is consumed by [`Renderer::render`].
@@ -77,6 +270,45 @@ pub struct RendererOutput {
     pub pixels_per_point: f32,
 }
 
+impl RendererOutput {
+    /// Merge `other` into this output, e.g. to render the outputs of several
+    /// passes of `egui` in a single call to [`Renderer::render`].
+    ///
+    /// The shapes of `other` are appended, so that they are painted on top
+    /// of the shapes of this output. The texture deltas of `other` are
+    /// applied after those of this output, so that later deltas win. If the
+    /// `pixels_per_point` of both outputs differ, an error with
+    /// `E_INVALIDARG` is returned and this output is left unchanged.
+    pub fn merge(&mut self, other: RendererOutput) -> Result<()> {
+        if self.pixels_per_point != other.pixels_per_point {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "outputs with different pixels_per_point can not be merged.",
+            ));
+        }
+        self.shapes.extend(other.shapes);
+        // a texture freed by this output and set again by `other` must not
+        // be freed after the set is applied.
+        self.textures_delta.free.retain(|tid| {
+            !other.textures_delta.set.iter().any(|(set, _)| set == tid)
+        });
+        self.textures_delta.set.extend(other.textures_delta.set);
+        self.textures_delta.free.extend(other.textures_delta.free);
+        Ok(())
+    }
+}
+
+impl fmt::Debug for RendererOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RendererOutput")
+            .field("shapes", &self.shapes.len())
+            .field("texture_deltas", &self.textures_delta.set.len())
+            .field("textures_freed", &self.textures_delta.free.len())
+            .field("pixels_per_point", &self.pixels_per_point)
+            .finish()
+    }
+}
+
 /// Convenience method to split a [`egui::FullOutput`] into the
 /// [`RendererOutput`] part and other parts for platform integration.
 pub fn split_output(
@@ -97,6 +329,127 @@ pub fn split_output(
     )
 }
 
+/// Convert a clip rectangle in physical pixels into a scissor rectangle for a
+/// render target of `target_size`.
+///
+/// The edges of the clip rectangle are rounded to the nearest pixel, and the
+/// result is intersected with the bounds of the render target. Inverted
+/// rectangles are treated as empty. Returns `None` if the resulting scissor
+/// rectangle is empty, in which case nothing should be drawn.
+pub fn clip_rect_to_scissor(
+    rect: egui::Rect,
+    target_size: (u32, u32),
+) -> Option<RECT> {
+    let (width, height) = (target_size.0 as f32, target_size.1 as f32);
+    let left = rect.left().round().max(0.).min(width);
+    let top = rect.top().round().max(0.).min(height);
+    let right = rect.right().round().min(width);
+    let bottom = rect.bottom().round().min(height);
+    // this also rejects rectangles with NaN edges.
+    if !(left < right && top < bottom) {
+        return None;
+    }
+    Some(RECT {
+        left: left as _,
+        top: top as _,
+        right: right as _,
+        bottom: bottom as _,
+    })
+}
+
+/// Collect the meshes to draw from primitives tessellated by `egui`. Paint
+/// callbacks are pushed to `callbacks` if provided, and dropped otherwise.
+fn clipped_meshes(
+    primitives: impl IntoIterator<Item = ClippedPrimitive>,
+    mut callbacks: Option<&mut Vec<CollectedCallback>>,
+) -> Vec<ClippedMesh> {
+    primitives
+        .into_iter()
+        .enumerate()
+        .filter_map(
+            |(
+                index,
+                ClippedPrimitive {
+                    primitive,
+                    clip_rect,
+                },
+            )| match primitive {
+                Primitive::Mesh(mesh) => Some(ClippedMesh {
+                    clip_rect,
+                    vertices: mesh.vertices,
+                    indices: IndexData::U32(mesh.indices),
+                    texture_id: mesh.texture_id,
+                    topology: MeshTopology::TriangleList,
+                }),
+                Primitive::Callback(callback) => {
+                    if let Some(callbacks) = callbacks.as_deref_mut() {
+                        callbacks.push(CollectedCallback {
+                            index,
+                            clip_rect,
+                            callback,
+                        });
+                    } else {
+                        warn!("paint callbacks are not yet supported.");
+                    }
+                    None
+                },
+            },
+        )
+        .collect()
+}
+
+/// Get a mesh filling the screen of `egui_ctx` with the window fill color of
+/// its visuals.
+fn background_mesh(egui_ctx: &egui::Context) -> ClippedMesh {
+    let screen_rect = egui_ctx.screen_rect();
+    let mut mesh = Mesh::default();
+    mesh.add_colored_rect(screen_rect, egui_ctx.style().visuals.window_fill);
+    ClippedMesh {
+        clip_rect: screen_rect,
+        vertices: mesh.vertices,
+        indices: IndexData::U32(mesh.indices),
+        texture_id: mesh.texture_id,
+        topology: MeshTopology::TriangleList,
+    }
+}
+
+/// Unpremultiply a vertex color of a mesh sampling a texture with straight
+/// alpha, which is blended with [`Renderer::straight_blend_desc`] multiplying
+/// the output by alpha. A premultiplied vertex color would be multiplied by
+/// its alpha twice. Additive colors with an alpha of zero are kept as is, and
+/// thus have no effect.
+fn unpremultiply(color: Rgba) -> Rgba {
+    let [r, g, b, a] = color.to_rgba_unmultiplied();
+    // the channels are stored as is.
+    Rgba::from_rgba_premultiplied(r, g, b, a)
+}
+
+/// Drop the meshes whose number of indices is not a multiple of the number of
+/// indices per primitive, returning how many were dropped. They are warned
+/// about once per frame, as a buggy shape generator produces incomplete
+/// meshes every frame.
+fn drop_incomplete_meshes(meshes: &mut Vec<ClippedMesh>) -> usize {
+    let len = meshes.len();
+    meshes.retain(|mesh| {
+        mesh.indices.len() % mesh.topology.indices_per_primitive() == 0
+    });
+    let meshes_incomplete = len - meshes.len();
+    if meshes_incomplete > 0 {
+        warn!(
+            concat!(
+                "egui wants to draw {} meshes with incomplete primitives ",
+                "in this frame. these requests will be ignored."
+            ),
+            meshes_incomplete,
+        );
+    }
+    meshes_incomplete
+}
+
+fn rect_size(rect: &RECT) -> (u32, u32) {
+    ((rect.right - rect.left) as _, (rect.bottom - rect.top) as _)
+}
+
 #[repr(C)]
 struct VertexData {
     pos: Pos2,
@@ -104,11 +457,131 @@ struct VertexData {
     color: Rgba,
 }
 
+// the layout of `VertexData` must match `Renderer::INPUT_ELEMENTS_DESC`, where
+// each element is appended right after the previous one.
+const _: () = {
+    assert!(mem::offset_of!(VertexData, pos) == 0);
+    assert!(mem::offset_of!(VertexData, uv) == mem::size_of::<Pos2>());
+    assert!(mem::offset_of!(VertexData, color) == 2 * mem::size_of::<Pos2>());
+    assert!(mem::size_of::<Pos2>() == 8);
+    assert!(mem::size_of::<Rgba>() == 16);
+    assert!(mem::size_of::<VertexData>() == 32);
+};
+
+/// A mesh from `egui` to be drawn, with its clip rectangle in points.
+struct ClippedMesh {
+    clip_rect: egui::Rect,
+    vertices: Vec<Vertex>,
+    indices: IndexData,
+    texture_id: egui::TextureId,
+    topology: MeshTopology,
+}
+
+impl ClippedMesh {
+    /// Split the mesh into meshes with 16-bit indices if its indices do not
+    /// fit in them.
+    fn split_to_u16(self) -> Vec<Self> {
+        let IndexData::U32(indices) = self.indices else {
+            return vec![self];
+        };
+        if indices.iter().all(|&i| i <= u16::MAX as u32) {
+            return vec![Self {
+                indices: IndexData::U32(indices),
+                ..self
+            }];
+        }
+        if self.topology != MeshTopology::TriangleList {
+            warn!(concat!(
+                "a mesh which is not a triangle list can not be split into ",
+                "meshes with 16-bit indices. this mesh will be skipped."
+            ));
+            return Vec::new();
+        }
+        let mesh = Mesh {
+            indices,
+            vertices: self.vertices,
+            texture_id: self.texture_id,
+        };
+        mesh.split_to_u16()
+            .into_iter()
+            .map(|mesh| Self {
+                clip_rect: self.clip_rect,
+                vertices: mesh.vertices,
+                indices: IndexData::U16(mesh.indices),
+                texture_id: mesh.texture_id,
+                topology: self.topology,
+            })
+            .collect()
+    }
+
+    /// Whether all vertex colors of the mesh are transparent black, so that
+    /// drawing it has no visible effect whatever texture it samples. Colors
+    /// with an alpha of zero but non-zero color channels are additive with
+    /// premultiplied alpha, and thus not transparent.
+    fn is_transparent(&self) -> bool {
+        self.vertices
+            .iter()
+            .all(|v| v.color == egui::Color32::TRANSPARENT)
+    }
+}
+
 struct MeshData {
     vtx: Vec<VertexData>,
-    idx: Vec<u32>,
+    idx: IndexData,
     tex: egui::TextureId,
-    clip_rect: egui::Rect,
+    scissor: RECT,
+    /// The corner radius of the clip rectangle in pixels, see
+    /// [`RenderOptions::rounded_clip`].
+    clip_radius: f32,
+    topology: MeshTopology,
+}
+
+/// Indices of a mesh, stored as 16-bit integers whenever possible to save
+/// memory bandwidth. Meshes referencing vertices beyond the range of 16-bit
+/// integers keep their 32-bit indices.
+enum IndexData {
+    U16(Vec<u16>),
+    U32(Vec<u32>),
+}
+
+impl IndexData {
+    /// Narrow 32-bit indices into 16-bit ones if possible, storing them in
+    /// `storage` if provided to avoid allocating.
+    fn narrow(self, storage: Option<Vec<u16>>) -> Self {
+        match self {
+            Self::U32(idx) if idx.iter().all(|&i| i <= u16::MAX as u32) => {
+                let mut narrowed = storage.unwrap_or_default();
+                narrowed.clear();
+                narrowed.extend(idx.into_iter().map(|i| i as u16));
+                Self::U16(narrowed)
+            },
+            idx => idx,
+        }
+    }
+
+    /// Widen 16-bit indices into 32-bit ones.
+    fn widen(self) -> Self {
+        match self {
+            Self::U16(idx) => {
+                Self::U32(idx.into_iter().map(u32::from).collect())
+            },
+            idx => idx,
+        }
+    }
+
+    fn into_u16(self) -> Option<Vec<u16>> {
+        match self {
+            Self::U16(idx) => Some(idx),
+            Self::U32(_) => None,
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Self::U16(idx) => idx.len(),
+            Self::U32(idx) => idx.len(),
+        }
+    }
 }
 
 impl Renderer {
@@ -119,13 +592,43 @@ impl Renderer {
     /// If any Direct3D resource creation fails, this function will return an
     /// error. You can create the Direct3D11 device with debug layer enabled
     /// to find out details on the error.
+    ///
+    /// The built-in shaders are compiled for shader model 5.0, which requires
+    /// a device of feature level 11_0 or above (see
+    /// [`Renderer::MIN_FEATURE_LEVEL`]). For devices of a lower feature
+    /// level, e.g. one created with a fallback to `D3D_FEATURE_LEVEL_10_0`,
+    /// this function returns an error with `E_INVALIDARG` naming both levels
+    /// instead of failing to create the shaders.
     pub fn new(device: &ID3D11Device) -> Result<Self> {
+        let feature_level = unsafe { device.GetFeatureLevel() };
+        if feature_level.0 < Self::MIN_FEATURE_LEVEL.0 {
+            return Err(Error::new(
+                E_INVALIDARG,
+                format!(
+                    concat!(
+                        "egui-directx11 requires a device of feature level ",
+                        "11_0 or above, but the device has feature level ",
+                        "{}_{}."
+                    ),
+                    feature_level.0 >> 12,
+                    (feature_level.0 >> 8) & 0xf,
+                ),
+            ));
+        }
+
         let mut input_layout = None;
         let mut vertex_shader = None;
+        let mut transform_vertex_shader = None;
         let mut pixel_shader = None;
+        let mut mrt_pixel_shader = None;
+        let mut rounded_clip_pixel_shader = None;
+        let mut unpremultiply_pixel_shader = None;
+        let mut dual_source_pixel_shader = None;
         let mut rasterizer_state = None;
         let mut sampler_state = None;
+        let mut point_sampler_state = None;
         let mut blend_state = None;
+        let mut straight_blend_state = None;
         unsafe {
             device.CreateInputLayout(
                 &Self::INPUT_ELEMENTS_DESC,
@@ -137,11 +640,36 @@ impl Renderer {
                 None,
                 Some(&mut vertex_shader),
             )?;
+            device.CreateVertexShader(
+                Self::VS_TRANSFORM_BLOB,
+                None,
+                Some(&mut transform_vertex_shader),
+            )?;
             device.CreatePixelShader(
                 Self::PS_BLOB,
                 None,
                 Some(&mut pixel_shader),
             )?;
+            device.CreatePixelShader(
+                Self::PS_MRT_BLOB,
+                None,
+                Some(&mut mrt_pixel_shader),
+            )?;
+            device.CreatePixelShader(
+                Self::PS_ROUNDED_CLIP_BLOB,
+                None,
+                Some(&mut rounded_clip_pixel_shader),
+            )?;
+            device.CreatePixelShader(
+                Self::PS_UNPREMULTIPLY_BLOB,
+                None,
+                Some(&mut unpremultiply_pixel_shader),
+            )?;
+            device.CreatePixelShader(
+                Self::PS_DUAL_SOURCE_BLOB,
+                None,
+                Some(&mut dual_source_pixel_shader),
+            )?;
             device.CreateRasterizerState(
                 &Self::RASTERIZER_DESC,
                 Some(&mut rasterizer_state),
@@ -150,24 +678,513 @@ impl Renderer {
                 &Self::SAMPLER_DESC,
                 Some(&mut sampler_state),
             )?;
+            device.CreateSamplerState(
+                &Self::POINT_SAMPLER_DESC,
+                Some(&mut point_sampler_state),
+            )?;
             device
                 .CreateBlendState(&Self::BLEND_DESC, Some(&mut blend_state))?;
+            device.CreateBlendState(
+                &Self::straight_blend_desc(Self::BLEND_DESC),
+                Some(&mut straight_blend_state),
+            )?;
         };
         Ok(Self {
             device: device.clone(),
+            device_version: DeviceVersion::detect(device),
             input_layout: input_layout.unwrap(),
             vertex_shader: vertex_shader.unwrap(),
+            transform_vertex_shader: transform_vertex_shader.unwrap(),
             pixel_shader: pixel_shader.unwrap(),
+            mrt_pixel_shader: mrt_pixel_shader.unwrap(),
+            custom_pixel_shader: None,
+            custom_vertex_shader: None,
+            transform_buffer: None,
+            rounded_clip_pixel_shader: rounded_clip_pixel_shader.unwrap(),
+            unpremultiply_pixel_shader: unpremultiply_pixel_shader.unwrap(),
+            dual_source_pixel_shader: dual_source_pixel_shader.unwrap(),
+            rounded_clip_buffer: None,
+            rounded_clip_bound: None,
+            stencil_mask: None,
+            depth_stencil_state: None,
+            custom_depth_stencil_state: None,
             rasterizer_state: rasterizer_state.unwrap(),
+            rasterizer_desc: Self::RASTERIZER_DESC,
+            rasterizer_conservative: false,
             sampler_state: sampler_state.unwrap(),
+            sampler_desc: Self::SAMPLER_DESC,
+            custom_sampler_state: None,
+            secondary_texture: None,
+            point_sampler_state: point_sampler_state.unwrap(),
+            texture_samplers: Vec::new(),
             blend_state: blend_state.unwrap(),
+            straight_blend_state: straight_blend_state.unwrap(),
+            straight_bound: false,
+            topology_bound: MeshTopology::TriangleList,
+            blend_desc: Self::BLEND_DESC,
             texture_pool: TexturePool::new(device),
+            options: RenderOptions::default(),
+            offscreen_target: None,
+            gamma_target: None,
+            gamma_space: false,
+            multiple_targets: false,
+            collected_callbacks: None,
+            callback_handlers: Vec::new(),
+            in_frame: false,
+            annotation_prefix: None,
+            blit_states: None,
+            dynamic_vertex_buffer: None,
+            dynamic_index_buffer: None,
+            buffer_pool: BufferPool::default(),
+            occlusion_queries: OcclusionQueries::default(),
+            damage: DamageTracker::default(),
+            stats: RenderStats::default(),
+            textures_changed: false,
+            scratch: RenderScratch::default(),
+            on_device_lost: None,
         })
     }
 
+    /// Get the [`RenderOptions`] currently used by this [`Renderer`].
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    /// Get a mutable reference to the [`RenderOptions`] used by this
+    /// [`Renderer`]. Changes take effect from the next call to
+    /// [`Renderer::render`].
+    pub fn options_mut(&mut self) -> &mut RenderOptions {
+        &mut self.options
+    }
+
+    /// Assert whether `egui` is displayed at an integer scale, i.e. one point
+    /// in `egui` covers an integer number of physical pixels. This is a
+    /// shorthand for setting [`RenderOptions::integer_scaling`].
+    pub fn set_integer_scaling(&mut self, integer_scaling: bool) {
+        self.options.integer_scaling = integer_scaling;
+    }
+
+    /// Set the color space `egui` is blended in at runtime, e.g. when the
+    /// swap chain is recreated with another format to toggle HDR. This is a
+    /// shorthand for setting [`RenderOptions::color_space`], which
+    /// additionally drops the internal render targets cached for the
+    /// previous color space.
+    ///
+    /// Setting [`ColorSpace::Linear`] also drops the non-sRGB copies of the
+    /// textures managed by `egui` kept for [`ColorSpace::Gamma`] or
+    /// [`Renderer::render_to_srgb_target`], which are created again on
+    /// demand.
+    pub fn set_color_space(&mut self, color_space: ColorSpace) {
+        if color_space != self.options.color_space {
+            self.options.color_space = color_space;
+            self.offscreen_target = None;
+            self.gamma_target = None;
+        }
+        if color_space == ColorSpace::Linear {
+            self.texture_pool.disable_gamma_views();
+            self.gamma_target = None;
+        }
+    }
+
+    /// Get the format of render target views matching
+    /// [`RenderOptions::color_space`], i.e. `DXGI_FORMAT_R8G8B8A8_UNORM_SRGB`
+    /// for [`ColorSpace::Linear`] and `DXGI_FORMAT_R8G8B8A8_UNORM` for
+    /// [`ColorSpace::Gamma`], e.g. to create the render target view of a
+    /// swap chain buffer. Float formats such as
+    /// `DXGI_FORMAT_R16G16B16A16_FLOAT` also work with
+    /// [`ColorSpace::Linear`], but are only worth it for HDR.
+    pub fn recommended_target_format(&self) -> DXGI_FORMAT {
+        match self.options.color_space {
+            ColorSpace::Linear => DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            ColorSpace::Gamma => DXGI_FORMAT_R8G8B8A8_UNORM,
+        }
+    }
+
+    /// Set a prefix for the names of the events emitted with the
+    /// `annotations` feature, or `None` for no prefix, e.g. `"MyPanel"` for
+    /// events named `MyPanel/egui-directx11: render`, to tell apart the
+    /// panels rendered with several calls in a GPU capture. Set it before
+    /// each call to name the events of that call.
+    pub fn set_annotation_prefix(&mut self, prefix: Option<&str>) {
+        self.annotation_prefix = prefix.map(str::to_owned);
+    }
+
+    /// Whether meshes are blended with dual-source blending, see
+    /// [`RenderOptions::dual_source_blending`].
+    fn dual_source_enabled(&self) -> bool {
+        self.options.dual_source_blending
+            && self.options.debug_mode == DebugMode::None
+            && self.options.blend_mode != BlendMode::Opaque
+            && !self.blends_in_gamma_space()
+    }
+
+    /// Whether colors are kept gamma-encoded while blending, see
+    /// [`ColorSpace::Gamma`].
+    fn blends_in_gamma_space(&self) -> bool {
+        self.gamma_space || self.options.color_space == ColorSpace::Gamma
+    }
+
+    /// Set the shader resource slot in the pixel shader stage which textures
+    /// are bound to. This is a shorthand for setting
+    /// [`RenderOptions::srv_slot`].
+    pub fn set_srv_slot(&mut self, srv_slot: u32) {
+        self.options.srv_slot = srv_slot;
+    }
+
+    /// Set a custom pixel shader to be used instead of the built-in one, or
+    /// `None` to use the built-in one.
+    ///
+    /// The custom pixel shader receives the same inputs as the built-in one,
+    /// namely `SV_POSITION`, `TEXCOORD` (a `float2`) and `COLOR` (a `float4`
+    /// holding the linear premultiplied vertex color), in that order, and is
+    /// expected to sample the texture bound to the slot specified by
+    /// [`RenderOptions::srv_slot`] with the sampler bound to slot 0. See
+    /// `shaders/egui.hlsl` in the repository of this crate for the built-in
+    /// shaders.
+    pub fn set_pixel_shader(
+        &mut self,
+        pixel_shader: Option<ID3D11PixelShader>,
+    ) {
+        self.custom_pixel_shader = pixel_shader;
+    }
+
+    /// Set a custom sampler state to be bound to sampler slot 0 instead of
+    /// the built-in one, e.g. a sampler state shared across your engine, or
+    /// `None` to use the built-in one.
+    ///
+    /// The custom sampler state is used for all textures, overriding the
+    /// [`egui::TextureOptions`] of textures managed by `egui`. If
+    /// [`RenderOptions::integer_scaling`] is enabled, textures managed by
+    /// `egui` are still sampled with the built-in point sampler state.
+    pub fn set_sampler_state(
+        &mut self,
+        sampler_state: Option<ID3D11SamplerState>,
+    ) {
+        self.custom_sampler_state = sampler_state;
+    }
+
+    /// Set a texture to be bound to shader resource slot `slot` of the pixel
+    /// shader stage for all meshes, e.g. a mask or noise texture sampled by a
+    /// custom pixel shader set with [`Renderer::set_pixel_shader`] next to
+    /// the texture of each mesh, or `None` to bind no such texture, which is
+    /// the default. The built-in pixel shader ignores it.
+    ///
+    /// Unlike the texture of each mesh, bound to the slot specified by
+    /// [`RenderOptions::srv_slot`], the texture is bound once when the
+    /// pipeline state is set up, e.g. to `t1` with a `slot` of `1`, and stays
+    /// bound for the whole render call and afterwards. It is ignored with a
+    /// warning if `slot` equals [`RenderOptions::srv_slot`].
+    pub fn set_secondary_texture(
+        &mut self,
+        slot: u32,
+        texture: Option<ID3D11ShaderResourceView>,
+    ) {
+        self.secondary_texture = texture.map(|texture| (slot, texture));
+    }
+
+    /// Set a callback to be invoked with the id, width and height of each
+    /// texture managed by `egui` right after it is created, or `None` to
+    /// remove the callback.
+    ///
+    /// When `egui` replaces a managed texture as a whole, the callback set by
+    /// [`Renderer::set_on_texture_freed`] is invoked for the old texture
+    /// before this callback is invoked for the new one.
+    pub fn set_on_texture_created(
+        &mut self,
+        callback: Option<TextureCreatedCallback>,
+    ) {
+        self.texture_pool.set_on_created(callback);
+    }
+
+    /// Set a callback to be invoked with the id of each texture managed by
+    /// `egui` right after it is freed, or `None` to remove the callback.
+    pub fn set_on_texture_freed(
+        &mut self,
+        callback: Option<TextureFreedCallback>,
+    ) {
+        self.texture_pool.set_on_freed(callback);
+    }
+
+    /// Set a callback to be invoked with the id of a texture managed by
+    /// `egui` and the error when creating or updating it fails, e.g. when
+    /// running out of video memory, or `None` to remove the callback.
+    ///
+    /// The callback decides with the returned [`TextureUploadPolicy`]
+    /// whether rendering is aborted with the error, which is what happens
+    /// without a callback, or whether the texture is skipped and rendering
+    /// continues. Meshes sampling a skipped texture which did not exist
+    /// before are treated like meshes sampling any non-existing texture.
+    /// Rendering is always aborted if the device has been removed, without
+    /// invoking the callback.
+    pub fn set_on_texture_upload_failed(
+        &mut self,
+        callback: Option<TextureUploadFailedCallback>,
+    ) {
+        self.texture_pool.set_on_upload_failed(callback);
+    }
+
+    /// Get the [`TexturePool`] holding all textures this [`Renderer`] can
+    /// sample from.
+    pub fn texture_pool(&self) -> &TexturePool {
+        &self.texture_pool
+    }
+
+    /// Register a shader resource view as a user texture, which can then be
+    /// displayed in `egui` (e.g. with [`egui::Image`]) using the returned
+    /// [`egui::TextureId`].
+    ///
+    /// The texture is sampled in the pixel shader as is, so its view format
+    /// should be an `_SRGB` one if the texture holds gamma-encoded colors. It
+    /// is assumed to hold colors with straight (non-premultiplied) alpha; see
+    /// [`Renderer::register_user_texture_premultiplied`] otherwise.
+    pub fn register_user_texture(
+        &mut self,
+        srv: ID3D11ShaderResourceView,
+    ) -> egui::TextureId {
+        self.texture_pool.register_user_texture(srv, None, false)
+    }
+
+    /// Same as [`Renderer::register_user_texture`], but tags the texture as
+    /// holding colors with premultiplied alpha.
+    ///
+    /// `egui` itself works with premultiplied alpha: vertex colors and
+    /// textures managed by `egui` (including the font atlas) are all
+    /// premultiplied, and are blended without multiplying colors by alpha
+    /// again, like the reference backends of `egui` do. Meshes sampling a
+    /// user texture with straight alpha have their vertex colors
+    /// unpremultiplied and are blended with a variant of the blend state
+    /// which multiplies colors by alpha, while meshes sampling a texture
+    /// registered with this method are blended like the rest of `egui`.
+    pub fn register_user_texture_premultiplied(
+        &mut self,
+        srv: ID3D11ShaderResourceView,
+    ) -> egui::TextureId {
+        self.texture_pool.register_user_texture(srv, None, true)
+    }
+
+    /// Register multiple shader resource views as user textures at once, e.g.
+    /// the sprites of a sprite sheet, in the same way as
+    /// [`Renderer::register_user_texture`].
+    ///
+    /// The returned ids are in the same order as `srvs` and are guaranteed to
+    /// be contiguous, i.e. the `n`-th id is `egui::TextureId::User(first + n)`
+    /// where `first` is the number in the first id, so that they can be
+    /// indexed easily.
+    pub fn register_user_textures(
+        &mut self,
+        srvs: &[ID3D11ShaderResourceView],
+    ) -> Vec<egui::TextureId> {
+        // ids are allocated sequentially, so registering all views under a
+        // single borrow of the pool yields a contiguous range.
+        srvs.iter()
+            .map(|srv| {
+                self.texture_pool.register_user_texture(
+                    srv.clone(),
+                    None,
+                    false,
+                )
+            })
+            .collect()
+    }
+
+    /// Same as [`Renderer::register_user_texture`], but attaches a
+    /// human-readable label to the texture. The label is set as the debug
+    /// object name of `srv`, which shows up in messages from the debug layer
+    /// and in graphics debuggers, and is also reported by
+    /// [`Renderer::user_textures`].
+    pub fn register_user_texture_labeled(
+        &mut self,
+        srv: ID3D11ShaderResourceView,
+        label: &str,
+    ) -> egui::TextureId {
+        self.texture_pool
+            .register_user_texture(srv, Some(label), false)
+    }
+
+    /// Register a single layer of a texture array as a user texture, so that
+    /// many small images (e.g. icons) can be stored in one texture array and
+    /// displayed individually in `egui`.
+    ///
+    /// As the built-in pixel shader samples a `Texture2D`, the slice `layer`
+    /// (with all its mip levels) is copied with `device_context` into a new
+    /// texture of the format of the texture array, which thus must not be a
+    /// typeless format. The copy is registered instead of the texture array,
+    /// so later changes to the texture array are not reflected in it.
+    ///
+    /// Returns an error with `E_INVALIDARG` if `layer` is out of range, or the
+    /// error from Direct3D11 if the texture or its view cannot be created.
+    pub fn register_user_texture_from_array(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        tex_array: &ID3D11Texture2D,
+        layer: u32,
+    ) -> Result<egui::TextureId> {
+        let mut desc = self::zeroed::<D3D11_TEXTURE2D_DESC>();
+        unsafe { tex_array.GetDesc(&mut desc) };
+        if layer >= desc.ArraySize {
+            return Err(Error::new(
+                E_INVALIDARG,
+                format!(
+                    "layer {layer} is out of range for a texture array of {} layers.",
+                    desc.ArraySize
+                ),
+            ));
+        }
+
+        let mut tex = None;
+        unsafe {
+            self.device.CreateTexture2D(
+                &D3D11_TEXTURE2D_DESC {
+                    Width: desc.Width,
+                    Height: desc.Height,
+                    MipLevels: desc.MipLevels,
+                    ArraySize: 1,
+                    Format: desc.Format,
+                    SampleDesc: desc.SampleDesc,
+                    Usage: D3D11_USAGE_DEFAULT,
+                    BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as _,
+                    ..Default::default()
+                },
+                None,
+                Some(&mut tex),
+            )
+        }?;
+        let tex = tex.unwrap();
+        for mip in 0..desc.MipLevels {
+            unsafe {
+                device_context.CopySubresourceRegion(
+                    &tex,
+                    mip,
+                    0,
+                    0,
+                    0,
+                    tex_array,
+                    // see `D3D11CalcSubresource`.
+                    mip + layer * desc.MipLevels,
+                    None,
+                )
+            };
+        }
+
+        let mut srv = None;
+        unsafe {
+            self.device
+                .CreateShaderResourceView(&tex, None, Some(&mut srv))
+        }?;
+        Ok(self
+            .texture_pool
+            .register_user_texture(srv.unwrap(), None, false))
+    }
+
+    /// Seed the texture managed by `egui` with id `texture_id`, usually the
+    /// font atlas with id [`egui::TextureId::default`], with a pre-baked
+    /// texture before `egui` uploads it, e.g. to speed up cold start with a
+    /// font atlas baked offline. Like the textures uploaded by `egui`, it
+    /// must hold colors with premultiplied alpha, e.g. the output of
+    /// [`egui::FontImage::srgba_pixels`] in an `_SRGB` format.
+    ///
+    /// The texture is treated as managed by `egui` and is freed or replaced
+    /// as a whole as `egui` requests. As it holds no CPU-side copy of its
+    /// pixels, partial updates to it are ignored with a warning, which
+    /// usually indicates that it does not match the font configuration of
+    /// `egui`. `texture_id` must be a managed id, or an error with
+    /// `E_INVALIDARG` is returned.
+    pub fn preload_font_atlas(
+        &mut self,
+        texture_id: egui::TextureId,
+        srv: ID3D11ShaderResourceView,
+    ) -> Result<()> {
+        if !matches!(texture_id, egui::TextureId::Managed(_)) {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "only managed textures can be preloaded.",
+            ));
+        }
+        self.texture_pool.preload(texture_id, srv);
+        Ok(())
+    }
+
+    /// Unregister a user texture previously registered with
+    /// [`Renderer::register_user_texture`], returning its shader resource
+    /// view, or `None` if `tid` is not a registered user texture.
+    pub fn unregister_user_texture(
+        &mut self,
+        tid: egui::TextureId,
+    ) -> Option<ID3D11ShaderResourceView> {
+        self.texture_pool.unregister_user_texture(tid)
+    }
+
+    /// Iterate over all registered user textures, yielding the id, shader
+    /// resource view and label (if any) of each of them in arbitrary order.
+    pub fn user_textures(
+        &self,
+    ) -> impl Iterator<
+        Item = (egui::TextureId, &ID3D11ShaderResourceView, Option<&str>),
+    > {
+        self.texture_pool.user_textures()
+    }
+
+    /// Set a callback to be invoked with the reason of device removal (as
+    /// returned by `ID3D11Device::GetDeviceRemovedReason`) when rendering fails
+    /// because the device has been removed, or `None` to remove the callback.
+    ///
+    /// This gives a single place to log the failure and schedule recreation of
+    /// the device and all resources derived from it, including the
+    /// [`Renderer`] itself.
+    pub fn set_on_device_lost(&mut self, callback: Option<DeviceLostCallback>) {
+        self.on_device_lost = callback;
+    }
+
+    /// Reset this [`Renderer`] to a clean state after a recoverable error,
+    /// e.g. a failed texture upload which left the texture pool partially
+    /// updated, without recreating its shaders and pipeline states.
+    ///
+    /// All textures managed by `egui` are freed, and all cached resources
+    /// depending on the size of the render target or the amount of geometry
+    /// drawn are dropped and recreated on demand. User textures are kept. As
+    /// `egui` only uploads a managed texture when it changes, it is *your*
+    /// responsibility to make `egui` upload them again, e.g. by using a new
+    /// [`egui::Context`].
+    pub fn reset(&mut self) {
+        self.texture_pool.clear_managed();
+        self.texture_pool.disable_gamma_views();
+        self.offscreen_target = None;
+        self.gamma_target = None;
+        self.dynamic_vertex_buffer = None;
+        self.dynamic_index_buffer = None;
+        self.buffer_pool = BufferPool::default();
+    }
+
+    /// Discard the contents of `render_target` with `DiscardView`, telling
+    /// the driver that they are not needed anymore, e.g. right before
+    /// clearing the back buffer of a flip-model swap chain every frame. This
+    /// is merely a performance hint, and the contents are undefined
+    /// afterwards, so *you* must overwrite every pixel before presenting.
+    ///
+    /// `DiscardView` requires `ID3D11DeviceContext1`, i.e. Direct3D 11.1
+    /// (see [`Renderer::device_version`]). Returns whether the contents were
+    /// discarded, which is `false` if the device context does not support
+    /// it, in which case this is a no-op.
+    pub fn discard_target(
+        &self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+    ) -> bool {
+        if self.device_version < DeviceVersion::Device1 {
+            return false;
+        }
+        let Ok(device_context) = device_context.cast::<ID3D11DeviceContext1>()
+        else {
+            return false;
+        };
+        unsafe { device_context.DiscardView(render_target) };
+        true
+    }
+
     /// Render the output of `egui` to the provided render target using the
     /// provided device context. The render target should use a linear color
-    /// space (e.g. `DXGI_FORMAT_R8G8B8A8_UNORM_SRGB`) for proper results.
+    /// space (e.g. `DXGI_FORMAT_R8G8B8A8_UNORM_SRGB`) for proper results,
+    /// unless [`RenderOptions::color_space`] is [`ColorSpace::Gamma`].
     ///
     /// The `scale_factor` should be the scale factor of your window and not
     /// confused with [`egui::Context::zoom_factor`]. If you are using `winit`,
@@ -180,7 +1197,9 @@ impl Renderer {
     /// result. You can create the Direct3D11 device with debug layer
     /// enabled to find out details on the error.
     /// If the device has been lost, you should drop the [`Renderer`] and create
-    /// a new one.
+    /// a new one. In this case the callback set with
+    /// [`Renderer::set_on_device_lost`], if any, is invoked with the reason of
+    /// device removal before this function returns.
     ///
     /// ## Pipeline State Management
     ///
@@ -191,153 +1210,1067 @@ impl Renderer {
     /// backup the current pipeline state and restore it afterwards if your
     /// rendering pipeline depends on it.
     ///
-    /// Particularly, it overrides:
-    /// + The input layout, vertex buffer, index buffer and primitive topology
-    ///   in the input assembly stage;
-    /// + The current shader in the vertex shader stage;
-    /// + The viewport and rasterizer state in the rasterizer stage;
-    /// + The current shader, shader resource slot 0 and sampler slot 0 in the
-    ///   pixel shader stage;
-    /// + The render target(s) and blend state in the output merger stage;
+    /// Particularly, it overrides:
+    /// + The input layout, vertex buffer, index buffer and primitive topology
+    ///   in the input assembly stage;
+    /// + The current shader and constant buffer slot 0 in the vertex shader
+    ///   stage;
+    /// + The viewport and rasterizer state in the rasterizer stage;
+    /// + The current shader, shader resource slot 0 (or the slot specified by
+    ///   [`RenderOptions::srv_slot`]) and sampler slot 0 in the pixel shader
+    ///   stage, constant buffer slot 0 if [`RenderOptions::rounded_clip`]
+    ///   is not empty, and the slot of the texture set with
+    ///   [`Renderer::set_secondary_texture`], if any;
+    /// + The render target(s) and blend state in the output merger stage;
+    /// + The depth stencil view and depth stencil state in the output merger
+    ///   stage, if a stencil mask is set with [`Renderer::set_stencil_mask`];
+    /// + The depth stencil state in the output merger stage, if a depth
+    ///   stencil view is bound by [`Renderer::render_into_current_target`];
+    ///
+    /// If [`RenderOptions::supersample`] or [`RenderOptions::render_scale`]
+    /// is not `1.0`, [`RenderOptions::pixel_scale`] is not `1` or
+    /// [`RenderOptions::straight_alpha_output`] is set, it additionally
+    /// clears an internal render target, and leaves shader resource slot 0
+    /// in the pixel shader stage unbound.
+    ///
+    /// ## Output Alpha
+    ///
+    /// Colors and alpha are both blended with premultiplied alpha "over"
+    /// compositing, i.e. `dst = src + dst * (1 - src.a)`, so the alpha of
+    /// the render target ends up as the accumulated coverage of `egui`,
+    /// `1 - (1 - a_1) * (1 - a_2) * ...` over the alphas of all shapes drawn
+    /// at a pixel, including anti-aliased edges. Rendered into a target
+    /// cleared to transparent black (see [`Renderer::render_premultiplied`]),
+    /// the result is a premultiplied overlay ready to be composited "over"
+    /// other content, e.g. keyed by its alpha in a streaming compositor.
+    /// To write straight alpha instead, e.g. for consumers which do not
+    /// support premultiplied alpha, see
+    /// [`RenderOptions::straight_alpha_output`].
+    /// Options changing the blend state, such as [`RenderOptions::blend_mode`],
+    /// [`RenderOptions::debug_mode`], [`RenderOptions::dual_source_blending`]
+    /// or [`RenderOptions::color_write_mask`], change the alpha produced.
+    ///
+    /// ## Determinism
+    ///
+    /// The sequence of Direct3D11 calls issued by this function depends only
+    /// on its inputs, the options and the textures registered, and not on
+    /// the iteration order of any hash-based collection. Rendering identical
+    /// input twice therefore issues identical calls, which keeps GPU captures
+    /// (e.g. with PIX) comparable across runs. Meshes are drawn in the order
+    /// `egui` paints them.
+    ///
+    /// See the [`egui-demo`](https://github.com/Nekomaru-PKU/egui-directx11/blob/main/examples/egui-demo.rs)
+    /// example for code examples.
+    pub fn render(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<()> {
+        self.render_impl(
+            device_context,
+            render_target,
+            egui_ctx,
+            &egui_output.textures_delta,
+            egui_output.shapes,
+            egui_output.pixels_per_point,
+            scale_factor,
+            true,
+        )
+        .inspect_err(|_| self.notify_device_lost())
+    }
+
+    /// Same as [`Renderer::render`], but takes the whole
+    /// [`egui::FullOutput`], splitting it with [`split_output`], and returns
+    /// the [`egui::PlatformOutput`] for *you* to handle, e.g. the cursor
+    /// icon with `win32::set_cursor_icon` on native Win32 windows. The
+    /// output of other viewports than the root viewport is dropped.
+    pub fn render_full_output(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        full_output: egui::FullOutput,
+        scale_factor: f32,
+    ) -> Result<egui::PlatformOutput> {
+        let (egui_output, platform_output, _) = split_output(full_output);
+        self.render(
+            device_context,
+            render_target,
+            egui_ctx,
+            egui_output,
+            scale_factor,
+        )?;
+        Ok(platform_output)
+    }
+
+    /// Same as [`Renderer::render`], but borrows the output of `egui` instead
+    /// of consuming it, e.g. to render the same output to several render
+    /// targets.
+    ///
+    /// The texture deltas are applied by reference on every call. Applying
+    /// the same deltas again is harmless but not free, as whole textures are
+    /// uploaded again.
+    ///
+    /// Borrowing saves nothing over cloning the output for
+    /// [`Renderer::render`]: the tessellator of `egui` consumes the shapes,
+    /// so the whole vector of shapes is cloned for tessellation on every
+    /// call. To avoid that, tessellate once with [`Renderer::tessellate`] and
+    /// render the primitives to each render target with
+    /// [`Renderer::render_primitives`] instead.
+    pub fn render_ref(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        egui_output: &RendererOutput,
+        scale_factor: f32,
+    ) -> Result<()> {
+        self.render_impl(
+            device_context,
+            render_target,
+            egui_ctx,
+            &egui_output.textures_delta,
+            egui_output.shapes.clone(),
+            egui_output.pixels_per_point,
+            scale_factor,
+            true,
+        )
+        .inspect_err(|_| self.notify_device_lost())
+    }
+
+    /// Same as [`Renderer::render`], but renders to the render target
+    /// currently bound to slot 0 of the output merger stage of the provided
+    /// device context, which is queried with `OMGetRenderTargets`.
+    ///
+    /// This is intended for engines which manage the output merger stage
+    /// themselves. Unlike [`Renderer::render`], it assumes the render target
+    /// to be bound already and does not call `OMSetRenderTargets`, so that the
+    /// bound render target(s) and depth stencil view are left untouched.
+    /// The depth stencil view, if any, is still bound while `egui` is drawn,
+    /// so a depth stencil state disabling depth testing and depth writes is
+    /// set along with it, which may be replaced with
+    /// [`Renderer::set_depth_stencil_state`]. If an internal render target is
+    /// used (see [`Renderer::render`]), render targets are rebound anyway and
+    /// only the queried render target is bound afterwards.
+    ///
+    /// If no render target is bound to slot 0, this function returns an error
+    /// with `E_INVALIDARG`.
+    pub fn render_into_current_target(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<()> {
+        let mut render_targets = [None];
+        unsafe {
+            device_context.OMGetRenderTargets(Some(&mut render_targets), None)
+        };
+        let Some(render_target) = render_targets[0].take() else {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "no render target is bound to the device context.",
+            ));
+        };
+        self.render_impl(
+            device_context,
+            &render_target,
+            egui_ctx,
+            &egui_output.textures_delta,
+            egui_output.shapes,
+            egui_output.pixels_per_point,
+            scale_factor,
+            false,
+        )
+        .inspect_err(|_| self.notify_device_lost())
+    }
+
+    /// Same as [`Renderer::render`], but renders to multiple render targets
+    /// simultaneously (MRT), e.g. to write a separate UI mask next to the
+    /// color for a deferred compositor. The render targets are bound to the
+    /// slots of the output merger stage in the order of `render_targets`.
+    ///
+    /// With two render targets, a built-in pixel shader writes the colors of
+    /// `egui` to the first one and the coverage of `egui`, i.e. the alpha of
+    /// the colors replicated to all channels, to the second one as a mask.
+    /// For more render targets or other outputs, a custom pixel shader
+    /// writing to `SV_Target0` up to `SV_TargetN` must be set with
+    /// [`Renderer::set_pixel_shader`]. All outputs are blended with the same
+    /// blend state as the first render target, i.e. as premultiplied colors,
+    /// so a mask should be written as its alpha replicated to all channels.
+    /// `shaders/egui.hlsl` in the repository of this crate contains the
+    /// source of the built-in shader, named `ps_main_mrt`.
+    ///
+    /// An error with `E_INVALIDARG` is returned if `render_targets` is empty
+    /// or exceeds `D3D11_SIMULTANEOUS_RENDER_TARGET_COUNT`, if the render
+    /// targets differ in size, if more than two render targets are given
+    /// without a custom pixel shader, if more than one render target is
+    /// given with [`RenderOptions::dual_source_blending`], or if an internal
+    /// render target would be used (see [`Renderer::render`]), which only
+    /// holds a single output.
+    pub fn render_to_multiple_targets(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_targets: &[ID3D11RenderTargetView],
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<()> {
+        let Some(first) = render_targets.first() else {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "no render target is provided.",
+            ));
+        };
+        if render_targets.len()
+            > D3D11_SIMULTANEOUS_RENDER_TARGET_COUNT as usize
+        {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "too many render targets are provided.",
+            ));
+        }
+        let size = Self::get_render_target_size(first)?;
+        for render_target in &render_targets[1..] {
+            if Self::get_render_target_size(render_target)? != size {
+                return Err(Error::new(
+                    E_INVALIDARG,
+                    "all render targets must have the same size.",
+                ));
+            }
+        }
+        if render_targets.len() > 2 && self.custom_pixel_shader.is_none() {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "more than two render targets require a custom pixel shader.",
+            ));
+        }
+        if render_targets.len() > 1 && self.options.dual_source_blending {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "dual-source blending requires a single render target.",
+            ));
+        }
+        if self.offscreen_mode().is_some() {
+            return Err(Error::new(
+                E_INVALIDARG,
+                concat!(
+                    "multiple render targets can not be used with ",
+                    "supersampling or pixel scaling."
+                ),
+            ));
+        }
+
+        let render_targets: Vec<_> =
+            render_targets.iter().cloned().map(Some).collect();
+        unsafe {
+            // see `Renderer::setup`.
+            device_context
+                .PSSetShaderResources(self.options.srv_slot, Some(&[None]));
+            device_context.OMSetRenderTargets(
+                Some(&render_targets),
+                self.stencil_mask.as_ref().map(|m| &m.depth_stencil_view),
+            )
+        };
+        self.multiple_targets = render_targets.len() > 1;
+        let result = self.render_impl(
+            device_context,
+            first,
+            egui_ctx,
+            &egui_output.textures_delta,
+            egui_output.shapes,
+            egui_output.pixels_per_point,
+            scale_factor,
+            false,
+        );
+        self.multiple_targets = false;
+        result.inspect_err(|_| self.notify_device_lost())
+    }
+
+    fn notify_device_lost(&mut self) {
+        if let Err(reason) = unsafe { self.device.GetDeviceRemovedReason() } {
+            if let Some(on_device_lost) = &mut self.on_device_lost {
+                on_device_lost(reason.code());
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn render_impl(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        textures_delta: &TexturesDelta,
+        shapes: Vec<ClippedShape>,
+        pixels_per_point: f32,
+        scale_factor: f32,
+        bind_render_target: bool,
+    ) -> Result<()> {
+        let _annotation = Annotation::begin(
+            device_context,
+            self.annotation_prefix.as_deref(),
+            "egui-directx11: render",
+        );
+        self.update_textures(device_context, textures_delta)?;
+        self.draw_shapes(
+            device_context,
+            render_target,
+            egui_ctx,
+            shapes,
+            pixels_per_point,
+            scale_factor,
+            bind_render_target,
+        )
+    }
+
+    /// Tessellate and draw `shapes` without updating textures.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_shapes(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        shapes: Vec<ClippedShape>,
+        pixels_per_point: f32,
+        scale_factor: f32,
+        bind_render_target: bool,
+    ) -> Result<()> {
+        if shapes.is_empty() && !self.options.fill_background {
+            let viewport = self.get_viewport(render_target)?;
+            self.damage.update(
+                viewport,
+                &[],
+                Transform::IDENTITY,
+                self.options.damage_tracking,
+            );
+            self.stats = RenderStats {
+                textures_changed: self.textures_changed,
+                ..RenderStats::default()
+            };
+            // still issue an (empty) occlusion query so that the result for
+            // this frame reports nothing drawn.
+            return self.with_occlusion_query(device_context, |_| Ok(()));
+        }
+
+        let mode = self.offscreen_mode();
+        let primitives =
+            Self::tessellate_for(mode, egui_ctx, shapes, pixels_per_point);
+        if self.dispatches_callbacks() {
+            return self.draw_dispatching_callbacks(
+                device_context,
+                render_target,
+                egui_ctx,
+                primitives,
+                scale_factor,
+                mode,
+                bind_render_target,
+            );
+        }
+        let mut meshes =
+            clipped_meshes(primitives, self.collected_callbacks.as_mut());
+        if self.options.fill_background {
+            meshes.insert(0, background_mesh(egui_ctx));
+        }
+        self.draw_in_mode(
+            device_context,
+            render_target,
+            meshes,
+            scale_factor,
+            egui_ctx.zoom_factor(),
+            mode,
+            bind_render_target,
+        )
+    }
+
+    /// Tessellate `shapes` from the output of `egui` the same way
+    /// [`Renderer::render`] does with the current options, for rendering the
+    /// resulting primitives with [`Renderer::render_primitives`] one or more
+    /// times, e.g. to multiple render targets, without tessellating again.
+    ///
+    /// `pixels_per_point` should be [`RendererOutput::pixels_per_point`]. The
+    /// primitives depend on [`RenderOptions::supersample`],
+    /// [`RenderOptions::render_scale`] and [`RenderOptions::pixel_scale`],
+    /// and should be tessellated again after changing them.
+    pub fn tessellate(
+        &self,
+        egui_ctx: &egui::Context,
+        shapes: Vec<ClippedShape>,
+        pixels_per_point: f32,
+    ) -> Vec<ClippedPrimitive> {
+        Self::tessellate_for(
+            self.offscreen_mode(),
+            egui_ctx,
+            shapes,
+            pixels_per_point,
+        )
+    }
+
+    fn tessellate_for(
+        mode: Option<OffscreenMode>,
+        egui_ctx: &egui::Context,
+        shapes: Vec<ClippedShape>,
+        pixels_per_point: f32,
+    ) -> Vec<ClippedPrimitive> {
+        let tessellation_scale = match mode {
+            Some(OffscreenMode::Supersample(scale)) => scale,
+            Some(OffscreenMode::PixelScale(factor)) => 1. / factor as f32,
+            None => 1.,
+        };
+        egui_ctx.tessellate(shapes, pixels_per_point * tessellation_scale)
+    }
+
+    /// Render primitives tessellated with [`Renderer::tessellate`] to the
+    /// provided render target. The primitives are borrowed, so that they can
+    /// be rendered again to another render target.
+    ///
+    /// `textures_delta` is applied before drawing and should be applied only
+    /// once, so pass an empty [`TexturesDelta`] when rendering the same
+    /// primitives again. `pixels_per_point` should be the number of physical
+    /// pixels per point the primitives were tessellated for, i.e.
+    /// [`RendererOutput::pixels_per_point`]. Other than that, this function
+    /// behaves like [`Renderer::render`], including the pipeline state it
+    /// overrides.
+    pub fn render_primitives(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        textures_delta: TexturesDelta,
+        primitives: &[ClippedPrimitive],
+        pixels_per_point: f32,
+    ) -> Result<()> {
+        self.update_textures(device_context, &textures_delta)
+            .and_then(|_| {
+                let mode = self.offscreen_mode();
+                self.draw_in_mode(
+                    device_context,
+                    render_target,
+                    clipped_meshes(primitives.iter().cloned(), None),
+                    pixels_per_point,
+                    1.,
+                    mode,
+                    true,
+                )
+            })
+            .inspect_err(|_| self.notify_device_lost())
+    }
+
+    /// Same as [`Renderer::render_primitives`], but draws each primitive with
+    /// the given [`MeshTopology`], e.g. [`MeshTopology::LineList`] for custom
+    /// meshes holding line content whose indices form pairs instead of
+    /// triples.
     ///
-    /// See the [`egui-demo`](https://github.com/Nekomaru-PKU/egui-directx11/blob/main/examples/egui-demo.rs)
-    /// example for code examples.
-    pub fn render(
+    /// This is an opt-in path for custom meshes built by *you*. The output
+    /// of `egui`, including lines, is always tessellated into triangle lists
+    /// and should be drawn as [`MeshTopology::TriangleList`], as with all
+    /// other rendering functions. Meshes whose number of indices is not a
+    /// multiple of the number of indices per primitive are skipped with a
+    /// warning.
+    pub fn render_primitives_with_topology(
         &mut self,
         device_context: &ID3D11DeviceContext,
         render_target: &ID3D11RenderTargetView,
-        egui_ctx: &egui::Context,
-        egui_output: RendererOutput,
-        scale_factor: f32,
+        textures_delta: TexturesDelta,
+        primitives: &[(ClippedPrimitive, MeshTopology)],
+        pixels_per_point: f32,
     ) -> Result<()> {
-        self.texture_pool
-            .update(device_context, egui_output.textures_delta)?;
+        let meshes = primitives
+            .iter()
+            .flat_map(|(primitive, topology)| {
+                clipped_meshes([primitive.clone()], None).into_iter().map(
+                    |mesh| ClippedMesh {
+                        topology: *topology,
+                        ..mesh
+                    },
+                )
+            })
+            .collect();
+        self.update_textures(device_context, &textures_delta)
+            .and_then(|_| {
+                let mode = self.offscreen_mode();
+                self.draw_in_mode(
+                    device_context,
+                    render_target,
+                    meshes,
+                    pixels_per_point,
+                    1.,
+                    mode,
+                    true,
+                )
+            })
+            .inspect_err(|_| self.notify_device_lost())
+    }
+
+    /// Render meshes already split into meshes with 16-bit indices, e.g. with
+    /// [`egui::Mesh::split_to_u16`], to the provided render target, along
+    /// with their clip rectangles in points. The meshes are drawn with 16-bit
+    /// index buffers as they are, without being split again.
+    ///
+    /// This is intended for users tessellating the output of `egui` themselves.
+    /// `textures_delta` is applied before drawing, and `pixels_per_point`
+    /// should be the number of physical pixels per point the meshes were
+    /// tessellated for. Other than that, this function behaves like
+    /// [`Renderer::render`], including the pipeline state it overrides.
+    pub fn render_meshes16(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        textures_delta: TexturesDelta,
+        meshes: Vec<(egui::Rect, Mesh16)>,
+        pixels_per_point: f32,
+    ) -> Result<()> {
+        let meshes = meshes
+            .into_iter()
+            .map(|(clip_rect, mesh)| ClippedMesh {
+                clip_rect,
+                vertices: mesh.vertices,
+                indices: IndexData::U16(mesh.indices),
+                texture_id: mesh.texture_id,
+                topology: MeshTopology::TriangleList,
+            })
+            .collect();
+        self.update_textures(device_context, &textures_delta)
+            .and_then(|_| {
+                let mode = self.offscreen_mode();
+                self.draw_in_mode(
+                    device_context,
+                    render_target,
+                    meshes,
+                    pixels_per_point,
+                    1.,
+                    mode,
+                    true,
+                )
+            })
+            .inspect_err(|_| self.notify_device_lost())
+    }
 
-        if egui_output.shapes.is_empty() {
+    /// Begin a new frame and apply `textures_delta`. Once all draws of the
+    /// last frame are done, the pooled buffers used in it are retired,
+    /// the dynamic buffers are reset and textures over budget are evicted.
+    fn update_textures(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        textures_delta: &TexturesDelta,
+    ) -> Result<()> {
+        self.buffer_pool.end_frame(&self.device, device_context)?;
+        // the first map of a dynamic buffer in a frame, or in a command list,
+        // must discard its contents.
+        self.reset_dynamic_buffers();
+        if let Some(budget) = self.options.texture_budget {
+            let evicted = self.texture_pool.evict(budget);
+            self.damage.invalidate_textures(evicted);
+        }
+        self.texture_pool.begin_frame();
+        self.apply_textures_delta(device_context, textures_delta)
+    }
+
+    /// Apply `textures_delta` to the texture pool within the current frame.
+    fn apply_textures_delta(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        textures_delta: &TexturesDelta,
+    ) -> Result<()> {
+        // the delta is empty in most frames of an idle UI, for which there
+        // is nothing to annotate either.
+        self.textures_changed = false;
+        if textures_delta.is_empty() {
             return Ok(());
         }
+        let _annotation = Annotation::begin(
+            device_context,
+            self.annotation_prefix.as_deref(),
+            "egui-directx11: update textures",
+        );
+        self.damage.invalidate_textures(
+            textures_delta.set.iter().map(|(tid, _)| *tid),
+        );
+        self.textures_changed = self.texture_pool.update(
+            device_context,
+            textures_delta,
+            self.options.texture_upload_strategy,
+        )?;
+        Ok(())
+    }
+
+    /// Get how an internal render target is used according to the options,
+    /// or `None` if `egui` is drawn directly into the render target.
+    fn offscreen_mode(&self) -> Option<OffscreenMode> {
+        let supersample = self.options.supersample;
+        let supersample = if supersample.is_finite() && supersample > 0. {
+            supersample
+        } else {
+            warn!(
+                "invalid supersample factor {supersample}. 1.0 will be used."
+            );
+            1.
+        };
+        let render_scale = self.options.render_scale;
+        let render_scale = if render_scale.is_finite() && render_scale > 0. {
+            render_scale
+        } else {
+            warn!("invalid render scale {render_scale}. 1.0 will be used.");
+            1.
+        };
+        let supersample = supersample * render_scale;
+        let pixel_scale = self.options.pixel_scale;
+        let pixel_scale = if pixel_scale > 0 {
+            pixel_scale
+        } else {
+            warn!("invalid pixel scale 0. 1 will be used.");
+            1
+        };
+        if pixel_scale != 1 {
+            Some(OffscreenMode::PixelScale(pixel_scale))
+        } else if supersample != 1. {
+            Some(OffscreenMode::Supersample(supersample))
+        } else if self.options.straight_alpha_output {
+            Some(OffscreenMode::Supersample(1.))
+        } else {
+            None
+        }
+    }
 
-        let frame_size = Self::get_render_target_size(render_target)?;
-        let frame_size_scaled = (
-            frame_size.0 as f32 / scale_factor,
-            frame_size.1 as f32 / scale_factor,
+    #[allow(clippy::too_many_arguments)]
+    fn draw_in_mode(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        meshes: Vec<ClippedMesh>,
+        scale_factor: f32,
+        zoom_factor: f32,
+        mode: Option<OffscreenMode>,
+        bind_render_target: bool,
+    ) -> Result<()> {
+        let _annotation = Annotation::begin(
+            device_context,
+            self.annotation_prefix.as_deref(),
+            "egui-directx11: draw",
         );
-        let zoom_factor = egui_ctx.zoom_factor();
+        if let Some(mode) = mode {
+            self.render_offscreen(
+                device_context,
+                render_target,
+                meshes,
+                scale_factor,
+                zoom_factor,
+                mode,
+            )
+        } else {
+            let viewport = self.get_viewport(render_target)?;
+            self.draw_primitives(
+                device_context,
+                bind_render_target.then_some(render_target),
+                viewport,
+                meshes,
+                scale_factor,
+                zoom_factor,
+            )
+        }
+    }
 
-        self.setup(device_context, render_target, frame_size);
-        let meshes = egui_ctx
-            .tessellate(egui_output.shapes, egui_output.pixels_per_point)
-            .into_iter()
-            .filter_map(
-                |ClippedPrimitive {
-                     primitive,
-                     clip_rect,
-                 }| match primitive {
-                    Primitive::Mesh(mesh) => Some((mesh, clip_rect)),
-                    Primitive::Callback(..) => {
-                        log::warn!("paint callbacks are not yet supported.");
-                        None
-                    },
+    fn draw_primitives(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: Option<&ID3D11RenderTargetView>,
+        viewport: RECT,
+        meshes: Vec<ClippedMesh>,
+        scale_factor: f32,
+        zoom_factor: f32,
+    ) -> Result<()> {
+        let scratch = self.prepare_primitives(
+            device_context,
+            render_target,
+            viewport,
+            meshes,
+            scale_factor,
+            zoom_factor,
+        )?;
+        let meshes = scratch.meshes();
+        let result = self.with_occlusion_query(device_context, |this| {
+            match this.options.buffer_strategy {
+                BufferStrategy::PerMesh => {
+                    for mesh in meshes {
+                        this.bind_mesh_buffers(device_context, mesh)?;
+                        this.draw_mesh(device_context, mesh, 0, 0)?;
+                    }
                 },
-            )
-            .filter_map(|(mesh, clip_rect)| {
-                if mesh.indices.is_empty() {
-                    return None;
-                }
-                if mesh.indices.len() % 3 != 0 {
-                    log::warn!(concat!(
-                        "egui wants to draw a incomplete triangle. ",
-                        "this request will be ignored."
-                    ));
-                    return None;
-                }
-                Some(MeshData {
-                    vtx: mesh
-                        .vertices
-                        .into_iter()
-                        .map(|Vertex { pos, uv, color }| VertexData {
-                            pos: Pos2::new(
-                                pos.x * zoom_factor / frame_size_scaled.0 * 2.0
-                                    - 1.0,
-                                1.0 - pos.y * zoom_factor / frame_size_scaled.1
-                                    * 2.0,
-                            ),
+                BufferStrategy::SingleDynamic => {
+                    this.draw_meshes_single_dynamic(device_context, meshes)?;
+                },
+                BufferStrategy::Pooled => {
+                    this.draw_meshes_pooled(device_context, meshes)?;
+                },
+            }
+            Ok(())
+        });
+        self.finish_primitives(scratch);
+        result
+    }
+
+    /// Set up the pipeline state and stage the meshes to draw in the scratch
+    /// memory, which is taken from this renderer until it is returned with
+    /// [`Renderer::finish_primitives`] once the meshes are drawn.
+    fn prepare_primitives(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: Option<&ID3D11RenderTargetView>,
+        viewport: RECT,
+        meshes: Vec<ClippedMesh>,
+        scale_factor: f32,
+        zoom_factor: f32,
+    ) -> Result<RenderScratch> {
+        let frame_size = rect_size(&viewport);
+        let frame_size_f32 = (frame_size.0 as f32, frame_size.1 as f32);
+        let pixels_per_point = scale_factor * zoom_factor;
+
+        // colors are kept gamma-encoded when rendering in gamma space.
+        if self.options.color_space == ColorSpace::Gamma {
+            self.texture_pool.enable_gamma_views(device_context)?;
+        }
+        let transfer_function = if self.blends_in_gamma_space() {
+            TransferFunction::Gamma(1.)
+        } else {
+            self.options.transfer_function
+        };
+        let rotation = self.options.display_rotation;
+        let transform =
+            Transform::to_ndc(pixels_per_point, rotation, frame_size_f32);
+        let opacity = self.options.layer_opacity.clamp(0., 1.);
+        let convert_color = |color| {
+            let color: Rgba = transfer_function.to_linear(color);
+            color * opacity
+        };
+        let overdraw = self.options.debug_mode == DebugMode::Overdraw;
+        let mut meshes = if self.options.index_format == IndexFormat::U16 {
+            meshes
+                .into_iter()
+                .flat_map(ClippedMesh::split_to_u16)
+                .collect()
+        } else {
+            meshes
+        };
+
+        self.setup(device_context, render_target, viewport)?;
+        // positions are transformed on the GPU by the vertex shader.
+        self.bind_transform(device_context, transform)?;
+        let mut scratch = mem::take(&mut self.scratch);
+        scratch.begin_frame();
+        let mut meshes_culled = 0;
+        let meshes_incomplete = drop_incomplete_meshes(&mut meshes);
+        for mesh in meshes {
+            if mesh.indices.len() == 0 {
+                continue;
+            }
+            if self.options.cull_transparent_meshes && mesh.is_transparent() {
+                meshes_culled += 1;
+                continue;
+            }
+            let Some(scissor) = clip_rect_to_scissor(
+                rotation.transform_rect(
+                    mesh.clip_rect * pixels_per_point,
+                    frame_size_f32,
+                ),
+                frame_size,
+            ) else {
+                continue;
+            };
+            let straight = !overdraw
+                && self.texture_pool.is_straight_alpha(mesh.texture_id);
+            let (mut vtx, idx_storage) = scratch.take_storage();
+            vtx.extend(mesh.vertices.into_iter().map(
+                |Vertex { pos, uv, color }| {
+                    if overdraw {
+                        VertexData {
+                            pos,
+                            uv: egui::epaint::WHITE_UV,
+                            color: DebugMode::OVERDRAW_INCREMENT,
+                        }
+                    } else {
+                        let color = convert_color(color);
+                        VertexData {
+                            pos,
                             uv,
-                            color: color.into(),
-                        })
-                        .collect(),
-                    idx: mesh.indices,
-                    tex: mesh.texture_id,
-                    clip_rect: clip_rect * scale_factor * zoom_factor,
-                })
+                            color: if straight {
+                                unpremultiply(color)
+                            } else {
+                                color
+                            },
+                        }
+                    }
+                },
+            ));
+            scratch.meshes_mut().push(MeshData {
+                vtx,
+                idx: match self.options.index_format {
+                    IndexFormat::U32 => mesh.indices.widen(),
+                    _ => mesh.indices.narrow(idx_storage),
+                },
+                tex: if overdraw {
+                    egui::TextureId::default()
+                } else {
+                    mesh.texture_id
+                },
+                scissor: RECT {
+                    left: scissor.left + viewport.left,
+                    top: scissor.top + viewport.top,
+                    right: scissor.right + viewport.left,
+                    bottom: scissor.bottom + viewport.top,
+                },
+                clip_radius: self.clip_radius(mesh.clip_rect)
+                    * pixels_per_point,
+                topology: mesh.topology,
             });
-        for mesh in meshes {
-            Self::draw_mesh(
-                &self.device,
-                device_context,
-                &self.texture_pool,
-                mesh,
-            )?;
         }
-        Ok(())
+
+        let mut meshes_skipped = 0;
+        if let Some(max_meshes) = self.options.max_meshes {
+            let len = scratch.meshes().len();
+            if len > max_meshes {
+                meshes_skipped = len - max_meshes;
+                warn!(
+                    concat!(
+                        "egui wants to draw {} meshes, exceeding the limit of ",
+                        "{}. the remaining {} meshes will be skipped."
+                    ),
+                    len, max_meshes, meshes_skipped,
+                );
+                scratch.truncate(max_meshes);
+            }
+        }
+        let meshes = scratch.meshes();
+        self.stats = RenderStats {
+            meshes_drawn: meshes.len(),
+            meshes_skipped,
+            meshes_culled,
+            meshes_incomplete,
+            textures_changed: self.textures_changed,
+        };
+        self.damage.update(
+            viewport,
+            meshes,
+            transform,
+            self.options.damage_tracking,
+        );
+        self.rounded_clip_bound = None;
+        Ok(scratch)
     }
 
+    /// Mark the textures of the meshes staged in `scratch` as used and return
+    /// `scratch` to this renderer.
+    fn finish_primitives(&mut self, scratch: RenderScratch) {
+        self.texture_pool
+            .mark_used(scratch.meshes().iter().map(|mesh| mesh.tex));
+        self.scratch = scratch;
+    }
+
+    /// Set up the pipeline state for rendering into the region `viewport` of
+    /// the render target. If `render_target` is `None`, the render target
+    /// currently bound is used.
     fn setup(
         &mut self,
         ctx: &ID3D11DeviceContext,
-        render_target: &ID3D11RenderTargetView,
-        frame_size: (u32, u32),
-    ) {
+        render_target: Option<&ID3D11RenderTargetView>,
+        viewport: RECT,
+    ) -> Result<()> {
+        self.update_states()?;
         unsafe {
             ctx.IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
             ctx.IASetInputLayout(&self.input_layout);
-            ctx.VSSetShader(&self.vertex_shader, None);
-            ctx.PSSetShader(&self.pixel_shader, None);
+            ctx.VSSetShader(
+                self.custom_vertex_shader
+                    .as_ref()
+                    .unwrap_or(&self.transform_vertex_shader),
+                None,
+            );
+            ctx.PSSetShader(self.active_pixel_shader(), None);
             ctx.RSSetState(&self.rasterizer_state);
             ctx.RSSetViewports(Some(&[D3D11_VIEWPORT {
-                TopLeftX: 0.,
-                TopLeftY: 0.,
-                Width: frame_size.0 as _,
-                Height: frame_size.1 as _,
+                TopLeftX: viewport.left as _,
+                TopLeftY: viewport.top as _,
+                Width: (viewport.right - viewport.left) as _,
+                Height: (viewport.bottom - viewport.top) as _,
                 MinDepth: 0.,
                 MaxDepth: 1.,
             }]));
-            ctx.PSSetSamplers(0, Some(&[Some(self.sampler_state.clone())]));
-            ctx.OMSetRenderTargets(Some(&[Some(render_target.clone())]), None);
+            ctx.PSSetSamplers(0, Some(&[Some(self.sampler_state().clone())]));
+            if let Some(render_target) = render_target {
+                // unbind the texture sampled last, which may be the render
+                // target itself when ping-ponging between render targets, to
+                // avoid a read-write hazard.
+                ctx.PSSetShaderResources(self.options.srv_slot, Some(&[None]));
+                ctx.OMSetRenderTargets(
+                    Some(&[Some(render_target.clone())]),
+                    self.stencil_mask.as_ref().map(|m| &m.depth_stencil_view),
+                );
+            }
+            match &self.secondary_texture {
+                Some((slot, _)) if *slot == self.options.srv_slot => {
+                    warn!(
+                        concat!(
+                            "the secondary texture is bound to slot {}, which ",
+                            "is the slot of the textures of meshes. it will ",
+                            "be ignored."
+                        ),
+                        slot
+                    );
+                },
+                Some((slot, texture)) => {
+                    ctx.PSSetShaderResources(
+                        *slot,
+                        Some(&[Some(texture.clone())]),
+                    );
+                },
+                None => {},
+            }
+            if let Some(stencil_mask) = &self.stencil_mask {
+                ctx.OMSetDepthStencilState(
+                    &stencil_mask.depth_stencil_state,
+                    stencil_mask.stencil_ref,
+                );
+            } else {
+                self.bind_depth_stencil_state(ctx)?;
+            }
             ctx.OMSetBlendState(&self.blend_state, Some(&[0.; 4]), u32::MAX);
+            self.straight_bound = false;
         }
+        self.topology_bound = MeshTopology::TriangleList;
+        Ok(())
     }
 
-    fn draw_mesh(
-        device: &ID3D11Device,
+    /// Get the pixel shader to draw meshes with. The built-in pixel shader is
+    /// used in debug modes.
+    fn active_pixel_shader(&self) -> &ID3D11PixelShader {
+        if self.options.debug_mode != DebugMode::None {
+            &self.pixel_shader
+        } else if let Some(pixel_shader) = &self.custom_pixel_shader {
+            pixel_shader
+        } else if self.multiple_targets {
+            &self.mrt_pixel_shader
+        } else if self.dual_source_enabled() {
+            &self.dual_source_pixel_shader
+        } else if self.rounded_clip_enabled() {
+            &self.rounded_clip_pixel_shader
+        } else {
+            &self.pixel_shader
+        }
+    }
+
+    fn sampler_state(&self) -> &ID3D11SamplerState {
+        self.custom_sampler_state
+            .as_ref()
+            .unwrap_or(&self.sampler_state)
+    }
+
+    /// Get the sampler state to sample the texture `tid` with.
+    fn texture_sampler_state(
+        &self,
+        tid: egui::TextureId,
+    ) -> &ID3D11SamplerState {
+        let managed = matches!(tid, egui::TextureId::Managed(_));
+        if managed && self.options.integer_scaling {
+            return &self.point_sampler_state;
+        }
+        if let Some(custom_sampler_state) = &self.custom_sampler_state {
+            return custom_sampler_state;
+        }
+        self.texture_pool
+            .texture_options(tid)
+            .and_then(|options| {
+                self.texture_samplers.iter().find(|(o, _)| *o == options)
+            })
+            .map_or(&self.sampler_state, |(_, sampler_state)| sampler_state)
+    }
+
+    /// Create immutable vertex and index buffers for a single mesh and bind
+    /// them to the input assembly stage.
+    fn bind_mesh_buffers(
+        &self,
         device_context: &ID3D11DeviceContext,
-        texture_pool: &TexturePool,
-        mesh: MeshData,
+        mesh: &MeshData,
     ) -> Result<()> {
-        let vb = Self::create_index_buffer(device, &mesh.idx)?;
-        let ib = Self::create_vertex_buffer(device, &mesh.vtx)?;
+        let vb = Self::create_vertex_buffer(&self.device, &mesh.vtx)?;
+        let (ib, ib_format) = match &mesh.idx {
+            IndexData::U16(idx) => (
+                Self::create_index_buffer(&self.device, idx)?,
+                DXGI_FORMAT_R16_UINT,
+            ),
+            IndexData::U32(idx) => (
+                Self::create_index_buffer(&self.device, idx)?,
+                DXGI_FORMAT_R32_UINT,
+            ),
+        };
         unsafe {
             device_context.IASetVertexBuffers(
                 0,
                 1,
-                Some(&Some(ib)),
+                Some(&Some(vb)),
                 Some(&(mem::size_of::<VertexData>() as _)),
                 Some(&0),
             );
-            device_context.IASetIndexBuffer(&vb, DXGI_FORMAT_R32_UINT, 0);
-            device_context.RSSetScissorRects(Some(&[RECT {
-                left: mesh.clip_rect.left() as _,
-                top: mesh.clip_rect.top() as _,
-                right: mesh.clip_rect.right() as _,
-                bottom: mesh.clip_rect.bottom() as _,
-            }]));
+            device_context.IASetIndexBuffer(&ib, ib_format, 0);
+        }
+        Ok(())
+    }
+
+    /// Draw a mesh whose vertices and indices are in the currently bound
+    /// buffers at `base_vertex` and `start_index` respectively.
+    fn draw_mesh(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        mesh: &MeshData,
+        start_index: u32,
+        base_vertex: i32,
+    ) -> Result<()> {
+        let sampler = self.texture_sampler_state(mesh.tex).clone();
+        unsafe { device_context.PSSetSamplers(0, Some(&[Some(sampler)])) };
+        let straight = self.texture_pool.is_straight_alpha(mesh.tex);
+        if straight != self.straight_bound {
+            let blend_state = if straight {
+                &self.straight_blend_state
+            } else {
+                &self.blend_state
+            };
+            unsafe {
+                device_context.OMSetBlendState(
+                    blend_state,
+                    Some(&[0.; 4]),
+                    u32::MAX,
+                )
+            };
+            self.straight_bound = straight;
+        }
+        if mesh.topology != self.topology_bound {
+            unsafe {
+                device_context.IASetPrimitiveTopology(mesh.topology.to_d3d())
+            };
+            self.topology_bound = mesh.topology;
         }
-        if let Some(srv) = texture_pool.get_srv(mesh.tex) {
+        unsafe { device_context.RSSetScissorRects(Some(&[mesh.scissor])) };
+        if self.rounded_clip_enabled() {
+            self.bind_rounded_clip(
+                device_context,
+                mesh.scissor,
+                mesh.clip_radius,
+            )?;
+        }
+        let srv = if self.blends_in_gamma_space() {
+            self.texture_pool.get_gamma_srv(mesh.tex)
+        } else {
+            self.texture_pool.get_srv(mesh.tex)
+        };
+        if let Some(srv) = srv {
             unsafe {
-                device_context.PSSetShaderResources(0, Some(&[Some(srv)]))
+                device_context.PSSetShaderResources(
+                    self.options.srv_slot,
+                    Some(&[Some(srv)]),
+                )
             };
         } else {
-            log::warn!(
+            warn!(
                 concat!(
                     "egui wants to sample a non-existing texture {:?}.",
                     "this request will be ignored."
@@ -345,14 +2278,33 @@ impl Renderer {
                 mesh.tex
             );
         };
-        unsafe { device_context.DrawIndexed(mesh.idx.len() as _, 0, 0) };
+        unsafe {
+            device_context.DrawIndexed(
+                mesh.idx.len() as _,
+                start_index,
+                base_vertex,
+            )
+        };
         Ok(())
     }
 }
 
 impl Renderer {
+    /// The minimum feature level of a device supported by [`Renderer`].
+    pub const MIN_FEATURE_LEVEL: D3D_FEATURE_LEVEL = D3D_FEATURE_LEVEL_11_0;
+
     const VS_BLOB: &'static [u8] = include_bytes!("../shaders/egui_vs.bin");
+    const VS_TRANSFORM_BLOB: &'static [u8] =
+        include_bytes!("../shaders/egui_vs_transform.bin");
     const PS_BLOB: &'static [u8] = include_bytes!("../shaders/egui_ps.bin");
+    const PS_MRT_BLOB: &'static [u8] =
+        include_bytes!("../shaders/egui_ps_mrt.bin");
+    const PS_ROUNDED_CLIP_BLOB: &'static [u8] =
+        include_bytes!("../shaders/egui_ps_rounded_clip.bin");
+    const PS_UNPREMULTIPLY_BLOB: &'static [u8] =
+        include_bytes!("../shaders/egui_ps_unpremultiply.bin");
+    const PS_DUAL_SOURCE_BLOB: &'static [u8] =
+        include_bytes!("../shaders/egui_ps_dual_source.bin");
 
     const INPUT_ELEMENTS_DESC: [D3D11_INPUT_ELEMENT_DESC; 3] = [
         D3D11_INPUT_ELEMENT_DESC {
@@ -404,14 +2356,20 @@ impl Renderer {
         AddressW: D3D11_TEXTURE_ADDRESS_BORDER,
         ComparisonFunc: D3D11_COMPARISON_ALWAYS,
         BorderColor: [1., 1., 1., 1.],
+        MaxLOD: D3D11_FLOAT32_MAX,
         ..self::zeroed()
     };
 
+    const POINT_SAMPLER_DESC: D3D11_SAMPLER_DESC = D3D11_SAMPLER_DESC {
+        Filter: D3D11_FILTER_MIN_MAG_MIP_POINT,
+        ..Self::SAMPLER_DESC
+    };
+
     const BLEND_DESC: D3D11_BLEND_DESC = D3D11_BLEND_DESC {
         RenderTarget: [
             D3D11_RENDER_TARGET_BLEND_DESC {
                 BlendEnable: BOOL(1),
-                SrcBlend: D3D11_BLEND_SRC_ALPHA,
+                SrcBlend: D3D11_BLEND_ONE,
                 DestBlend: D3D11_BLEND_INV_SRC_ALPHA,
                 BlendOp: D3D11_BLEND_OP_ADD,
                 SrcBlendAlpha: D3D11_BLEND_ONE,
@@ -455,9 +2413,9 @@ impl Renderer {
         Ok(vertex_buffer.unwrap())
     }
 
-    fn create_index_buffer(
+    fn create_index_buffer<T>(
         device: &ID3D11Device,
-        data: &[u32],
+        data: &[T],
     ) -> Result<ID3D11Buffer> {
         let mut index_buffer = None;
         unsafe {
@@ -478,12 +2436,424 @@ impl Renderer {
         Ok(index_buffer.unwrap())
     }
 
+    /// Get the region of `render_target` which `egui` is drawn into, as
+    /// specified by [`RenderOptions::viewport`].
+    fn get_viewport(
+        &self,
+        render_target: &ID3D11RenderTargetView,
+    ) -> Result<RECT> {
+        let (width, height) = Self::get_render_target_size(render_target)?;
+        Ok(match self.options.viewport {
+            Some(viewport)
+                if viewport.left < viewport.right
+                    && viewport.top < viewport.bottom =>
+            {
+                viewport
+            },
+            Some(viewport) => {
+                warn!(
+                    "invalid viewport {viewport:?}. the whole render target will be used."
+                );
+                RECT {
+                    left: 0,
+                    top: 0,
+                    right: width as _,
+                    bottom: height as _,
+                }
+            },
+            None => RECT {
+                left: 0,
+                top: 0,
+                right: width as _,
+                bottom: height as _,
+            },
+        })
+    }
+
     fn get_render_target_size(
         rtv: &ID3D11RenderTargetView,
     ) -> Result<(u32, u32)> {
         let tex = unsafe { rtv.GetResource() }?.cast::<ID3D11Texture2D>()?;
         let mut desc = self::zeroed();
         unsafe { tex.GetDesc(&mut desc) };
-        Ok((desc.Width, desc.Height))
+        // the view selects a single slice of an array or cube texture, whose
+        // slices all have the size of the texture, but it may also select a
+        // smaller mip level.
+        let mut rtv_desc = self::zeroed();
+        unsafe { rtv.GetDesc(&mut rtv_desc) };
+        let mip_slice = unsafe {
+            match rtv_desc.ViewDimension {
+                D3D11_RTV_DIMENSION_TEXTURE2D => {
+                    rtv_desc.Anonymous.Texture2D.MipSlice
+                },
+                D3D11_RTV_DIMENSION_TEXTURE2DARRAY => {
+                    rtv_desc.Anonymous.Texture2DArray.MipSlice
+                },
+                _ => 0,
+            }
+        };
+        Ok((
+            (desc.Width >> mip_slice).max(1),
+            (desc.Height >> mip_slice).max(1),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mesh(vertex_count: usize, indices: Vec<u32>) -> ClippedMesh {
+        ClippedMesh {
+            clip_rect: egui::Rect::EVERYTHING,
+            vertices: vec![Vertex::default(); vertex_count],
+            indices: IndexData::U32(indices),
+            texture_id: egui::TextureId::default(),
+            topology: MeshTopology::TriangleList,
+        }
+    }
+
+    fn rect(min: (f32, f32), max: (f32, f32)) -> egui::Rect {
+        egui::Rect::from_min_max(min.into(), max.into())
+    }
+
+    fn output(
+        clip_rects: &[egui::Rect],
+        set: &[u64],
+        free: &[u64],
+    ) -> RendererOutput {
+        let image = egui::ColorImage::new([1, 1], egui::Color32::WHITE);
+        let delta = egui::epaint::ImageDelta::full(
+            image,
+            egui::TextureOptions::default(),
+        );
+        RendererOutput {
+            textures_delta: TexturesDelta {
+                set: set
+                    .iter()
+                    .map(|&id| (egui::TextureId::Managed(id), delta.clone()))
+                    .collect(),
+                free: free
+                    .iter()
+                    .copied()
+                    .map(egui::TextureId::Managed)
+                    .collect(),
+            },
+            shapes: clip_rects
+                .iter()
+                .map(|&clip_rect| ClippedShape {
+                    clip_rect,
+                    shape: egui::Shape::Noop,
+                })
+                .collect(),
+            pixels_per_point: 1.,
+        }
+    }
+
+    fn set_ids(output: &RendererOutput) -> Vec<egui::TextureId> {
+        output
+            .textures_delta
+            .set
+            .iter()
+            .map(|(tid, _)| *tid)
+            .collect()
+    }
+
+    #[test]
+    fn merge_appends_shapes_and_deltas() {
+        let (a, b) = (rect((0., 0.), (1., 1.)), rect((2., 2.), (3., 3.)));
+        let mut merged = output(&[a], &[1], &[2]);
+        merged.merge(output(&[b], &[3], &[4])).unwrap();
+        let clip_rects: Vec<_> =
+            merged.shapes.iter().map(|s| s.clip_rect).collect();
+        assert_eq!(clip_rects, [a, b]);
+        assert_eq!(set_ids(&merged), [1, 3].map(egui::TextureId::Managed));
+        assert_eq!(
+            merged.textures_delta.free,
+            [2, 4].map(egui::TextureId::Managed)
+        );
+    }
+
+    #[test]
+    fn merge_keeps_textures_set_again() {
+        let mut merged = output(&[], &[], &[1, 2]);
+        merged.merge(output(&[], &[1], &[])).unwrap();
+        assert_eq!(set_ids(&merged), [egui::TextureId::Managed(1)]);
+        assert_eq!(merged.textures_delta.free, [egui::TextureId::Managed(2)]);
+    }
+
+    #[test]
+    fn merge_rejects_different_pixels_per_point() {
+        let mut merged = output(&[egui::Rect::EVERYTHING], &[1], &[]);
+        let mut other = output(&[egui::Rect::EVERYTHING], &[2], &[]);
+        other.pixels_per_point = 2.;
+        assert!(merged.merge(other).is_err());
+        assert_eq!(merged.shapes.len(), 1);
+        assert_eq!(set_ids(&merged), [egui::TextureId::Managed(1)]);
+    }
+
+    #[test]
+    fn scissor_rounds_to_nearest_pixel() {
+        let scissor =
+            clip_rect_to_scissor(rect((1.4, 2.6), (10.5, 20.2)), (64, 64));
+        assert_eq!(
+            scissor,
+            Some(RECT {
+                left: 1,
+                top: 3,
+                right: 11,
+                bottom: 20,
+            })
+        );
+    }
+
+    #[test]
+    fn scissor_rejects_inverted_rects() {
+        assert_eq!(
+            clip_rect_to_scissor(rect((10., 0.), (5., 8.)), (64, 64)),
+            None
+        );
+        assert_eq!(
+            clip_rect_to_scissor(rect((0., 10.), (8., 5.)), (64, 64)),
+            None
+        );
+        assert_eq!(clip_rect_to_scissor(egui::Rect::NOTHING, (64, 64)), None);
+        assert_eq!(clip_rect_to_scissor(egui::Rect::NAN, (64, 64)), None);
+    }
+
+    #[test]
+    fn scissor_is_clamped_to_target() {
+        assert_eq!(
+            clip_rect_to_scissor(egui::Rect::EVERYTHING, (64, 32)),
+            Some(RECT {
+                left: 0,
+                top: 0,
+                right: 64,
+                bottom: 32,
+            })
+        );
+        assert_eq!(
+            clip_rect_to_scissor(rect((-8., 16.), (16., 48.)), (64, 32)),
+            Some(RECT {
+                left: 0,
+                top: 16,
+                right: 16,
+                bottom: 32,
+            })
+        );
+    }
+
+    #[test]
+    fn scissor_rejects_rects_outside_target() {
+        let size = (64, 32);
+        assert_eq!(
+            clip_rect_to_scissor(rect((70., 0.), (80., 8.)), size),
+            None
+        );
+        assert_eq!(
+            clip_rect_to_scissor(rect((0., 40.), (8., 50.)), size),
+            None
+        );
+        assert_eq!(
+            clip_rect_to_scissor(rect((-9., 0.), (-1., 8.)), size),
+            None
+        );
+    }
+
+    /// Blend `vertex_color` times `texel` onto transparent black like the
+    /// built-in pixel shader and the blend state of straight-alpha textures.
+    fn blend_straight(vertex_color: Rgba, texel: Rgba) -> Rgba {
+        let src = vertex_color * texel;
+        Rgba::from_rgba_premultiplied(
+            src.r() * src.a(),
+            src.g() * src.a(),
+            src.b() * src.a(),
+            src.a(),
+        )
+    }
+
+    #[test]
+    fn straight_alpha_is_applied_once() {
+        // white at half opacity, as premultiplied by `egui`.
+        let vertex_color = Rgba::from_rgba_premultiplied(0.5, 0.5, 0.5, 0.5);
+        // orange at half opacity, as stored in a straight-alpha texture.
+        let texel = Rgba::from_rgba_premultiplied(1., 0.5, 0., 0.5);
+        let blended = blend_straight(unpremultiply(vertex_color), texel);
+        let expected = Rgba::from_rgba_unmultiplied(1., 0.5, 0., 0.25);
+        for (x, y) in blended.to_array().into_iter().zip(expected.to_array()) {
+            assert!((x - y).abs() < 1e-6, "{blended:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn unpremultiply_keeps_additive_colors() {
+        let additive = Rgba::from_rgba_premultiplied(0.5, 0., 0., 0.);
+        assert_eq!(unpremultiply(additive), additive);
+    }
+
+    #[test]
+    fn transfer_functions_decode_before_premultiplying() {
+        // premultiplied in linear space by `egui`.
+        let translucent =
+            egui::Color32::from_rgba_unmultiplied(255, 128, 0, 64);
+        let opaque = egui::Color32::from_rgb(255, 128, 0);
+        let alpha = translucent.a() as f32 / 255.;
+        for transfer_function in
+            [TransferFunction::Srgb, TransferFunction::Gamma(1.8)]
+        {
+            let expected = transfer_function.to_linear(opaque) * alpha;
+            let decoded = transfer_function.to_linear(translucent);
+            for (x, y) in
+                decoded.to_array().into_iter().zip(expected.to_array())
+            {
+                assert!((x - y).abs() < 2e-3, "{decoded:?} != {expected:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn transfer_functions_keep_additive_colors() {
+        let additive = egui::Color32::from_rgba_premultiplied(255, 0, 0, 0);
+        for transfer_function in
+            [TransferFunction::Srgb, TransferFunction::Gamma(1.8)]
+        {
+            let decoded = transfer_function.to_linear(additive);
+            assert!((decoded.r() - 1.).abs() < 1e-6, "{decoded:?}");
+            assert_eq!(decoded.a(), 0.);
+        }
+    }
+
+    #[test]
+    fn index_data_narrows_small_indices() {
+        let idx = IndexData::U32(vec![0, 1, u16::MAX as u32]).narrow(None);
+        assert_eq!(idx.len(), 3);
+        assert_eq!(idx.into_u16(), Some(vec![0, 1, u16::MAX]));
+    }
+
+    #[test]
+    fn index_data_keeps_large_indices() {
+        let idx = IndexData::U32(vec![0, u16::MAX as u32 + 1]).narrow(None);
+        assert!(matches!(&idx, IndexData::U32(i) if i.len() == 2));
+        assert_eq!(idx.into_u16(), None);
+    }
+
+    #[test]
+    fn index_data_narrows_into_storage() {
+        let storage = Vec::with_capacity(16);
+        let ptr = storage.as_ptr();
+        let idx = IndexData::U32(vec![2, 1, 0]).narrow(Some(storage));
+        let IndexData::U16(idx) = idx else {
+            panic!("indices should have been narrowed");
+        };
+        assert_eq!(idx, [2, 1, 0]);
+        assert_eq!(idx.as_ptr(), ptr);
+    }
+
+    #[test]
+    fn index_data_widens() {
+        let idx = IndexData::U16(vec![3, 4, 5]).widen();
+        assert!(matches!(idx, IndexData::U32(i) if i == [3, 4, 5]));
+    }
+
+    #[test]
+    fn transparent_black_meshes_are_transparent() {
+        let mut mesh = mesh(3, vec![0, 1, 2]);
+        for v in &mut mesh.vertices {
+            v.color = egui::Color32::TRANSPARENT;
+        }
+        mesh.texture_id = egui::TextureId::User(0);
+        assert!(mesh.is_transparent());
+    }
+
+    #[test]
+    fn additive_meshes_are_not_transparent() {
+        let mut mesh = mesh(3, vec![0, 1, 2]);
+        for v in &mut mesh.vertices {
+            v.color = egui::Color32::TRANSPARENT;
+        }
+        mesh.vertices[1].color =
+            egui::Color32::from_rgba_premultiplied(255, 0, 0, 0);
+        assert!(!mesh.is_transparent());
+    }
+
+    /// Count the warnings about incomplete meshes, which only
+    /// [`drop_incomplete_meshes`] logs among the tests.
+    #[cfg(feature = "log")]
+    static INCOMPLETE_WARNINGS: std::sync::atomic::AtomicUsize =
+        std::sync::atomic::AtomicUsize::new(0);
+
+    #[cfg(feature = "log")]
+    struct WarningCounter;
+
+    #[cfg(feature = "log")]
+    impl log::Log for WarningCounter {
+        fn enabled(&self, metadata: &log::Metadata) -> bool {
+            metadata.level() <= log::Level::Warn
+        }
+
+        fn log(&self, record: &log::Record) {
+            if record.args().to_string().contains("incomplete primitives") {
+                INCOMPLETE_WARNINGS
+                    .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    #[test]
+    fn incomplete_meshes_are_dropped_with_a_single_warning() {
+        #[cfg(feature = "log")]
+        {
+            log::set_logger(&WarningCounter).unwrap();
+            log::set_max_level(log::LevelFilter::Warn);
+        }
+        let mut lines = mesh(3, vec![0, 1, 2]);
+        lines.topology = MeshTopology::LineList;
+        let mut meshes = vec![
+            mesh(3, vec![0, 1, 2]),
+            mesh(3, vec![0, 1]),
+            mesh(3, vec![0, 1, 2, 0]),
+            lines,
+            mesh(0, Vec::new()),
+        ];
+        assert_eq!(drop_incomplete_meshes(&mut meshes), 3);
+        assert_eq!(meshes.len(), 2);
+        assert!(meshes.iter().all(|m| m.indices.len() % 3 == 0));
+        #[cfg(feature = "log")]
+        assert_eq!(
+            INCOMPLETE_WARNINGS.load(std::sync::atomic::Ordering::SeqCst),
+            1
+        );
+    }
+
+    #[test]
+    fn split_to_u16_keeps_small_meshes() {
+        let meshes = mesh(3, vec![0, 1, 2]).split_to_u16();
+        assert_eq!(meshes.len(), 1);
+        assert_eq!(meshes[0].indices.len(), 3);
+    }
+
+    #[test]
+    fn split_to_u16_splits_large_meshes() {
+        // whole triangles over more vertices than 16-bit indices can address
+        let count = 3 * (u16::MAX as usize / 3 + 1);
+        let indices = (0..count as u32).collect();
+        let meshes = mesh(count, indices).split_to_u16();
+        assert!(meshes.len() > 1);
+        for mesh in &meshes {
+            assert!(matches!(mesh.indices, IndexData::U16(_)));
+            assert!(mesh.vertices.len() <= u16::MAX as usize + 1);
+        }
+        let total: usize = meshes.iter().map(|m| m.indices.len()).sum();
+        assert_eq!(total, count);
+    }
+
+    #[test]
+    fn split_to_u16_skips_large_line_lists() {
+        let mut lines =
+            mesh(u16::MAX as usize + 2, vec![0, u16::MAX as u32 + 1]);
+        lines.topology = MeshTopology::LineList;
+        assert!(lines.split_to_u16().is_empty());
     }
 }
@@ -0,0 +1,396 @@
+use crate::{rect_size, zeroed, ClippedMesh, Renderer, VertexData};
+
+use egui::{Pos2, Rgba};
+
+use std::mem;
+
+use windows::{
+    core::Result,
+    Win32::{
+        Foundation::{BOOL, RECT},
+        Graphics::{Direct3D::*, Direct3D11::*, Dxgi::Common::*},
+    },
+};
+
+/// An internal render target which `egui` is rendered into before being
+/// blitted to the render target provided by the user.
+pub struct OffscreenTarget {
+    rtv: ID3D11RenderTargetView,
+    srv: ID3D11ShaderResourceView,
+    size: (u32, u32),
+    format: DXGI_FORMAT,
+    /// Whether the target has a full mip chain, which is generated before
+    /// downsampling it.
+    mips: bool,
+}
+
+impl OffscreenTarget {
+    pub fn new(
+        device: &ID3D11Device,
+        size: (u32, u32),
+        format: DXGI_FORMAT,
+        mips: bool,
+    ) -> Result<Self> {
+        let mut tex = None;
+        unsafe {
+            device.CreateTexture2D(
+                &D3D11_TEXTURE2D_DESC {
+                    Width: size.0,
+                    Height: size.1,
+                    // 0 for a full mip chain.
+                    MipLevels: if mips { 0 } else { 1 },
+                    ArraySize: 1,
+                    Format: format,
+                    SampleDesc: DXGI_SAMPLE_DESC {
+                        Count: 1,
+                        Quality: 0,
+                    },
+                    Usage: D3D11_USAGE_DEFAULT,
+                    BindFlags: (D3D11_BIND_RENDER_TARGET.0
+                        | D3D11_BIND_SHADER_RESOURCE.0)
+                        as _,
+                    MiscFlags: if mips {
+                        D3D11_RESOURCE_MISC_GENERATE_MIPS.0 as _
+                    } else {
+                        0
+                    },
+                    ..Default::default()
+                },
+                None,
+                Some(&mut tex),
+            )
+        }?;
+        let tex = tex.unwrap();
+
+        // the render target view targets mip 0, and the shader resource view
+        // covers all mips.
+        let mut rtv = None;
+        unsafe { device.CreateRenderTargetView(&tex, None, Some(&mut rtv)) }?;
+        let mut srv = None;
+        unsafe { device.CreateShaderResourceView(&tex, None, Some(&mut srv)) }?;
+        Ok(Self {
+            rtv: rtv.unwrap(),
+            srv: srv.unwrap(),
+            size,
+            format,
+            mips,
+        })
+    }
+}
+
+/// Pipeline states used to blit an [`OffscreenTarget`] to the render target.
+pub struct BlitStates {
+    blend_state: ID3D11BlendState,
+    opaque_blend_state: ID3D11BlendState,
+    linear_sampler: ID3D11SamplerState,
+    point_sampler: ID3D11SamplerState,
+    /// The channels of the render target written by the blend states.
+    color_write_mask: D3D11_COLOR_WRITE_ENABLE,
+}
+
+impl BlitStates {
+    pub fn new(
+        device: &ID3D11Device,
+        color_write_mask: D3D11_COLOR_WRITE_ENABLE,
+    ) -> Result<Self> {
+        let mut blend_state = None;
+        let mut opaque_blend_state = None;
+        let mut linear_sampler = None;
+        let mut point_sampler = None;
+        let mut blend_desc = Self::BLEND_DESC;
+        blend_desc.RenderTarget[0].RenderTargetWriteMask =
+            color_write_mask.0 as _;
+        unsafe {
+            device.CreateBlendState(&blend_desc, Some(&mut blend_state))?;
+            let mut opaque_desc = blend_desc;
+            opaque_desc.RenderTarget[0].BlendEnable = BOOL(0);
+            device.CreateBlendState(
+                &opaque_desc,
+                Some(&mut opaque_blend_state),
+            )?;
+            device.CreateSamplerState(
+                &D3D11_SAMPLER_DESC {
+                    Filter: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+                    ..Self::SAMPLER_DESC
+                },
+                Some(&mut linear_sampler),
+            )?;
+            device.CreateSamplerState(
+                &D3D11_SAMPLER_DESC {
+                    Filter: D3D11_FILTER_MIN_MAG_MIP_POINT,
+                    ..Self::SAMPLER_DESC
+                },
+                Some(&mut point_sampler),
+            )?;
+        }
+        Ok(Self {
+            blend_state: blend_state.unwrap(),
+            opaque_blend_state: opaque_blend_state.unwrap(),
+            linear_sampler: linear_sampler.unwrap(),
+            point_sampler: point_sampler.unwrap(),
+            color_write_mask,
+        })
+    }
+
+    const SAMPLER_DESC: D3D11_SAMPLER_DESC = D3D11_SAMPLER_DESC {
+        Filter: D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+        AddressU: D3D11_TEXTURE_ADDRESS_CLAMP,
+        AddressV: D3D11_TEXTURE_ADDRESS_CLAMP,
+        AddressW: D3D11_TEXTURE_ADDRESS_CLAMP,
+        ComparisonFunc: D3D11_COMPARISON_ALWAYS,
+        ..zeroed()
+    };
+
+    // the offscreen target is cleared to transparent black before rendering,
+    // so it holds premultiplied colors after `egui` is blended onto it.
+    const BLEND_DESC: D3D11_BLEND_DESC = D3D11_BLEND_DESC {
+        RenderTarget: [
+            D3D11_RENDER_TARGET_BLEND_DESC {
+                BlendEnable: BOOL(1),
+                SrcBlend: D3D11_BLEND_ONE,
+                DestBlend: D3D11_BLEND_INV_SRC_ALPHA,
+                BlendOp: D3D11_BLEND_OP_ADD,
+                SrcBlendAlpha: D3D11_BLEND_ONE,
+                DestBlendAlpha: D3D11_BLEND_INV_SRC_ALPHA,
+                BlendOpAlpha: D3D11_BLEND_OP_ADD,
+                RenderTargetWriteMask: D3D11_COLOR_WRITE_ENABLE_ALL.0 as _,
+            },
+            zeroed(),
+            zeroed(),
+            zeroed(),
+            zeroed(),
+            zeroed(),
+            zeroed(),
+            zeroed(),
+        ],
+        ..zeroed()
+    };
+}
+
+/// How the internal render target is sized relative to the render target and
+/// blitted to it.
+#[derive(Clone, Copy)]
+pub enum OffscreenMode {
+    /// Render at the size of the render target scaled by the factor, and
+    /// downsample to the whole render target with linear filtering. When
+    /// downsampling, a mip chain of the internal render target is generated
+    /// and sampled trilinearly, so that each texel of the render target
+    /// averages a box of about `factor` x `factor` texels instead of a
+    /// single bilinear tap, which would alias for factors above 2.
+    Supersample(f32),
+    /// Render at the size of the render target divided by the factor, and
+    /// upscale by exactly the factor with point filtering. Pixels on the
+    /// right and bottom edges not covered by the upscaled target are left
+    /// untouched.
+    PixelScale(u32),
+}
+
+impl Renderer {
+    /// Render `meshes` into an internal render target sized according to
+    /// `mode`, and then blit it to `render_target`.
+    pub(crate) fn render_offscreen(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        meshes: Vec<ClippedMesh>,
+        scale_factor: f32,
+        zoom_factor: f32,
+        mode: OffscreenMode,
+    ) -> Result<()> {
+        let viewport = self.get_viewport(render_target)?;
+        let frame_size = rect_size(&viewport);
+        let mips = matches!(mode, OffscreenMode::Supersample(s) if s > 1.);
+        let (internal_size, blit_size, scale, filter) = match mode {
+            OffscreenMode::Supersample(scale) => (
+                (
+                    ((frame_size.0 as f32 * scale).round() as u32).max(1),
+                    ((frame_size.1 as f32 * scale).round() as u32).max(1),
+                ),
+                frame_size,
+                scale,
+                D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+            ),
+            OffscreenMode::PixelScale(factor) => {
+                let internal_size = (
+                    (frame_size.0 / factor).max(1),
+                    (frame_size.1 / factor).max(1),
+                );
+                (
+                    internal_size,
+                    (internal_size.0 * factor, internal_size.1 * factor),
+                    1. / factor as f32,
+                    D3D11_FILTER_MIN_MAG_MIP_POINT,
+                )
+            },
+        };
+        let format = {
+            let mut desc = zeroed();
+            unsafe { render_target.GetDesc(&mut desc) };
+            desc.Format
+        };
+
+        if !self.offscreen_target.as_ref().is_some_and(|t| {
+            t.size == internal_size && t.format == format && t.mips == mips
+        }) {
+            // drop the old target first to keep the peak memory usage low.
+            self.offscreen_target = None;
+            self.offscreen_target = Some(OffscreenTarget::new(
+                &self.device,
+                internal_size,
+                format,
+                mips,
+            )?);
+        }
+
+        let (internal_rtv, internal_srv) = {
+            let target = self.offscreen_target.as_ref().unwrap();
+            (target.rtv.clone(), target.srv.clone())
+        };
+        unsafe {
+            device_context.ClearRenderTargetView(&internal_rtv, &[0.; 4])
+        };
+        // the stencil mask has the size of the render target, so it is only
+        // applied when blitting, and so is the color write mask, as the
+        // internal target must hold all channels to be blitted.
+        let stencil_mask = self.stencil_mask.take();
+        let color_write_mask = mem::replace(
+            &mut self.options.color_write_mask,
+            D3D11_COLOR_WRITE_ENABLE_ALL,
+        );
+        let result = self.draw_primitives(
+            device_context,
+            Some(&internal_rtv),
+            RECT {
+                left: 0,
+                top: 0,
+                right: internal_size.0 as _,
+                bottom: internal_size.1 as _,
+            },
+            meshes,
+            scale_factor * scale,
+            zoom_factor,
+        );
+        self.options.color_write_mask = color_write_mask;
+        self.stencil_mask = stencil_mask;
+        result?;
+        if mips {
+            unsafe { device_context.GenerateMips(&internal_srv) };
+        }
+        self.damage.downscale(scale, viewport);
+        self.blit(
+            device_context,
+            render_target,
+            RECT {
+                right: viewport.left + blit_size.0 as i32,
+                bottom: viewport.top + blit_size.1 as i32,
+                ..viewport
+            },
+            &internal_srv,
+            filter,
+            true,
+        )
+    }
+
+    /// Blit `srv` to the region `blit_rect` of `render_target`. If
+    /// `unpremultiply` is set, the colors are unpremultiplied as requested
+    /// with [`RenderOptions::straight_alpha_output`](crate::RenderOptions::straight_alpha_output),
+    /// replacing the contents of the region.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn blit(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        blit_rect: RECT,
+        srv: &ID3D11ShaderResourceView,
+        filter: D3D11_FILTER,
+        unpremultiply: bool,
+    ) -> Result<()> {
+        let white = Rgba::WHITE;
+        let vtx = [
+            VertexData {
+                pos: Pos2::new(-1., 1.),
+                uv: Pos2::new(0., 0.),
+                color: white,
+            },
+            VertexData {
+                pos: Pos2::new(1., 1.),
+                uv: Pos2::new(1., 0.),
+                color: white,
+            },
+            VertexData {
+                pos: Pos2::new(-1., -1.),
+                uv: Pos2::new(0., 1.),
+                color: white,
+            },
+            VertexData {
+                pos: Pos2::new(1., -1.),
+                uv: Pos2::new(1., 1.),
+                color: white,
+            },
+        ];
+        let idx: [u16; 6] = [0, 1, 2, 2, 1, 3];
+        let vb = Self::create_vertex_buffer(&self.device, &vtx)?;
+        let ib = Self::create_index_buffer(&self.device, &idx)?;
+
+        let color_write_mask = self.options.color_write_mask;
+        if self
+            .blit_states
+            .as_ref()
+            .is_none_or(|s| s.color_write_mask != color_write_mask)
+        {
+            self.blit_states =
+                Some(BlitStates::new(&self.device, color_write_mask)?);
+        }
+
+        let unpremultiply = unpremultiply && self.options.straight_alpha_output;
+        self.setup(device_context, Some(render_target), blit_rect)?;
+        let states = self.blit_states.as_ref().unwrap();
+        let sampler = if filter == D3D11_FILTER_MIN_MAG_MIP_POINT {
+            &states.point_sampler
+        } else {
+            &states.linear_sampler
+        };
+        unsafe {
+            device_context.IASetVertexBuffers(
+                0,
+                1,
+                Some(&Some(vb)),
+                Some(&(std::mem::size_of::<VertexData>() as _)),
+                Some(&0),
+            );
+            device_context.IASetIndexBuffer(&ib, DXGI_FORMAT_R16_UINT, 0);
+            device_context
+                .IASetPrimitiveTopology(D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST);
+            device_context.RSSetScissorRects(Some(&[blit_rect]));
+            device_context.PSSetSamplers(0, Some(&[Some(sampler.clone())]));
+            device_context.OMSetBlendState(
+                if unpremultiply {
+                    &states.opaque_blend_state
+                } else {
+                    &states.blend_state
+                },
+                Some(&[0.; 4]),
+                u32::MAX,
+            );
+            // the internal target is always blitted with the built-in
+            // shaders, as the vertices are already in normalized device
+            // coordinates and the built-in pixel shaders sample slot 0.
+            device_context.VSSetShader(&self.vertex_shader, None);
+            device_context.PSSetShader(
+                if unpremultiply {
+                    &self.unpremultiply_pixel_shader
+                } else {
+                    &self.pixel_shader
+                },
+                None,
+            );
+            device_context.PSSetShaderResources(0, Some(&[Some(srv.clone())]));
+            device_context.DrawIndexed(idx.len() as _, 0, 0);
+
+            // unbind the internal target so that it can be bound as a render
+            // target again in the next frame without a read-write hazard.
+            device_context.PSSetShaderResources(0, Some(&[None]));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,632 @@
+use windows::Win32::{
+    Foundation::RECT,
+    Graphics::{
+        Direct3D::{
+            D3D11_PRIMITIVE_TOPOLOGY_LINELIST,
+            D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST, D3D_PRIMITIVE_TOPOLOGY,
+        },
+        Direct3D11::{D3D11_COLOR_WRITE_ENABLE, D3D11_COLOR_WRITE_ENABLE_ALL},
+    },
+};
+
+/// Options controlling how a [`Renderer`](crate::Renderer) renders the output
+/// of `egui`.
+///
+/// The options can be changed at any time between two calls to
+/// [`Renderer::render`](crate::Renderer::render) via
+/// [`Renderer::options_mut`](crate::Renderer::options_mut). The default value
+/// of each option reproduces the behavior of a [`Renderer`](crate::Renderer)
+/// without any configuration.
+#[derive(Clone, Debug)]
+pub struct RenderOptions {
+    /// The factor by which `egui` is supersampled. Defaults to `1.0`.
+    ///
+    /// If this is not `1.0`, `egui` is tessellated and rasterized into an
+    /// internal render target whose size is that of the render target scaled
+    /// by this factor, and then downsampled into the render target through a
+    /// mip chain generated with `GenerateMips`, which averages the texels
+    /// covered by each pixel even for factors above `2.0`. A factor of `2.0`
+    /// results in noticeably smoother edges at the cost of four times the
+    /// fill rate.
+    ///
+    /// The factor must be positive. Invalid factors are treated as `1.0`.
+    pub supersample: f32,
+
+    /// The factor by which the resolution `egui` is rendered at is reduced,
+    /// e.g. `0.75` to render at three quarters of the native resolution on
+    /// very high-DPI displays, trading the legibility of text for fill rate.
+    /// Defaults to `1.0`.
+    ///
+    /// If this is not `1.0`, `egui` is rendered into an internal render
+    /// target in the same way as with [`RenderOptions::supersample`], and
+    /// upscaled into the render target with linear filtering. Both factors
+    /// are multiplied, so a supersample factor of `2.0` with a render scale
+    /// of `0.5` renders at native resolution without an internal render
+    /// target. Like `supersample`, this is ignored if
+    /// [`RenderOptions::pixel_scale`] is not `1`.
+    ///
+    /// The factor must be positive. Invalid factors are treated as `1.0`.
+    pub render_scale: f32,
+
+    /// The integer factor by which `egui` is upscaled with nearest-neighbor
+    /// filtering, for a crisp blocky look in retro or pixel-art UIs. Defaults
+    /// to `1`.
+    ///
+    /// If this is not `1`, `egui` is tessellated and rasterized into an
+    /// internal render target whose size is that of the render target divided
+    /// by this factor (rounded down), and then upscaled by exactly this factor
+    /// into the top-left corner of the render target with point filtering, so
+    /// that each pixel becomes a block of `pixel_scale * pixel_scale` pixels.
+    /// If the size of the render target is not a multiple of this factor, the
+    /// remaining pixels on its right and bottom edges are left untouched.
+    ///
+    /// This takes precedence over [`RenderOptions::supersample`], which is
+    /// ignored if this is not `1`. A factor of `0` is treated as `1`.
+    pub pixel_scale: u32,
+
+    /// Whether `egui` is displayed at an integer scale, i.e. one point in
+    /// `egui` covers an integer number of physical pixels. Defaults to
+    /// `false`.
+    ///
+    /// If this is `true`, textures managed by `egui` (including the font
+    /// atlas) are sampled with point filtering instead of linear filtering,
+    /// which avoids blurry text in pixel-perfect UIs. User textures are not
+    /// affected. This is only an assertion made by the user: enabling it at a
+    /// non-integer scale results in jagged text.
+    pub integer_scaling: bool,
+
+    /// The transfer function used to decode the gamma-encoded vertex colors
+    /// from `egui` into the linear color space the shaders work in. Defaults
+    /// to [`TransferFunction::Srgb`], the curve `egui` itself uses.
+    ///
+    /// As `egui` premultiplies colors in linear space with the sRGB curve,
+    /// translucent colors are unmultiplied before being decoded with another
+    /// curve and premultiplied again afterwards.
+    ///
+    /// Textures are always decoded by the hardware using the sRGB curve and
+    /// are not affected by this option.
+    pub transfer_function: TransferFunction,
+
+    /// The color space `egui` is blended in, which must match the format of
+    /// the render target. Defaults to [`ColorSpace::Linear`]. Use
+    /// [`Renderer::set_color_space`](crate::Renderer::set_color_space) to
+    /// change it when the format of the render target changes, e.g. when
+    /// toggling HDR.
+    pub color_space: ColorSpace,
+
+    /// The rotation applied to the output of `egui` relative to the render
+    /// target, for displays mounted in a rotated orientation while the swap
+    /// chain is not. Defaults to [`DisplayRotation::None`].
+    ///
+    /// For [`DisplayRotation::Rotate90`] and [`DisplayRotation::Rotate270`],
+    /// width and height of the screen as seen by `egui` are those of the
+    /// render target swapped, and it is *your* responsibility to report the
+    /// swapped screen size and to rotate pointer positions in the input fed
+    /// to `egui` accordingly.
+    pub display_rotation: DisplayRotation,
+
+    /// An opacity in `0.0..=1.0` multiplied to the alpha of everything drawn,
+    /// e.g. to fade out an overlay without touching the state of `egui`.
+    /// Defaults to `1.0`.
+    ///
+    /// As colors are blended with premultiplied alpha, all channels of the
+    /// vertex colors are scaled, so the output fades towards what is behind
+    /// it instead of towards black.
+    pub layer_opacity: f32,
+
+    /// Whether to enable alpha-to-coverage, which converts the alpha of the
+    /// output into a coverage mask of samples and may improve the appearance
+    /// of anti-aliased text edges. Defaults to `false`.
+    ///
+    /// This only makes sense when rendering to a multisampled render target.
+    pub alpha_to_coverage: bool,
+
+    /// Whether to enable depth clipping in the rasterizer state, which clips
+    /// geometry outside the depth range of the viewport. Defaults to
+    /// `false`.
+    ///
+    /// `egui` is drawn at a depth of `0.0`, so this only matters when
+    /// compositing with a depth buffer, e.g. with a stencil mask set with
+    /// [`Renderer::set_stencil_mask`](crate::Renderer::set_stencil_mask) or
+    /// with [`Renderer::render_into_current_target`](crate::Renderer::render_into_current_target),
+    /// where some setups expect depth clipping to be consistent with the
+    /// rest of the scene.
+    pub depth_clip: bool,
+
+    /// Whether to rasterize with conservative rasterization, which draws
+    /// every pixel touched by a primitive, e.g. for a picking pass writing
+    /// widget ids with
+    /// [`Renderer::render_to_multiple_targets`](crate::Renderer::render_to_multiple_targets),
+    /// so that thin widgets can still be hit. Defaults to `false`.
+    ///
+    /// Conservative rasterization requires the Direct3D 11.3 runtime
+    /// (`ID3D11Device3`) and a device supporting at least
+    /// `D3D11_CONSERVATIVE_RASTERIZATION_TIER_1`. Otherwise, a warning is
+    /// logged and `egui` is rasterized normally. As anti-aliased edges are
+    /// widened as well, this is not meant for rendering `egui` to the
+    /// screen.
+    pub conservative_rasterization: bool,
+
+    /// The shader resource slot in the pixel shader stage which textures are
+    /// bound to. Defaults to `0`, which is the slot the built-in pixel shader
+    /// samples from.
+    ///
+    /// This is only useful with a custom pixel shader set with
+    /// [`Renderer::set_pixel_shader`](crate::Renderer::set_pixel_shader)
+    /// which expects its texture at another slot, e.g. because slot 0 is used
+    /// by some other effect. It must be less than
+    /// `D3D11_COMMONSHADER_INPUT_RESOURCE_SLOT_COUNT`.
+    pub srv_slot: u32,
+
+    /// How vertex and index buffers are allocated for the meshes to draw.
+    /// Defaults to [`BufferStrategy::PerMesh`].
+    pub buffer_strategy: BufferStrategy,
+
+    /// The format of the index buffers meshes are drawn with. Defaults to
+    /// [`IndexFormat::Auto`].
+    pub index_format: IndexFormat,
+
+    /// Whether to wrap the draws of each frame in an occlusion query, whose
+    /// result can be read with
+    /// [`Renderer::pixels_drawn`](crate::Renderer::pixels_drawn). Defaults to
+    /// `false`, as queries come with a small cost.
+    pub occlusion_query: bool,
+
+    /// Whether to track the region of the render target changed between
+    /// frames, which can be read with
+    /// [`Renderer::damage_rect`](crate::Renderer::damage_rect). Defaults to
+    /// `false`, as hashing all meshes of each frame comes with a cost.
+    pub damage_tracking: bool,
+
+    /// The maximum number of meshes drawn per frame, or `None` for no limit.
+    /// Defaults to `None`.
+    ///
+    /// This bounds the cost of a frame under pathological output of `egui`.
+    /// Meshes beyond the limit are skipped in the order `egui` paints them,
+    /// so the ones painted last (usually the topmost) are dropped first. The
+    /// number of meshes skipped is reported in
+    /// [`RenderStats::meshes_skipped`](crate::RenderStats::meshes_skipped).
+    pub max_meshes: Option<usize>,
+
+    /// The maximum number of bytes textures managed by `egui` may take, as
+    /// reported by
+    /// [`TexturePool::memory_report`](crate::TexturePool::memory_report), or
+    /// `None` for no limit. Defaults to `None`.
+    ///
+    /// Once all draws of a frame are done, i.e. when the textures of the next
+    /// frame are updated, the least recently sampled managed textures are
+    /// freed until the budget is met, as if `egui` had freed them. Textures
+    /// sampled in the frame just drawn and the font atlas are never freed,
+    /// so the budget may still be exceeded, with a warning.
+    ///
+    /// This is meant for memory-constrained environments and should be used
+    /// with care: `egui` is not aware of the eviction and does not upload an
+    /// evicted texture again by itself, so meshes sampling it are drawn
+    /// without it. Use
+    /// [`Renderer::set_on_texture_freed`](crate::Renderer::set_on_texture_freed)
+    /// to learn about evicted textures, e.g. to make `egui` load the images
+    /// again with [`egui::Context::forget_image`].
+    pub texture_budget: Option<usize>,
+
+    /// How textures managed by `egui` are uploaded to the GPU. Defaults to
+    /// [`TextureUploadStrategy::Dynamic`]. Textures already uploaded keep
+    /// the strategy they were created with until `egui` replaces them.
+    pub texture_upload_strategy: TextureUploadStrategy,
+
+    /// The region of the render target in pixels which `egui` is drawn into,
+    /// or `None` for the whole render target. Defaults to `None`.
+    ///
+    /// The screen as seen by `egui` is mapped to this region instead of the
+    /// whole render target, e.g. to center `egui` with black bars around it
+    /// for letterboxing. Nothing is drawn outside of it. It is *your*
+    /// responsibility to report its size as the screen size and to offset
+    /// pointer positions in the input fed to `egui` accordingly. Empty or
+    /// inverted regions are treated as `None`.
+    pub viewport: Option<RECT>,
+
+    /// The channels of the render target written to, e.g.
+    /// `D3D11_COLOR_WRITE_ENABLE_ALPHA` to write only the alpha channel for
+    /// premultiplied compositing. Defaults to `D3D11_COLOR_WRITE_ENABLE_ALL`.
+    ///
+    /// Channels not included are left untouched, but are still computed and
+    /// blended as usual for the channels included. When an internal render
+    /// target is used (see [`Renderer::render`](crate::Renderer::render)),
+    /// all channels are written to it, and the mask is applied when it is
+    /// blitted to the render target.
+    pub color_write_mask: D3D11_COLOR_WRITE_ENABLE,
+
+    /// Whether to skip meshes which are fully transparent, saving their draw
+    /// calls. Defaults to `false`.
+    ///
+    /// A mesh is skipped if all its vertex colors are transparent black,
+    /// [`egui::Color32::TRANSPARENT`], as the texture sampled is multiplied
+    /// by the vertex colors. Colors with an alpha of zero but non-zero color
+    /// channels are additive with premultiplied alpha and are still drawn.
+    /// A custom pixel shader which does not multiply by the vertex colors
+    /// should not be combined with this option. The number of meshes skipped
+    /// is reported in
+    /// [`RenderStats::meshes_culled`](crate::RenderStats::meshes_culled).
+    pub cull_transparent_meshes: bool,
+
+    /// Whether to fill the screen with the window fill color of the visuals
+    /// of `egui` (see [`egui::Visuals::window_fill`]) before drawing, so
+    /// that the render target needs no separate clear matching the theme.
+    /// Defaults to `false`.
+    ///
+    /// The fill covers [`egui::Context::screen_rect`] and is blended like
+    /// any other shape, so a translucent fill color does not completely
+    /// cover the previous contents. This applies to functions drawing the
+    /// shapes in [`RendererOutput`](crate::RendererOutput), such as
+    /// [`Renderer::render`](crate::Renderer::render), but not to
+    /// [`Renderer::render_primitives`](crate::Renderer::render_primitives)
+    /// and its variants drawing primitives tessellated beforehand.
+    pub fill_background: bool,
+
+    /// How `egui` is blended with the contents of the render target.
+    /// Defaults to [`BlendMode::Premultiplied`].
+    pub blend_mode: BlendMode,
+
+    /// Whether to write colors with straight (unpremultiplied) alpha into
+    /// the render target, for overlays exported to consumers which expect
+    /// straight alpha and would otherwise show dark fringes around
+    /// anti-aliased edges. Defaults to `false`.
+    ///
+    /// `egui` is blended with premultiplied alpha into an internal render
+    /// target cleared to transparent black, whose alpha accumulates the
+    /// coverage of `egui`, and the result is blitted to the render target
+    /// with a pixel shader dividing colors by alpha, replacing the contents
+    /// of the render target within the viewport. Pixels not covered by
+    /// `egui` are written as transparent black.
+    pub straight_alpha_output: bool,
+
+    /// Whether to blend with dual-source blending on linear render targets,
+    /// to approximate the gamma-space compositing `egui` is designed for
+    /// more closely. Defaults to `false`.
+    ///
+    /// Blending premultiplied colors on a linear render target darkens
+    /// translucent shapes over light content compared to blending them in
+    /// gamma space, as `egui` does. With this option, a built-in pixel
+    /// shader outputs the premultiplied color to `SV_Target0` and, to
+    /// `SV_Target1`, the per-channel factor the render target is multiplied
+    /// by in gamma space over white, converted back to linear, and the blend
+    /// state computes `src0 + dst * src1.rgb` for colors
+    /// (`D3D11_BLEND_SRC1_COLOR`) and `src0.a + dst.a * (1 - src1.a)` for
+    /// alpha (`D3D11_BLEND_INV_SRC1_ALPHA`). The result is exact over black
+    /// and white and closer to gamma-space compositing in between. A custom
+    /// pixel shader set with
+    /// [`Renderer::set_pixel_shader`](crate::Renderer::set_pixel_shader) must
+    /// output both targets in the same way.
+    ///
+    /// Dual-source blending is supported on all devices of feature level
+    /// 10_0 and above, thus on all devices supported by this crate, but only
+    /// with a single render target bound to slot 0.
+    /// [`Renderer::render_to_multiple_targets`](crate::Renderer::render_to_multiple_targets)
+    /// returns an error with more than one render target if this is set. It
+    /// has no effect when blending in gamma space (see [`ColorSpace::Gamma`]),
+    /// with [`BlendMode::Opaque`] or in debug modes, and takes precedence
+    /// over [`RenderOptions::rounded_clip`] with the built-in pixel shaders.
+    pub dual_source_blending: bool,
+
+    /// Clip rectangles in points paired with their corner radius in points,
+    /// e.g. the inner rectangles of rounded containers. Defaults to empty,
+    /// clipping all meshes to sharp rectangles.
+    ///
+    /// `egui` clips content to rectangles, so content inside a rounded
+    /// container may bleed into its corners. Meshes whose clip rectangle is
+    /// equal to one listed here have their corners masked with an
+    /// anti-aliased edge by a built-in pixel shader, and other meshes are
+    /// clipped to sharp rectangles as usual. The clip rectangle of each mesh
+    /// (in pixels of the render target) and its radius (in pixels, `0.0` if
+    /// not listed) are bound to constant buffer slot 0 of the pixel shader
+    /// stage, so that a custom pixel shader set with
+    /// [`Renderer::set_pixel_shader`](crate::Renderer::set_pixel_shader) may
+    /// mask the corners as well. Rounded clipping is not applied in debug
+    /// modes, nor by the built-in shader of
+    /// [`Renderer::render_to_multiple_targets`](crate::Renderer::render_to_multiple_targets).
+    pub rounded_clip: Vec<(egui::Rect, f32)>,
+
+    /// A debug visualization replacing the normal output of `egui`. Defaults
+    /// to [`DebugMode::None`].
+    pub debug_mode: DebugMode,
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        Self {
+            supersample: 1.,
+            render_scale: 1.,
+            pixel_scale: 1,
+            integer_scaling: false,
+            transfer_function: TransferFunction::Srgb,
+            color_space: ColorSpace::Linear,
+            display_rotation: DisplayRotation::None,
+            layer_opacity: 1.,
+            alpha_to_coverage: false,
+            depth_clip: false,
+            conservative_rasterization: false,
+            srv_slot: 0,
+            buffer_strategy: BufferStrategy::PerMesh,
+            index_format: IndexFormat::Auto,
+            occlusion_query: false,
+            damage_tracking: false,
+            max_meshes: None,
+            texture_budget: None,
+            texture_upload_strategy: TextureUploadStrategy::Dynamic,
+            viewport: None,
+            color_write_mask: D3D11_COLOR_WRITE_ENABLE_ALL,
+            cull_transparent_meshes: false,
+            fill_background: false,
+            blend_mode: BlendMode::Premultiplied,
+            straight_alpha_output: false,
+            dual_source_blending: false,
+            rounded_clip: Vec::new(),
+            debug_mode: DebugMode::None,
+        }
+    }
+}
+
+/// A transfer function converting gamma-encoded colors to linear colors. See
+/// [`RenderOptions::transfer_function`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TransferFunction {
+    /// The piecewise sRGB curve.
+    Srgb,
+    /// A pure power curve with the given exponent, e.g. `2.2`.
+    Gamma(f32),
+}
+
+impl TransferFunction {
+    /// Decode a premultiplied gamma-encoded color from `egui` into a
+    /// premultiplied linear color.
+    ///
+    /// `egui` premultiplies colors in linear space and encodes the result
+    /// with the sRGB curve, so decoding each channel with the sRGB curve is
+    /// exact. Other curves are applied to the unmultiplied color, which is
+    /// then premultiplied again, as the curves are not linear. Additive
+    /// colors with an alpha of zero are decoded as is.
+    pub(crate) fn to_linear(self, color: egui::Color32) -> egui::Rgba {
+        let srgb = egui::Rgba::from(color);
+        let Self::Gamma(gamma) = self else {
+            return srgb;
+        };
+        let a = srgb.a();
+        let f = |c: f32| {
+            if a == 0. {
+                egui::ecolor::gamma_from_linear(c).powf(gamma)
+            } else {
+                let c = egui::ecolor::gamma_from_linear((c / a).min(1.));
+                c.powf(gamma) * a
+            }
+        };
+        egui::Rgba::from_rgba_premultiplied(
+            f(srgb.r()),
+            f(srgb.g()),
+            f(srgb.b()),
+            a,
+        )
+    }
+}
+
+/// The color space `egui` is blended in. See [`RenderOptions::color_space`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorSpace {
+    /// Blend linear colors, for `_SRGB` render targets and float render
+    /// targets holding linear colors, e.g. `DXGI_FORMAT_R16G16B16A16_FLOAT`
+    /// for HDR (scRGB).
+    #[default]
+    Linear,
+    /// Blend gamma-encoded colors as is, for render targets with a non-sRGB
+    /// format holding gamma-encoded colors, e.g.
+    /// `DXGI_FORMAT_R8G8B8A8_UNORM`, as the reference backends of `egui` do.
+    /// Vertex colors are not decoded and [`RenderOptions::transfer_function`]
+    /// is ignored. A copy of each texture managed by `egui` is kept in a
+    /// non-sRGB format, as with
+    /// [`Renderer::render_to_srgb_target`](crate::Renderer::render_to_srgb_target),
+    /// while user textures are sampled as is.
+    Gamma,
+}
+
+/// How `egui` is blended with the contents of the render target. See
+/// [`RenderOptions::blend_mode`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Blend colors with premultiplied alpha, which is what `egui` expects.
+    #[default]
+    Premultiplied,
+    /// Disable blending and write colors as is, for UIs known to be fully
+    /// opaque, saving the cost of blending.
+    ///
+    /// Anti-aliased edges of shapes and text rely on blending their
+    /// translucent fringes, so they are hardened into dark or jagged edges,
+    /// and translucent shapes overwrite what is behind them. This takes
+    /// precedence over [`RenderOptions::dual_source_blending`], but not over
+    /// [`DebugMode::Overdraw`].
+    Opaque,
+}
+
+/// How vertex and index buffers are allocated for the meshes to draw. See
+/// [`RenderOptions::buffer_strategy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum BufferStrategy {
+    /// Create a pair of immutable vertex and index buffers for each mesh.
+    #[default]
+    PerMesh,
+    /// Upload all meshes of a frame into a single pair of dynamic vertex and
+    /// index buffers which persist across frames, mapping each buffer only
+    /// once per draw, and draw each mesh with offsets into them. This is
+    /// usually the fastest strategy for text-heavy UIs.
+    ///
+    /// The buffers are sub-allocated like a bump allocator, whose cursor is
+    /// reset each frame. Several draws in a frame, e.g. with
+    /// [`Renderer::draw`](crate::Renderer::draw), append to the buffers
+    /// without overwriting data the GPU may still read, and the buffers are
+    /// only orphaned with `D3D11_MAP_WRITE_DISCARD` at the start of a frame
+    /// or when they are exhausted, in which case they grow if a single draw
+    /// does not fit.
+    SingleDynamic,
+    /// Draw each mesh with a pair of vertex and index buffers from a pool of
+    /// buffers persisting across frames, bucketed by power-of-two sizes,
+    /// which avoids creating buffers every frame while keeping one pair of
+    /// buffers per mesh.
+    ///
+    /// As immutable buffers can not be written to again, pooled buffers
+    /// have `D3D11_USAGE_DEFAULT` and are written with `UpdateSubresource`.
+    /// The buffers used in a frame are only reused once an event query
+    /// issued at the end of the frame signals that the GPU has finished
+    /// with them, so the pool holds about as many buffers as the meshes of
+    /// the frames in flight. Buffers of a size no longer drawn with are
+    /// released after a while.
+    Pooled,
+}
+
+/// The format of the index buffers meshes are drawn with. See
+/// [`RenderOptions::index_format`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IndexFormat {
+    /// Use 16-bit indices (`DXGI_FORMAT_R16_UINT`) for meshes whose indices
+    /// fit in them, and 32-bit indices for the others.
+    #[default]
+    Auto,
+    /// Always use 16-bit indices. Meshes with more than 65536 vertices are
+    /// split into several meshes with [`egui::Mesh::split_to_u16`]. Meshes
+    /// drawn as [`MeshTopology::LineList`] can not be split and are skipped
+    /// with a warning.
+    U16,
+    /// Always use 32-bit indices (`DXGI_FORMAT_R32_UINT`), at the cost of
+    /// twice the memory bandwidth for indices.
+    U32,
+}
+
+/// How textures managed by `egui` are uploaded to the GPU. See
+/// [`RenderOptions::texture_upload_strategy`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextureUploadStrategy {
+    /// Sample the font atlas as a `D3D11_USAGE_DYNAMIC` texture, which is
+    /// written by the CPU directly. A partial update of it maps the whole
+    /// texture with `D3D11_MAP_WRITE_DISCARD` and writes all of its pixels
+    /// again from a copy kept on the CPU, which lets the driver rename the
+    /// texture instead of waiting for the GPU, but may stall on very large
+    /// atlases.
+    ///
+    /// Color images are rarely updated partially, so they are sampled in
+    /// `D3D11_USAGE_DEFAULT` instead, without a copy on the CPU, and only
+    /// the changed region of a partial update is uploaded with
+    /// `UpdateSubresource`.
+    #[default]
+    Dynamic,
+    /// Sample textures with `D3D11_USAGE_DEFAULT`, which are filled from a
+    /// `D3D11_USAGE_STAGING` texture with `CopyResource`, keeping the
+    /// sampled texture out of CPU-writable memory.
+    ///
+    /// The staging texture is kept for partial updates, which write only the
+    /// changed region into it and copy that region with
+    /// `CopySubresourceRegion`, doubling the GPU-side memory of each texture.
+    /// Mapping the staging texture waits for the GPU to finish the previous
+    /// copy from it, so an update right after another may add latency, while
+    /// each update transfers far less data than with
+    /// [`TextureUploadStrategy::Dynamic`].
+    Staging,
+}
+
+/// A debug visualization replacing the normal output of `egui`. See
+/// [`RenderOptions::debug_mode`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DebugMode {
+    /// No debug visualization.
+    #[default]
+    None,
+    /// Visualize overdraw: every mesh adds a constant small gray increment
+    /// to each pixel it covers with additive blending, regardless of its
+    /// colors and texture, so that regions drawn over many times glow
+    /// brighter. A pixel saturates to white after being drawn 10 times.
+    ///
+    /// Each mesh is drawn with its vertex colors replaced by the increment,
+    /// sampling the white texel of the font atlas, using the built-in pixel
+    /// shader. Custom pixel shaders are ignored in this mode. You usually
+    /// want to clear the render target to black first.
+    Overdraw,
+}
+
+impl DebugMode {
+    /// The linear color added to the render target for each mesh drawn in
+    /// [`DebugMode::Overdraw`].
+    pub(crate) const OVERDRAW_INCREMENT: egui::Rgba =
+        egui::Rgba::from_rgba_premultiplied(0.1, 0.1, 0.1, 0.1);
+}
+
+/// The primitive topology a mesh is drawn with. See
+/// [`Renderer::render_primitives_with_topology`](crate::Renderer::render_primitives_with_topology).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum MeshTopology {
+    /// Every three indices form a triangle. All meshes tessellated by `egui`
+    /// are triangle lists.
+    #[default]
+    TriangleList,
+    /// Every two indices form a line one pixel wide, for crisp thin lines in
+    /// custom meshes. Lines are not anti-aliased.
+    LineList,
+}
+
+impl MeshTopology {
+    pub(crate) fn to_d3d(self) -> D3D_PRIMITIVE_TOPOLOGY {
+        match self {
+            Self::TriangleList => D3D11_PRIMITIVE_TOPOLOGY_TRIANGLELIST,
+            Self::LineList => D3D11_PRIMITIVE_TOPOLOGY_LINELIST,
+        }
+    }
+
+    /// The number of indices forming a single primitive.
+    pub(crate) fn indices_per_primitive(self) -> usize {
+        match self {
+            Self::TriangleList => 3,
+            Self::LineList => 2,
+        }
+    }
+}
+
+/// A clockwise rotation applied to the output of `egui`. See
+/// [`RenderOptions::display_rotation`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum DisplayRotation {
+    /// No rotation.
+    #[default]
+    None,
+    /// Rotate by 90 degrees clockwise, so that the top-left corner of `egui`
+    /// ends up in the top-right corner of the render target.
+    Rotate90,
+    /// Rotate by 180 degrees.
+    Rotate180,
+    /// Rotate by 270 degrees clockwise, so that the top-left corner of `egui`
+    /// ends up in the bottom-left corner of the render target.
+    Rotate270,
+}
+
+impl DisplayRotation {
+    /// Transform a position in physical pixels in the screen as seen by
+    /// `egui` into a position in the render target of `target_size`.
+    pub(crate) fn transform(
+        self,
+        pos: egui::Pos2,
+        target_size: (f32, f32),
+    ) -> egui::Pos2 {
+        let (w, h) = target_size;
+        match self {
+            Self::None => pos,
+            Self::Rotate90 => egui::pos2(w - pos.y, pos.x),
+            Self::Rotate180 => egui::pos2(w - pos.x, h - pos.y),
+            Self::Rotate270 => egui::pos2(pos.y, h - pos.x),
+        }
+    }
+
+    /// Same as [`DisplayRotation::transform`] but for a rectangle.
+    pub(crate) fn transform_rect(
+        self,
+        rect: egui::Rect,
+        target_size: (f32, f32),
+    ) -> egui::Rect {
+        egui::Rect::from_two_pos(
+            self.transform(rect.min, target_size),
+            self.transform(rect.max, target_size),
+        )
+    }
+}
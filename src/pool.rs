@@ -0,0 +1,221 @@
+use crate::{IndexData, MeshData, Renderer, VertexData};
+
+use std::{
+    collections::{HashMap, VecDeque},
+    mem, ptr,
+};
+
+use windows::{
+    core::{Interface, Result},
+    Win32::{
+        Foundation::S_OK,
+        Graphics::{Direct3D11::*, Dxgi::Common::*},
+    },
+};
+
+/// A free list of vertex and index buffers of
+/// [`BufferStrategy::Pooled`](crate::BufferStrategy::Pooled), bucketed by
+/// power-of-two sizes and bind flags. Buffers used in a frame are retired
+/// along with an event query issued at the end of the frame, and only reused
+/// once the query signals that the GPU has finished drawing with them.
+#[derive(Default)]
+pub struct BufferPool {
+    /// Free buffers by capacity and bind flags.
+    free: HashMap<(usize, i32), Bucket>,
+    in_use: Vec<PooledBuffer>,
+    retired: VecDeque<(ID3D11Query, Vec<PooledBuffer>)>,
+    frame: u64,
+}
+
+#[derive(Default)]
+struct Bucket {
+    buffers: Vec<PooledBuffer>,
+    /// The last frame a buffer was taken from or returned to this bucket.
+    last_used: u64,
+}
+
+struct PooledBuffer {
+    buffer: ID3D11Buffer,
+    capacity: usize,
+    bind_flags: D3D11_BIND_FLAG,
+}
+
+impl BufferPool {
+    const MIN_CAPACITY: usize = 1024;
+
+    /// The number of frames after which the free buffers of a bucket no
+    /// buffer is taken from are released.
+    const MAX_IDLE_FRAMES: u64 = 120;
+
+    /// Get a buffer holding `data`, reusing a free buffer of the same bucket
+    /// if there is one.
+    fn acquire(
+        &mut self,
+        device: &ID3D11Device,
+        device_context: &ID3D11DeviceContext,
+        data: &[u8],
+        bind_flags: D3D11_BIND_FLAG,
+    ) -> Result<ID3D11Buffer> {
+        let capacity = data.len().next_power_of_two().max(Self::MIN_CAPACITY);
+        let free =
+            self.free
+                .get_mut(&(capacity, bind_flags.0))
+                .and_then(|bucket| {
+                    bucket.last_used = self.frame;
+                    bucket.buffers.pop()
+                });
+        let pooled = match free {
+            Some(pooled) => pooled,
+            None => {
+                let mut buffer = None;
+                unsafe {
+                    device.CreateBuffer(
+                        &D3D11_BUFFER_DESC {
+                            ByteWidth: capacity as _,
+                            Usage: D3D11_USAGE_DEFAULT,
+                            BindFlags: bind_flags.0 as _,
+                            ..D3D11_BUFFER_DESC::default()
+                        },
+                        None,
+                        Some(&mut buffer),
+                    )
+                }?;
+                PooledBuffer {
+                    buffer: buffer.unwrap(),
+                    capacity,
+                    bind_flags,
+                }
+            },
+        };
+        unsafe {
+            device_context.UpdateSubresource(
+                &pooled.buffer,
+                0,
+                Some(&D3D11_BOX {
+                    left: 0,
+                    top: 0,
+                    front: 0,
+                    right: data.len() as _,
+                    bottom: 1,
+                    back: 1,
+                }),
+                data.as_ptr() as _,
+                0,
+                0,
+            )
+        };
+        let buffer = pooled.buffer.clone();
+        self.in_use.push(pooled);
+        Ok(buffer)
+    }
+
+    /// Retire the buffers used in the frame just drawn, reclaim those retired
+    /// in previous frames the GPU has finished with, and release the buckets
+    /// left unused for [`BufferPool::MAX_IDLE_FRAMES`].
+    pub(crate) fn end_frame(
+        &mut self,
+        device: &ID3D11Device,
+        device_context: &ID3D11DeviceContext,
+    ) -> Result<()> {
+        // results can only be read back from the immediate context, which
+        // also covers queries recorded on deferred contexts once their
+        // command lists are executed.
+        let immediate_context = unsafe { device.GetImmediateContext() }?;
+        while let Some((query, _)) = self.retired.front() {
+            // see `OcclusionQueries::poll` for why the raw function is called.
+            let hr = unsafe {
+                (Interface::vtable(&immediate_context).GetData)(
+                    Interface::as_raw(&immediate_context),
+                    Interface::as_raw(query),
+                    ptr::null_mut(),
+                    0,
+                    D3D11_ASYNC_GETDATA_DONOTFLUSH.0 as _,
+                )
+            };
+            if hr != S_OK {
+                break;
+            }
+            let (_, buffers) = self.retired.pop_front().unwrap();
+            for pooled in buffers {
+                let key = (pooled.capacity, pooled.bind_flags.0);
+                let bucket = self.free.entry(key).or_insert_with(|| Bucket {
+                    buffers: Vec::new(),
+                    last_used: self.frame,
+                });
+                bucket.buffers.push(pooled);
+            }
+        }
+        let frame = self.frame;
+        self.free.retain(|_, bucket| {
+            frame - bucket.last_used <= Self::MAX_IDLE_FRAMES
+        });
+        self.frame += 1;
+
+        if self.in_use.is_empty() {
+            return Ok(());
+        }
+        let mut query = None;
+        unsafe {
+            device.CreateQuery(
+                &D3D11_QUERY_DESC {
+                    Query: D3D11_QUERY_EVENT,
+                    MiscFlags: 0,
+                },
+                Some(&mut query),
+            )
+        }?;
+        let query = query.unwrap();
+        unsafe { device_context.End(&query) };
+        self.retired.push_back((query, mem::take(&mut self.in_use)));
+        Ok(())
+    }
+}
+
+impl Renderer {
+    /// Draw `meshes` with vertex and index buffers from the buffer pool.
+    pub(crate) fn draw_meshes_pooled(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        meshes: &[MeshData],
+    ) -> Result<()> {
+        for mesh in meshes {
+            let vb = self.buffer_pool.acquire(
+                &self.device,
+                device_context,
+                as_bytes(&mesh.vtx),
+                D3D11_BIND_VERTEX_BUFFER,
+            )?;
+            let (idx, ib_format) = match &mesh.idx {
+                IndexData::U16(idx) => (as_bytes(idx), DXGI_FORMAT_R16_UINT),
+                IndexData::U32(idx) => (as_bytes(idx), DXGI_FORMAT_R32_UINT),
+            };
+            let ib = self.buffer_pool.acquire(
+                &self.device,
+                device_context,
+                idx,
+                D3D11_BIND_INDEX_BUFFER,
+            )?;
+            unsafe {
+                device_context.IASetVertexBuffers(
+                    0,
+                    1,
+                    Some(&Some(vb)),
+                    Some(&(mem::size_of::<VertexData>() as _)),
+                    Some(&0),
+                );
+                device_context.IASetIndexBuffer(&ib, ib_format, 0);
+            }
+            self.draw_mesh(device_context, mesh, 0, 0)?;
+        }
+        Ok(())
+    }
+}
+
+fn as_bytes<T>(data: &[T]) -> &[u8] {
+    unsafe {
+        std::slice::from_raw_parts(
+            data.as_ptr() as *const u8,
+            mem::size_of_val(data),
+        )
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{ColorSpace, Renderer, RendererOutput};
+
+use std::mem;
+
+use windows::{core::Result, Win32::Graphics::Direct3D11::*};
+
+impl Renderer {
+    /// Same as [`Renderer::render`], but clears `render_target` to
+    /// transparent black first and renders linear colors with premultiplied
+    /// alpha, e.g. for a UI rendered to a texture which is later minified in
+    /// a 3D scene.
+    ///
+    /// Unlike colors with straight alpha, premultiplied colors can be
+    /// filtered and averaged as is, so mipmaps of the result do not darken
+    /// the edges of translucent content into dark halos. This function does
+    /// not generate mipmaps itself, so *you* should call `GenerateMips` on a
+    /// shader resource view of the texture afterwards, which must have been
+    /// created with `D3D11_RESOURCE_MISC_GENERATE_MIPS`. Sample the result
+    /// with premultiplied alpha blending, e.g. by registering it with
+    /// [`Renderer::register_user_texture_premultiplied`].
+    ///
+    /// [`RenderOptions::color_space`](crate::RenderOptions::color_space) and
+    /// [`RenderOptions::color_write_mask`](crate::RenderOptions::color_write_mask)
+    /// are overridden with [`ColorSpace::Linear`] and
+    /// `D3D11_COLOR_WRITE_ENABLE_ALL` for this call, as gamma-encoded colors
+    /// or partially written channels would not be averaged correctly, and
+    /// [`RenderOptions::straight_alpha_output`](crate::RenderOptions::straight_alpha_output)
+    /// is ignored.
+    /// `render_target` should be a view of a float or `_SRGB` format.
+    pub fn render_premultiplied(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<()> {
+        unsafe {
+            device_context.ClearRenderTargetView(render_target, &[0.; 4])
+        };
+
+        // the color space is restored right after, so the cached targets and
+        // texture copies for it are kept.
+        let color_space =
+            mem::replace(&mut self.options.color_space, ColorSpace::Linear);
+        let color_write_mask = mem::replace(
+            &mut self.options.color_write_mask,
+            D3D11_COLOR_WRITE_ENABLE_ALL,
+        );
+        let straight_alpha_output =
+            mem::replace(&mut self.options.straight_alpha_output, false);
+        let result = self.render(
+            device_context,
+            render_target,
+            egui_ctx,
+            egui_output,
+            scale_factor,
+        );
+        self.options.straight_alpha_output = straight_alpha_output;
+        self.options.color_write_mask = color_write_mask;
+        self.options.color_space = color_space;
+        result
+    }
+}
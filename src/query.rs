@@ -0,0 +1,162 @@
+use crate::Renderer;
+
+use std::{
+    collections::VecDeque,
+    mem, ptr, thread,
+    time::{Duration, Instant},
+};
+
+use windows::{
+    core::{Error, Interface, Result},
+    Win32::{
+        Foundation::{ERROR_TIMEOUT, S_FALSE, S_OK},
+        Graphics::Direct3D11::*,
+    },
+};
+
+/// Occlusion queries issued around the draws of each frame. Results are read
+/// back without stalling, so a result is usually available a frame or two
+/// after the frame it was issued for.
+#[derive(Default)]
+pub struct OcclusionQueries {
+    free: Vec<ID3D11Query>,
+    pending: VecDeque<ID3D11Query>,
+    samples_passed: Option<u64>,
+}
+
+impl OcclusionQueries {
+    /// Read back the results of all finished queries without blocking.
+    fn poll(&mut self, device_context: &ID3D11DeviceContext) {
+        while let Some(query) = self.pending.front() {
+            let mut samples_passed = 0u64;
+            // `GetData` returns `S_FALSE` if the result is not yet available,
+            // which the wrapper from the `windows` crate treats as success,
+            // so the raw function is called instead.
+            let hr = unsafe {
+                (Interface::vtable(device_context).GetData)(
+                    Interface::as_raw(device_context),
+                    Interface::as_raw(query),
+                    &mut samples_passed as *mut u64 as _,
+                    mem::size_of::<u64>() as _,
+                    D3D11_ASYNC_GETDATA_DONOTFLUSH.0 as _,
+                )
+            };
+            if hr != S_OK {
+                break;
+            }
+            self.samples_passed = Some(samples_passed);
+            self.free.push(self.pending.pop_front().unwrap());
+        }
+    }
+}
+
+impl Renderer {
+    /// Call `f`, wrapping all draws it issues in an occlusion query if
+    /// [`RenderOptions::occlusion_query`](crate::RenderOptions::occlusion_query)
+    /// is enabled.
+    pub(crate) fn with_occlusion_query<R>(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        f: impl FnOnce(&mut Self) -> Result<R>,
+    ) -> Result<R> {
+        if !self.options.occlusion_query {
+            return f(self);
+        }
+
+        self.occlusion_queries.poll(device_context);
+        let query = match self.occlusion_queries.free.pop() {
+            Some(query) => query,
+            None => {
+                let mut query = None;
+                unsafe {
+                    self.device.CreateQuery(
+                        &D3D11_QUERY_DESC {
+                            Query: D3D11_QUERY_OCCLUSION,
+                            MiscFlags: 0,
+                        },
+                        Some(&mut query),
+                    )
+                }?;
+                query.unwrap()
+            },
+        };
+        unsafe { device_context.Begin(&query) };
+        let result = f(self);
+        unsafe { device_context.End(&query) };
+        self.occlusion_queries.pending.push_back(query);
+        result
+    }
+
+    /// Get the number of samples that passed the depth and stencil tests in
+    /// the most recent frame whose occlusion query result is available, or
+    /// `None` if no result is available yet. A value of `0` means nothing
+    /// visible was drawn in that frame, in which case presenting it again
+    /// can be skipped if the previous frame looked the same.
+    ///
+    /// This requires [`RenderOptions::occlusion_query`] to be enabled. As
+    /// results are read back without stalling the pipeline, they usually lag
+    /// one or two frames behind. Note that fully transparent pixels still
+    /// count as drawn, and that samples rather than pixels are counted, so
+    /// the value scales with the sample count of a multisampled render
+    /// target.
+    ///
+    /// [`RenderOptions::occlusion_query`]: crate::RenderOptions::occlusion_query
+    pub fn pixels_drawn(&self) -> Option<u64> {
+        self.occlusion_queries.samples_passed
+    }
+
+    /// Flush the provided device context and block until the GPU has
+    /// finished all work submitted to it so far, e.g. so that a readback
+    /// right after [`Renderer::render`] reflects the rendered frame.
+    ///
+    /// This waits for at most `timeout`, after which an error with the
+    /// `HRESULT` of `ERROR_TIMEOUT` is returned. If the device is lost while
+    /// waiting, an error with the reason of device removal is returned
+    /// instead. This stalls the CPU, so avoid calling it every frame.
+    pub fn flush_and_wait(
+        &self,
+        device_context: &ID3D11DeviceContext,
+        timeout: Duration,
+    ) -> Result<()> {
+        let mut query = None;
+        unsafe {
+            self.device.CreateQuery(
+                &D3D11_QUERY_DESC {
+                    Query: D3D11_QUERY_EVENT,
+                    MiscFlags: 0,
+                },
+                Some(&mut query),
+            )
+        }?;
+        let query = query.unwrap();
+        unsafe {
+            device_context.End(&query);
+            device_context.Flush();
+        }
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            // see `OcclusionQueries::poll` for why the raw function is called.
+            let hr = unsafe {
+                (Interface::vtable(device_context).GetData)(
+                    Interface::as_raw(device_context),
+                    Interface::as_raw(&query),
+                    ptr::null_mut(),
+                    0,
+                    D3D11_ASYNC_GETDATA_DONOTFLUSH.0 as _,
+                )
+            };
+            if hr != S_FALSE {
+                return hr.ok();
+            }
+            if Instant::now() >= deadline {
+                unsafe { self.device.GetDeviceRemovedReason() }?;
+                return Err(Error::new(
+                    ERROR_TIMEOUT.to_hresult(),
+                    "timed out waiting for the GPU to finish.",
+                ));
+            }
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+}
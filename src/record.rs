@@ -0,0 +1,86 @@
+use crate::{Annotation, Renderer, RendererOutput};
+
+use std::mem;
+
+use windows::{
+    core::{Error, Result},
+    Win32::{
+        Foundation::{BOOL, E_INVALIDARG},
+        Graphics::Direct3D11::*,
+    },
+};
+
+impl Renderer {
+    /// Same as [`Renderer::render`], but records the draws of `egui` on the
+    /// deferred context `device_context` and finishes them into a command
+    /// list, which *you* execute later with `ExecuteCommandList`, e.g. to
+    /// build the command lists of a frame on several threads.
+    ///
+    /// The textures in `egui_output` are updated on `texture_context` right
+    /// away, which should be the immediate context or a deferred context
+    /// whose command list *you* execute before the returned one, so that
+    /// the draws sample up-to-date textures. The returned command list must
+    /// in turn be executed before textures are updated for the next frame,
+    /// as a texture updated in place in the meantime would be sampled with
+    /// its new contents. The command list does not restore the state of
+    /// `device_context`, which is cleared after recording as usual.
+    ///
+    /// [`RenderOptions::occlusion_query`](crate::RenderOptions::occlusion_query)
+    /// is not supported, as its results can not be read back from a
+    /// deferred context, and is ignored with a warning here.
+    ///
+    /// An error with `E_INVALIDARG` is returned if `device_context` is not
+    /// a deferred context.
+    pub fn record(
+        &mut self,
+        texture_context: &ID3D11DeviceContext,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<ID3D11CommandList> {
+        if unsafe { device_context.GetType() } != D3D11_DEVICE_CONTEXT_DEFERRED
+        {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "draws must be recorded on a deferred context.",
+            ));
+        }
+        self.update_textures(texture_context, &egui_output.textures_delta)
+            .inspect_err(|_| self.notify_device_lost())?;
+
+        let occlusion_query =
+            mem::replace(&mut self.options.occlusion_query, false);
+        if occlusion_query {
+            warn!(concat!(
+                "occlusion queries are not supported on deferred contexts. ",
+                "they will be disabled while recording."
+            ));
+        }
+        let result = {
+            let _annotation = Annotation::begin(
+                device_context,
+                self.annotation_prefix.as_deref(),
+                "egui-directx11: record",
+            );
+            self.draw_shapes(
+                device_context,
+                render_target,
+                egui_ctx,
+                egui_output.shapes,
+                egui_output.pixels_per_point,
+                scale_factor,
+                true,
+            )
+        };
+        self.options.occlusion_query = occlusion_query;
+        result.inspect_err(|_| self.notify_device_lost())?;
+
+        let mut command_list = None;
+        unsafe {
+            device_context.FinishCommandList(BOOL(0), Some(&mut command_list))
+        }?;
+        Ok(command_list.unwrap())
+    }
+}
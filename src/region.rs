@@ -0,0 +1,66 @@
+use crate::{clipped_meshes, Annotation, Renderer, RendererOutput};
+
+use windows::{core::Result, Win32::Graphics::Direct3D11::*};
+
+impl Renderer {
+    /// Same as [`Renderer::render`], but only draws the part of `egui`
+    /// within `region`, a rectangle in points in the screen as seen by
+    /// `egui`, e.g. the area under a magnifier.
+    ///
+    /// Meshes whose clip rectangle or geometry does not intersect `region`
+    /// are culled entirely, without uploading their vertices, and the
+    /// remaining ones are clipped to `region`. Pixels outside `region` are
+    /// left untouched.
+    pub fn render_region(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        region: egui::Rect,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<()> {
+        let _annotation = Annotation::begin(
+            device_context,
+            self.annotation_prefix.as_deref(),
+            "egui-directx11: render region",
+        );
+        self.update_textures(device_context, &egui_output.textures_delta)
+            .and_then(|_| {
+                let mode = self.offscreen_mode();
+                let primitives = Self::tessellate_for(
+                    mode,
+                    egui_ctx,
+                    egui_output.shapes,
+                    egui_output.pixels_per_point,
+                );
+                let meshes = clipped_meshes(primitives, None)
+                    .into_iter()
+                    .filter_map(|mut mesh| {
+                        let clip_rect = mesh.clip_rect.intersect(region);
+                        let mut bounds = egui::Rect::NOTHING;
+                        for vertex in &mesh.vertices {
+                            bounds.extend_with(vertex.pos);
+                        }
+                        if !clip_rect.is_positive()
+                            || !bounds.intersects(region)
+                        {
+                            return None;
+                        }
+                        mesh.clip_rect = clip_rect;
+                        Some(mesh)
+                    })
+                    .collect();
+                self.draw_in_mode(
+                    device_context,
+                    render_target,
+                    meshes,
+                    scale_factor,
+                    egui_ctx.zoom_factor(),
+                    mode,
+                    true,
+                )
+            })
+            .inspect_err(|_| self.notify_device_lost())
+    }
+}
@@ -0,0 +1,216 @@
+use crate::{zeroed, Renderer, RendererOutput};
+
+use windows::{
+    core::{Error, Interface, Result},
+    Win32::{
+        Foundation::E_INVALIDARG,
+        Graphics::{Direct3D11::*, Dxgi::Common::*},
+    },
+};
+
+impl Renderer {
+    /// Same as [`Renderer::render`], but renders to a multisampled render
+    /// target and then resolves it into `resolve_target` with
+    /// `ResolveSubresource`, e.g. for MSAA render targets with a specific
+    /// sample quality level such as CSAA or EQAA.
+    ///
+    /// `resolve_format` is the format the samples are resolved as. The
+    /// multisampled texture and `resolve_target` may be created with a
+    /// typeless format (e.g. `DXGI_FORMAT_R8G8B8A8_TYPELESS`), which is in
+    /// fact required to resolve into a view format differing from the format
+    /// of either texture, such as resolving an `_SRGB` render target view
+    /// into a `_UNORM` texture. In this case `resolve_format` must be a typed
+    /// format of the same format family. If `resolve_format` is `None`, the
+    /// format of `render_target` is used.
+    ///
+    /// An error with `E_INVALIDARG` is returned if `render_target` is not a
+    /// view of a multisampled 2D texture, if `resolve_target` is
+    /// multisampled, if the sizes of both differ, or if the format resolved
+    /// as is typeless or does not match the format of either texture, which
+    /// would otherwise only be reported by the debug layer.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_and_resolve(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        resolve_target: &ID3D11Texture2D,
+        resolve_format: Option<DXGI_FORMAT>,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<()> {
+        let (msaa_target, format) =
+            Self::check_resolve(render_target, resolve_target, resolve_format)?;
+        self.render(
+            device_context,
+            render_target,
+            egui_ctx,
+            egui_output,
+            scale_factor,
+        )?;
+        unsafe {
+            device_context.ResolveSubresource(
+                resolve_target,
+                0,
+                &msaa_target,
+                0,
+                format,
+            )
+        };
+        Ok(())
+    }
+
+    /// Get the multisampled texture `render_target` views and the format to
+    /// resolve it as into `resolve_target`.
+    fn check_resolve(
+        render_target: &ID3D11RenderTargetView,
+        resolve_target: &ID3D11Texture2D,
+        resolve_format: Option<DXGI_FORMAT>,
+    ) -> Result<(ID3D11Texture2D, DXGI_FORMAT)> {
+        let mut rtv_desc = zeroed();
+        unsafe { render_target.GetDesc(&mut rtv_desc) };
+        if rtv_desc.ViewDimension != D3D11_RTV_DIMENSION_TEXTURE2DMS {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "the render target must be a view of a multisampled texture.",
+            ));
+        }
+        let msaa_target = unsafe { render_target.GetResource() }?
+            .cast::<ID3D11Texture2D>()?;
+        let mut msaa_desc = D3D11_TEXTURE2D_DESC::default();
+        let mut resolve_desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe {
+            msaa_target.GetDesc(&mut msaa_desc);
+            resolve_target.GetDesc(&mut resolve_desc);
+        }
+        if resolve_desc.SampleDesc.Count != 1 {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "the resolve target must not be multisampled.",
+            ));
+        }
+        if (msaa_desc.Width, msaa_desc.Height)
+            != (resolve_desc.Width, resolve_desc.Height)
+        {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "the render target and the resolve target differ in size.",
+            ));
+        }
+
+        let format = resolve_format.unwrap_or(rtv_desc.Format);
+        if is_typeless(format) {
+            return Err(Error::new(
+                E_INVALIDARG,
+                format!("can not resolve as the typeless format {format:?}."),
+            ));
+        }
+        // a typed texture can only be resolved as its own format, and a
+        // typeless one as any typed format of its family.
+        for texture_format in [msaa_desc.Format, resolve_desc.Format] {
+            if texture_format != format
+                && texture_format != format_family(format)
+            {
+                return Err(Error::new(
+                    E_INVALIDARG,
+                    format!(
+                        "can not resolve as {:?} with a texture of {:?}.",
+                        format, texture_format,
+                    ),
+                ));
+            }
+        }
+        Ok((msaa_target, format))
+    }
+}
+
+fn is_typeless(format: DXGI_FORMAT) -> bool {
+    matches!(
+        format,
+        DXGI_FORMAT_R8G8B8A8_TYPELESS
+            | DXGI_FORMAT_B8G8R8A8_TYPELESS
+            | DXGI_FORMAT_B8G8R8X8_TYPELESS
+            | DXGI_FORMAT_R10G10B10A2_TYPELESS
+            | DXGI_FORMAT_R16G16B16A16_TYPELESS
+    )
+}
+
+/// Get the typeless format of the family of `format` for the formats render
+/// targets are usually created with, or `format` itself otherwise.
+fn format_family(format: DXGI_FORMAT) -> DXGI_FORMAT {
+    match format {
+        DXGI_FORMAT_R8G8B8A8_UNORM | DXGI_FORMAT_R8G8B8A8_UNORM_SRGB => {
+            DXGI_FORMAT_R8G8B8A8_TYPELESS
+        },
+        DXGI_FORMAT_B8G8R8A8_UNORM | DXGI_FORMAT_B8G8R8A8_UNORM_SRGB => {
+            DXGI_FORMAT_B8G8R8A8_TYPELESS
+        },
+        DXGI_FORMAT_B8G8R8X8_UNORM | DXGI_FORMAT_B8G8R8X8_UNORM_SRGB => {
+            DXGI_FORMAT_B8G8R8X8_TYPELESS
+        },
+        DXGI_FORMAT_R10G10B10A2_UNORM => DXGI_FORMAT_R10G10B10A2_TYPELESS,
+        DXGI_FORMAT_R16G16B16A16_FLOAT | DXGI_FORMAT_R16G16B16A16_UNORM => {
+            DXGI_FORMAT_R16G16B16A16_TYPELESS
+        },
+        _ => format,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TYPED_FORMATS: [DXGI_FORMAT; 9] = [
+        DXGI_FORMAT_R8G8B8A8_UNORM,
+        DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+        DXGI_FORMAT_B8G8R8A8_UNORM,
+        DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
+        DXGI_FORMAT_B8G8R8X8_UNORM,
+        DXGI_FORMAT_B8G8R8X8_UNORM_SRGB,
+        DXGI_FORMAT_R10G10B10A2_UNORM,
+        DXGI_FORMAT_R16G16B16A16_FLOAT,
+        DXGI_FORMAT_R16G16B16A16_UNORM,
+    ];
+
+    #[test]
+    fn typed_formats_belong_to_a_typeless_family() {
+        for format in TYPED_FORMATS {
+            assert!(!is_typeless(format), "{format:?}");
+            assert!(is_typeless(format_family(format)), "{format:?}");
+        }
+    }
+
+    #[test]
+    fn srgb_formats_share_the_family_of_their_unorm_format() {
+        for (unorm, srgb) in [
+            (DXGI_FORMAT_R8G8B8A8_UNORM, DXGI_FORMAT_R8G8B8A8_UNORM_SRGB),
+            (DXGI_FORMAT_B8G8R8A8_UNORM, DXGI_FORMAT_B8G8R8A8_UNORM_SRGB),
+            (DXGI_FORMAT_B8G8R8X8_UNORM, DXGI_FORMAT_B8G8R8X8_UNORM_SRGB),
+        ] {
+            assert_eq!(format_family(unorm), format_family(srgb));
+        }
+    }
+
+    #[test]
+    fn families_do_not_mix_channel_orders() {
+        assert_ne!(
+            format_family(DXGI_FORMAT_R8G8B8A8_UNORM),
+            format_family(DXGI_FORMAT_B8G8R8A8_UNORM),
+        );
+        assert_ne!(
+            format_family(DXGI_FORMAT_B8G8R8A8_UNORM),
+            format_family(DXGI_FORMAT_B8G8R8X8_UNORM),
+        );
+    }
+
+    #[test]
+    fn other_formats_are_their_own_family() {
+        for format in [
+            DXGI_FORMAT_R32G32B32A32_FLOAT,
+            DXGI_FORMAT_R11G11B10_FLOAT,
+            DXGI_FORMAT_R8G8B8A8_TYPELESS,
+        ] {
+            assert_eq!(format_family(format), format);
+        }
+    }
+}
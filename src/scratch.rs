@@ -0,0 +1,87 @@
+use crate::{MeshData, Renderer, RendererOutput, VertexData};
+
+use std::{fmt, mem};
+
+use windows::{core::Result, Win32::Graphics::Direct3D11::*};
+
+/// Reusable memory for the vertex and index data a [`Renderer`] stages for
+/// each frame before uploading it to the GPU. See
+/// [`Renderer::render_with_scratch`].
+///
+/// Once warmed up by a few frames, the memory held is reused by later frames
+/// of similar complexity without allocating. Note that tessellation by
+/// `egui` itself still allocates.
+#[derive(Default)]
+pub struct RenderScratch {
+    meshes: Vec<MeshData>,
+    spare: Vec<MeshData>,
+}
+
+impl fmt::Debug for RenderScratch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RenderScratch")
+            .field("meshes", &(self.meshes.len() + self.spare.len()))
+            .finish_non_exhaustive()
+    }
+}
+
+impl RenderScratch {
+    /// Recycle the meshes of the previous frame for a new frame.
+    pub(crate) fn begin_frame(&mut self) {
+        self.spare.append(&mut self.meshes);
+    }
+
+    /// Take the vertex and index storage of a recycled mesh, if any.
+    pub(crate) fn take_storage(
+        &mut self,
+    ) -> (Vec<VertexData>, Option<Vec<u16>>) {
+        match self.spare.pop() {
+            Some(mesh) => {
+                let mut vtx = mesh.vtx;
+                vtx.clear();
+                (vtx, mesh.idx.into_u16())
+            },
+            None => (Vec::new(), None),
+        }
+    }
+
+    pub(crate) fn meshes(&self) -> &[MeshData] {
+        &self.meshes
+    }
+
+    pub(crate) fn meshes_mut(&mut self) -> &mut Vec<MeshData> {
+        &mut self.meshes
+    }
+
+    /// Recycle the meshes after the first `len` ones.
+    pub(crate) fn truncate(&mut self, len: usize) {
+        self.spare.extend(self.meshes.drain(len..));
+    }
+}
+
+impl Renderer {
+    /// Same as [`Renderer::render`], but stages vertex and index data in
+    /// `scratch` instead of the memory held by this [`Renderer`], so that the
+    /// caller owns and controls the reusable memory, e.g. to share it across
+    /// several renderers or to free it explicitly by dropping it.
+    pub fn render_with_scratch(
+        &mut self,
+        scratch: &mut RenderScratch,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<()> {
+        mem::swap(&mut self.scratch, scratch);
+        let result = self.render(
+            device_context,
+            render_target,
+            egui_ctx,
+            egui_output,
+            scale_factor,
+        );
+        mem::swap(&mut self.scratch, scratch);
+        result
+    }
+}
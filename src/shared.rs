@@ -0,0 +1,102 @@
+use crate::{Renderer, RendererOutput};
+
+use windows::{
+    core::{Error, Interface, Result, HRESULT},
+    Win32::{
+        Foundation::{WAIT_ABANDONED, WAIT_TIMEOUT},
+        Graphics::{
+            Direct3D11::*,
+            Dxgi::{IDXGIKeyedMutex, DXGI_ERROR_WAIT_TIMEOUT},
+        },
+    },
+};
+
+impl Renderer {
+    /// Render the output of `egui` into a texture shared with another device
+    /// or API, e.g. Media Foundation or Direct2D, synchronizing with its
+    /// keyed mutex. Available with the `shared-texture` feature.
+    ///
+    /// The texture must be opened on the device of this [`Renderer`] (e.g.
+    /// with `ID3D11Device::OpenSharedResource`) and created with
+    /// `D3D11_BIND_RENDER_TARGET` and either `D3D11_RESOURCE_MISC_SHARED` or
+    /// `D3D11_RESOURCE_MISC_SHARED_KEYEDMUTEX`. A render target view is
+    /// created with the format of the texture, which should therefore be an
+    /// `_SRGB` format for proper results as described in [`Renderer::render`].
+    ///
+    /// If the texture has a keyed mutex, i.e. it was created with
+    /// `D3D11_RESOURCE_MISC_SHARED_KEYEDMUTEX`, the mutex is acquired with
+    /// `acquire_key` before rendering, waiting for at most
+    /// `timeout_ms` milliseconds, and released with `release_key` afterwards,
+    /// even if rendering fails. A common protocol is to acquire with `0` and
+    /// release with `1` on one side, and vice versa on the other side. If the
+    /// mutex cannot be acquired in time, this function returns an error with
+    /// `DXGI_ERROR_WAIT_TIMEOUT` without rendering anything. Otherwise, the
+    /// texture is rendered into without any synchronization, and it is *your*
+    /// responsibility to flush the device context before the other side uses
+    /// it.
+    #[allow(clippy::too_many_arguments)]
+    pub fn render_to_shared_texture(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        texture: &ID3D11Texture2D,
+        acquire_key: u64,
+        release_key: u64,
+        timeout_ms: u32,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<()> {
+        let mut render_target = None;
+        unsafe {
+            self.device.CreateRenderTargetView(
+                texture,
+                None,
+                Some(&mut render_target),
+            )
+        }?;
+        let render_target = render_target.unwrap();
+
+        let Ok(keyed_mutex) = texture.cast::<IDXGIKeyedMutex>() else {
+            return self.render(
+                device_context,
+                &render_target,
+                egui_ctx,
+                egui_output,
+                scale_factor,
+            );
+        };
+        // `AcquireSync` returns `WAIT_TIMEOUT` and `WAIT_ABANDONED` as success
+        // codes, which the wrapper from the `windows` crate treats as success,
+        // so the raw function is called instead.
+        let hr = unsafe {
+            (Interface::vtable(&keyed_mutex).AcquireSync)(
+                Interface::as_raw(&keyed_mutex),
+                acquire_key,
+                timeout_ms,
+            )
+        };
+        if hr == HRESULT(WAIT_TIMEOUT.0 as _) {
+            return Err(Error::new(
+                DXGI_ERROR_WAIT_TIMEOUT,
+                "timed out acquiring the keyed mutex of the shared texture.",
+            ));
+        }
+        hr.ok()?;
+        if hr == HRESULT(WAIT_ABANDONED.0 as _) {
+            warn!(concat!(
+                "the keyed mutex of the shared texture was abandoned by its ",
+                "previous owner. its content may be inconsistent."
+            ));
+        }
+
+        let result = self.render(
+            device_context,
+            &render_target,
+            egui_ctx,
+            egui_output,
+            scale_factor,
+        );
+        unsafe { keyed_mutex.ReleaseSync(release_key) }?;
+        result
+    }
+}
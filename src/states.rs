@@ -0,0 +1,376 @@
+use crate::{BlendMode, DebugMode, DeviceVersion, Renderer};
+
+use egui::{TextureFilter, TextureOptions, TextureWrapMode};
+
+use std::mem;
+
+use windows::{
+    core::{Interface, Result},
+    Win32::{Foundation::BOOL, Graphics::Direct3D11::*},
+};
+
+impl Renderer {
+    /// Recreate the pipeline states whose descriptions depend on the options
+    /// if the options have changed since they were created.
+    pub(crate) fn update_states(&mut self) -> Result<()> {
+        let blend_desc = self.build_blend_desc();
+        if blend_desc != self.blend_desc {
+            let mut blend_state = None;
+            let mut straight_blend_state = None;
+            unsafe {
+                self.device
+                    .CreateBlendState(&blend_desc, Some(&mut blend_state))?;
+                self.device.CreateBlendState(
+                    &Self::straight_blend_desc(blend_desc),
+                    Some(&mut straight_blend_state),
+                )?;
+            }
+            self.blend_state = blend_state.unwrap();
+            self.straight_blend_state = straight_blend_state.unwrap();
+            self.blend_desc = blend_desc;
+        }
+        let rasterizer_desc = self.rasterizer_desc();
+        let conservative = self.options.conservative_rasterization;
+        if rasterizer_desc != self.rasterizer_desc
+            || conservative != self.rasterizer_conservative
+        {
+            let conservative_state = if conservative {
+                self.create_conservative_rasterizer_state(rasterizer_desc)?
+            } else {
+                None
+            };
+            self.rasterizer_state = match conservative_state {
+                Some(rasterizer_state) => rasterizer_state,
+                None => {
+                    let mut rasterizer_state = None;
+                    unsafe {
+                        self.device.CreateRasterizerState(
+                            &rasterizer_desc,
+                            Some(&mut rasterizer_state),
+                        )
+                    }?;
+                    rasterizer_state.unwrap()
+                },
+            };
+            self.rasterizer_desc = rasterizer_desc;
+            self.rasterizer_conservative = conservative;
+        }
+        self.update_texture_samplers()
+    }
+
+    /// Get the description of the blend state `egui` is blended with
+    /// according to the current options, e.g. to replicate or diff the
+    /// pipeline state. Meshes sampling user textures with straight alpha are
+    /// blended with a variant whose `SrcBlend` of render target 0 is
+    /// `D3D11_BLEND_SRC_ALPHA` instead.
+    pub fn blend_desc(&self) -> D3D11_BLEND_DESC {
+        self.build_blend_desc()
+    }
+
+    /// Get the description of the built-in sampler state, as configured with
+    /// [`Renderer::set_texture_filter`] and [`Renderer::set_mip_lod_bias`].
+    /// Textures managed by `egui` are sampled with variants of it according
+    /// to their [`egui::TextureOptions`].
+    pub fn sampler_desc(&self) -> D3D11_SAMPLER_DESC {
+        self.sampler_desc
+    }
+
+    /// Get the description of the rasterizer state according to the current
+    /// options. With [`RenderOptions::conservative_rasterization`](crate::RenderOptions::conservative_rasterization),
+    /// which this description can not express, it is created as a
+    /// `D3D11_RASTERIZER_DESC2` with the same fields if supported.
+    pub fn rasterizer_desc(&self) -> D3D11_RASTERIZER_DESC {
+        D3D11_RASTERIZER_DESC {
+            DepthClipEnable: BOOL(self.options.depth_clip as _),
+            ..Self::RASTERIZER_DESC
+        }
+    }
+
+    /// Create a variant of the rasterizer state of `desc` with conservative
+    /// rasterization, or `None` if the device does not support it.
+    fn create_conservative_rasterizer_state(
+        &self,
+        desc: D3D11_RASTERIZER_DESC,
+    ) -> Result<Option<ID3D11RasterizerState>> {
+        let mut options = D3D11_FEATURE_DATA_D3D11_OPTIONS2::default();
+        let supported = unsafe {
+            self.device.CheckFeatureSupport(
+                D3D11_FEATURE_D3D11_OPTIONS2,
+                &mut options as *mut _ as _,
+                mem::size_of_val(&options) as _,
+            )
+        }
+        .is_ok_and(|_| {
+            options.ConservativeRasterizationTier
+                != D3D11_CONSERVATIVE_RASTERIZATION_NOT_SUPPORTED
+        });
+        let device = (self.device_version >= DeviceVersion::Device3)
+            .then(|| self.device.cast::<ID3D11Device3>().ok())
+            .flatten();
+        let (true, Some(device)) = (supported, device) else {
+            warn!(concat!(
+                "conservative rasterization is not supported by the device. ",
+                "egui will be rasterized normally."
+            ));
+            return Ok(None);
+        };
+
+        let mut rasterizer_state = None;
+        unsafe {
+            device.CreateRasterizerState2(
+                &D3D11_RASTERIZER_DESC2 {
+                    FillMode: desc.FillMode,
+                    CullMode: desc.CullMode,
+                    FrontCounterClockwise: desc.FrontCounterClockwise,
+                    DepthBias: desc.DepthBias,
+                    DepthBiasClamp: desc.DepthBiasClamp,
+                    SlopeScaledDepthBias: desc.SlopeScaledDepthBias,
+                    DepthClipEnable: desc.DepthClipEnable,
+                    ScissorEnable: desc.ScissorEnable,
+                    MultisampleEnable: desc.MultisampleEnable,
+                    AntialiasedLineEnable: desc.AntialiasedLineEnable,
+                    ForcedSampleCount: 0,
+                    ConservativeRaster:
+                        D3D11_CONSERVATIVE_RASTERIZATION_MODE_ON,
+                },
+                Some(&mut rasterizer_state),
+            )
+        }?;
+        rasterizer_state.unwrap().cast().map(Some)
+    }
+
+    /// Create the sampler states for the options of the managed textures
+    /// which have none yet.
+    fn update_texture_samplers(&mut self) -> Result<()> {
+        for options in self.texture_pool.managed_texture_options() {
+            if self.texture_samplers.iter().any(|(o, _)| *o == options) {
+                continue;
+            }
+            let mut sampler_state = None;
+            unsafe {
+                self.device.CreateSamplerState(
+                    &Self::texture_sampler_desc(self.sampler_desc, options),
+                    Some(&mut sampler_state),
+                )
+            }?;
+            self.texture_samplers
+                .push((options, sampler_state.unwrap()));
+        }
+        Ok(())
+    }
+
+    /// Get the variant of `desc` sampling with the filter and wrap mode
+    /// `egui` requested in `options`.
+    fn texture_sampler_desc(
+        desc: D3D11_SAMPLER_DESC,
+        options: TextureOptions,
+    ) -> D3D11_SAMPLER_DESC {
+        let mut filter = 0;
+        if options.minification == TextureFilter::Linear {
+            filter |= D3D11_FILTER_MIN_LINEAR_MAG_MIP_POINT.0;
+        }
+        if options.magnification == TextureFilter::Linear {
+            filter |= D3D11_FILTER_MIN_POINT_MAG_LINEAR_MIP_POINT.0;
+        }
+        if options.mipmap_mode == Some(TextureFilter::Linear) {
+            filter |= D3D11_FILTER_MIN_MAG_POINT_MIP_LINEAR.0;
+        }
+        let address = match options.wrap_mode {
+            TextureWrapMode::ClampToEdge => D3D11_TEXTURE_ADDRESS_CLAMP,
+            TextureWrapMode::Repeat => D3D11_TEXTURE_ADDRESS_WRAP,
+            TextureWrapMode::MirroredRepeat => D3D11_TEXTURE_ADDRESS_MIRROR,
+        };
+        D3D11_SAMPLER_DESC {
+            Filter: D3D11_FILTER(filter),
+            AddressU: address,
+            AddressV: address,
+            ..desc
+        }
+    }
+
+    /// Set the filter used by the built-in sampler state to sample user
+    /// textures, e.g. `D3D11_FILTER_MIN_MAG_MIP_POINT` for a blocky look.
+    /// Defaults to `D3D11_FILTER_MIN_MAG_MIP_LINEAR`.
+    ///
+    /// Textures managed by `egui` are sampled with the filter and wrap mode
+    /// `egui` requests in their [`egui::TextureOptions`] instead, e.g.
+    /// [`egui::TextureOptions::NEAREST`] for images loaded as pixel art.
+    ///
+    /// The sampler state is recreated right away, and an error is returned if
+    /// this fails, in which case the previous sampler state is kept. This has
+    /// no effect while a custom sampler state set with
+    /// [`Renderer::set_sampler_state`] is in use.
+    pub fn set_texture_filter(&mut self, filter: D3D11_FILTER) -> Result<()> {
+        self.rebuild_sampler_state(D3D11_SAMPLER_DESC {
+            Filter: filter,
+            ..self.sampler_desc
+        })
+    }
+
+    /// Set the mip level of detail bias of the built-in sampler state, e.g. a
+    /// slightly negative bias like `-0.5` to sharpen minified textures.
+    /// Defaults to `0.0`.
+    ///
+    /// This only affects textures with mipmaps, which are currently user
+    /// textures only, as textures managed by `egui` have a single mip level.
+    /// The sampler state is recreated in the same way as with
+    /// [`Renderer::set_texture_filter`].
+    pub fn set_mip_lod_bias(&mut self, bias: f32) -> Result<()> {
+        self.rebuild_sampler_state(D3D11_SAMPLER_DESC {
+            MipLODBias: bias,
+            ..self.sampler_desc
+        })
+    }
+
+    fn rebuild_sampler_state(
+        &mut self,
+        sampler_desc: D3D11_SAMPLER_DESC,
+    ) -> Result<()> {
+        if sampler_desc == self.sampler_desc {
+            return Ok(());
+        }
+        let mut sampler_state = None;
+        unsafe {
+            self.device
+                .CreateSamplerState(&sampler_desc, Some(&mut sampler_state))
+        }?;
+        self.sampler_state = sampler_state.unwrap();
+        self.sampler_desc = sampler_desc;
+        // the sampler states of managed textures derive from the built-in
+        // one, and are recreated on demand.
+        self.texture_samplers.clear();
+        Ok(())
+    }
+
+    /// Get the variant of `desc` for user textures with straight alpha, whose
+    /// colors must be multiplied by alpha while blending. The vertex colors
+    /// of meshes sampling them are unpremultiplied, so that the alpha of the
+    /// vertex colors is applied only once.
+    pub(crate) fn straight_blend_desc(
+        mut desc: D3D11_BLEND_DESC,
+    ) -> D3D11_BLEND_DESC {
+        desc.RenderTarget[0].SrcBlend = D3D11_BLEND_SRC_ALPHA;
+        desc
+    }
+
+    fn build_blend_desc(&self) -> D3D11_BLEND_DESC {
+        let mut desc = D3D11_BLEND_DESC {
+            AlphaToCoverageEnable: BOOL(self.options.alpha_to_coverage as _),
+            ..Self::BLEND_DESC
+        };
+        desc.RenderTarget[0].RenderTargetWriteMask =
+            self.options.color_write_mask.0 as _;
+        if self.options.debug_mode == DebugMode::Overdraw {
+            desc.RenderTarget[0] = D3D11_RENDER_TARGET_BLEND_DESC {
+                SrcBlend: D3D11_BLEND_ONE,
+                DestBlend: D3D11_BLEND_ONE,
+                SrcBlendAlpha: D3D11_BLEND_ONE,
+                DestBlendAlpha: D3D11_BLEND_ONE,
+                ..desc.RenderTarget[0]
+            };
+        } else if self.options.blend_mode == BlendMode::Opaque {
+            desc.RenderTarget[0].BlendEnable = BOOL(0);
+        } else if self.dual_source_enabled() {
+            desc.RenderTarget[0] = D3D11_RENDER_TARGET_BLEND_DESC {
+                SrcBlend: D3D11_BLEND_ONE,
+                DestBlend: D3D11_BLEND_SRC1_COLOR,
+                SrcBlendAlpha: D3D11_BLEND_ONE,
+                DestBlendAlpha: D3D11_BLEND_INV_SRC1_ALPHA,
+                ..desc.RenderTarget[0]
+            };
+        }
+        desc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(
+        magnification: TextureFilter,
+        minification: TextureFilter,
+        mipmap_mode: Option<TextureFilter>,
+        wrap_mode: TextureWrapMode,
+    ) -> TextureOptions {
+        TextureOptions {
+            magnification,
+            minification,
+            wrap_mode,
+            mipmap_mode,
+        }
+    }
+
+    #[test]
+    fn sampler_desc_maps_filters() {
+        use TextureFilter::{Linear, Nearest};
+        let clamp = TextureWrapMode::ClampToEdge;
+        for (mag, min, mip, filter) in [
+            (Nearest, Nearest, None, D3D11_FILTER_MIN_MAG_MIP_POINT),
+            (
+                Linear,
+                Nearest,
+                None,
+                D3D11_FILTER_MIN_POINT_MAG_LINEAR_MIP_POINT,
+            ),
+            (Nearest, Linear, None, D3D11_FILTER_MIN_LINEAR_MAG_MIP_POINT),
+            (Linear, Linear, None, D3D11_FILTER_MIN_MAG_LINEAR_MIP_POINT),
+            (
+                Linear,
+                Linear,
+                Some(Nearest),
+                D3D11_FILTER_MIN_MAG_LINEAR_MIP_POINT,
+            ),
+            (
+                Linear,
+                Linear,
+                Some(Linear),
+                D3D11_FILTER_MIN_MAG_MIP_LINEAR,
+            ),
+            (
+                Nearest,
+                Nearest,
+                Some(Linear),
+                D3D11_FILTER_MIN_MAG_POINT_MIP_LINEAR,
+            ),
+        ] {
+            let desc = Renderer::texture_sampler_desc(
+                Renderer::SAMPLER_DESC,
+                options(mag, min, mip, clamp),
+            );
+            assert_eq!(desc.Filter, filter, "{mag:?} {min:?} {mip:?}");
+        }
+    }
+
+    #[test]
+    fn sampler_desc_maps_wrap_modes() {
+        use TextureFilter::Linear;
+        for (wrap_mode, address) in [
+            (TextureWrapMode::ClampToEdge, D3D11_TEXTURE_ADDRESS_CLAMP),
+            (TextureWrapMode::Repeat, D3D11_TEXTURE_ADDRESS_WRAP),
+            (
+                TextureWrapMode::MirroredRepeat,
+                D3D11_TEXTURE_ADDRESS_MIRROR,
+            ),
+        ] {
+            let desc = Renderer::texture_sampler_desc(
+                Renderer::SAMPLER_DESC,
+                options(Linear, Linear, None, wrap_mode),
+            );
+            assert_eq!((desc.AddressU, desc.AddressV), (address, address));
+        }
+    }
+
+    #[test]
+    fn sampler_desc_keeps_other_fields() {
+        let base = D3D11_SAMPLER_DESC {
+            MipLODBias: -0.5,
+            ..Renderer::SAMPLER_DESC
+        };
+        let desc =
+            Renderer::texture_sampler_desc(base, TextureOptions::NEAREST);
+        assert_eq!(desc.MipLODBias, -0.5);
+        assert_eq!(desc.AddressW, base.AddressW);
+        assert_eq!(desc.MaxLOD, base.MaxLOD);
+    }
+}
@@ -0,0 +1,31 @@
+use crate::Renderer;
+
+/// Statistics of the last frame rendered by a [`Renderer`]. See
+/// [`Renderer::stats`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RenderStats {
+    /// The number of meshes drawn.
+    pub meshes_drawn: usize,
+    /// The number of meshes skipped because of
+    /// [`RenderOptions::max_meshes`](crate::RenderOptions::max_meshes).
+    pub meshes_skipped: usize,
+    /// The number of fully transparent meshes skipped because of
+    /// [`RenderOptions::cull_transparent_meshes`](crate::RenderOptions::cull_transparent_meshes).
+    pub meshes_culled: usize,
+    /// The number of meshes skipped because their number of indices is not a
+    /// multiple of the number of indices per primitive, which are warned
+    /// about once per frame.
+    pub meshes_incomplete: usize,
+    /// Whether any texture managed by `egui` was created, updated or freed
+    /// before drawing, e.g. to decide whether a cache of the rendered frame
+    /// is invalidated. Requests of `egui` which are ignored, e.g. partial
+    /// updates of non-existing textures, do not count.
+    pub textures_changed: bool,
+}
+
+impl Renderer {
+    /// Get the statistics of the last frame rendered.
+    pub fn stats(&self) -> &RenderStats {
+        &self.stats
+    }
+}
@@ -0,0 +1,122 @@
+use crate::Renderer;
+
+use windows::{
+    core::Result,
+    Win32::{Foundation::BOOL, Graphics::Direct3D11::*},
+};
+
+/// A stencil buffer and the stencil test against it, used to clip `egui` to
+/// an arbitrary region. See [`Renderer::set_stencil_mask`].
+///
+/// The stencil buffer is prepared by *you* before rendering, and the depth
+/// stencil state is expected to be created with a description like:
+///
+/// ```ignore
+/// D3D11_DEPTH_STENCIL_DESC {
+///     DepthEnable: FALSE,
+///     StencilEnable: TRUE,
+///     StencilReadMask: 0xff,
+///     StencilWriteMask: 0,
+///     FrontFace: D3D11_DEPTH_STENCILOP_DESC {
+///         StencilFailOp: D3D11_STENCIL_OP_KEEP,
+///         StencilDepthFailOp: D3D11_STENCIL_OP_KEEP,
+///         StencilPassOp: D3D11_STENCIL_OP_KEEP,
+///         StencilFunc: D3D11_COMPARISON_EQUAL,
+///     },
+///     // the built-in rasterizer state does not cull, so both faces must be
+///     // tested the same way.
+///     BackFace: /* same as `FrontFace` */,
+///     ..
+/// }
+/// ```
+///
+/// With the description above, `egui` is only drawn where the stencil buffer
+/// equals [`StencilMask::stencil_ref`]. Depth testing should be disabled as
+/// `egui` is drawn without meaningful depth.
+#[derive(Clone, Debug)]
+pub struct StencilMask {
+    /// The depth stencil view holding the stencil buffer, which must have the
+    /// same size as the render target.
+    pub depth_stencil_view: ID3D11DepthStencilView,
+    /// The depth stencil state configured for stencil testing.
+    pub depth_stencil_state: ID3D11DepthStencilState,
+    /// The reference value the stencil buffer is tested against.
+    pub stencil_ref: u32,
+}
+
+impl Renderer {
+    /// Set a stencil mask to clip `egui` to, or `None` to draw without
+    /// stencil testing.
+    ///
+    /// While a stencil mask is set, [`Renderer::render`] binds its depth
+    /// stencil view along with the render target and sets its depth stencil
+    /// state. [`Renderer::render_into_current_target`] only sets the depth
+    /// stencil state and tests against the depth stencil view currently
+    /// bound instead. If an internal render target is used, e.g. with
+    /// [`RenderOptions::supersample`](crate::RenderOptions::supersample),
+    /// the stencil test is applied when the internal render target is
+    /// blitted to the render target.
+    pub fn set_stencil_mask(&mut self, stencil_mask: Option<StencilMask>) {
+        self.stencil_mask = stencil_mask;
+    }
+
+    /// Set the depth stencil state to draw `egui` with while a depth stencil
+    /// view is bound by *you*, or `None` to use the built-in one, which
+    /// disables depth testing, depth writes and stencil testing.
+    ///
+    /// [`Renderer::render_into_current_target`] keeps the depth stencil
+    /// view currently bound, if any, and sets this depth stencil state along
+    /// with it, so that `egui` is not rejected by a depth test configured
+    /// for your scene, e.g. against a depth buffer cleared to the far plane.
+    /// It is not set if no depth stencil view is bound, or if a stencil mask
+    /// is set with [`Renderer::set_stencil_mask`], whose own depth stencil
+    /// state is used instead.
+    pub fn set_depth_stencil_state(
+        &mut self,
+        depth_stencil_state: Option<ID3D11DepthStencilState>,
+    ) {
+        self.custom_depth_stencil_state = depth_stencil_state;
+    }
+
+    /// Set the depth stencil state for drawing with the depth stencil view
+    /// currently bound, if any.
+    pub(crate) fn bind_depth_stencil_state(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+    ) -> Result<()> {
+        let mut depth_stencil_view = None;
+        unsafe {
+            device_context
+                .OMGetRenderTargets(None, Some(&mut depth_stencil_view))
+        };
+        if depth_stencil_view.is_none() {
+            return Ok(());
+        }
+        if self.custom_depth_stencil_state.is_none()
+            && self.depth_stencil_state.is_none()
+        {
+            let mut depth_stencil_state = None;
+            unsafe {
+                self.device.CreateDepthStencilState(
+                    &D3D11_DEPTH_STENCIL_DESC {
+                        DepthEnable: BOOL(0),
+                        DepthWriteMask: D3D11_DEPTH_WRITE_MASK_ZERO,
+                        DepthFunc: D3D11_COMPARISON_ALWAYS,
+                        StencilEnable: BOOL(0),
+                        ..D3D11_DEPTH_STENCIL_DESC::default()
+                    },
+                    Some(&mut depth_stencil_state),
+                )
+            }?;
+            self.depth_stencil_state = depth_stencil_state;
+        }
+        let depth_stencil_state = self
+            .custom_depth_stencil_state
+            .as_ref()
+            .or(self.depth_stencil_state.as_ref());
+        unsafe {
+            device_context.OMSetDepthStencilState(depth_stencil_state, 0)
+        };
+        Ok(())
+    }
+}
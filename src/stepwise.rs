@@ -0,0 +1,144 @@
+use crate::{
+    background_mesh, clipped_meshes, RenderScratch, Renderer, RendererOutput,
+};
+
+use windows::{
+    core::{Error, Result},
+    Win32::Graphics::Direct3D11::*,
+};
+
+/// The draws of a frame submitted one at a time, returned by
+/// [`Renderer::render_stepwise`].
+struct RenderSteps<'a> {
+    renderer: &'a mut Renderer,
+    device_context: &'a ID3D11DeviceContext,
+    scratch: Option<RenderScratch>,
+    error: Option<Error>,
+    next: usize,
+}
+
+impl Iterator for RenderSteps<'_> {
+    type Item = Result<()>;
+
+    fn next(&mut self) -> Option<Result<()>> {
+        if let Some(error) = self.error.take() {
+            return Some(Err(error));
+        }
+        let mesh = self.scratch.as_ref()?.meshes().get(self.next)?;
+        self.next += 1;
+        let result = self
+            .renderer
+            .bind_mesh_buffers(self.device_context, mesh)
+            .and_then(|_| {
+                self.renderer.draw_mesh(self.device_context, mesh, 0, 0)
+            });
+        if result.is_err() {
+            // the remaining draws are skipped after a failed one.
+            self.next = usize::MAX;
+            self.renderer.notify_device_lost();
+        }
+        Some(result)
+    }
+}
+
+impl Drop for RenderSteps<'_> {
+    fn drop(&mut self) {
+        if let Some(scratch) = self.scratch.take() {
+            self.renderer.finish_primitives(scratch);
+        }
+    }
+}
+
+impl Renderer {
+    /// Same as [`Renderer::render`], but returns an iterator submitting the
+    /// draw of one mesh per call to [`Iterator::next`], so that *you* can
+    /// check a time budget between draws, e.g. in a soft real-time tool
+    /// which must not block its thread for too long at once.
+    ///
+    /// Textures are updated, the output of `egui` is tessellated and the
+    /// pipeline state is set up once, before this function returns. *You*
+    /// should thus not change the pipeline state of `device_context` until
+    /// the iterator is exhausted or dropped. If preparing the frame fails,
+    /// the iterator yields that error only. If a draw fails, its error is
+    /// yielded and the remaining draws are skipped. Dropping the iterator
+    /// early skips the draws not yet submitted.
+    ///
+    /// Each mesh is drawn with buffers of its own, as with
+    /// [`BufferStrategy::PerMesh`](crate::BufferStrategy::PerMesh), and
+    /// `egui` is drawn directly into `render_target`. Options requiring an
+    /// internal render target (see [`Renderer::render`]) and
+    /// [`RenderOptions::occlusion_query`](crate::RenderOptions::occlusion_query)
+    /// are not supported, and are ignored with a warning here. Paint
+    /// callbacks are dropped with a warning.
+    pub fn render_stepwise<'a>(
+        &'a mut self,
+        device_context: &'a ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> impl Iterator<Item = Result<()>> + 'a {
+        let (scratch, error) = match self.prepare_stepwise(
+            device_context,
+            render_target,
+            egui_ctx,
+            egui_output,
+            scale_factor,
+        ) {
+            Ok(scratch) => (Some(scratch), None),
+            Err(error) => {
+                self.notify_device_lost();
+                (None, Some(error))
+            },
+        };
+        RenderSteps {
+            renderer: self,
+            device_context,
+            scratch,
+            error,
+            next: 0,
+        }
+    }
+
+    fn prepare_stepwise(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        render_target: &ID3D11RenderTargetView,
+        egui_ctx: &egui::Context,
+        egui_output: RendererOutput,
+        scale_factor: f32,
+    ) -> Result<RenderScratch> {
+        self.update_textures(device_context, &egui_output.textures_delta)?;
+        if self.offscreen_mode().is_some() {
+            warn!(concat!(
+                "internal render targets are not supported when rendering ",
+                "stepwise. egui will be drawn directly into the render target."
+            ));
+        }
+        if self.options.occlusion_query {
+            warn!(concat!(
+                "occlusion queries are not supported when rendering ",
+                "stepwise. they will be disabled."
+            ));
+        }
+        let primitives = Self::tessellate_for(
+            None,
+            egui_ctx,
+            egui_output.shapes,
+            egui_output.pixels_per_point,
+        );
+        let mut meshes = clipped_meshes(primitives, None);
+        if self.options.fill_background {
+            meshes.insert(0, background_mesh(egui_ctx));
+        }
+        let viewport = self.get_viewport(render_target)?;
+        self.prepare_primitives(
+            device_context,
+            Some(render_target),
+            viewport,
+            meshes,
+            scale_factor,
+            egui_ctx.zoom_factor(),
+        )
+    }
+}
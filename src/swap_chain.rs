@@ -0,0 +1,355 @@
+use crate::ColorSpace;
+
+use windows::{
+    core::{Error, Interface, Result},
+    Win32::{
+        Foundation::{BOOL, E_INVALIDARG, HWND},
+        Graphics::{
+            Direct3D11::*,
+            Dxgi::{Common::*, *},
+        },
+    },
+};
+
+/// Options for creating a [`SwapChain`].
+#[derive(Clone, Copy, Debug)]
+pub struct SwapChainOptions {
+    /// The format of the back buffers. Defaults to
+    /// `DXGI_FORMAT_R8G8B8A8_UNORM`.
+    ///
+    /// Flip-model swap chains do not support `_SRGB` formats for their back
+    /// buffers. Use [`SwapChainOptions::render_target_format`] to render with
+    /// an `_SRGB` format instead.
+    pub format: DXGI_FORMAT,
+
+    /// The format of the render target view created for the back buffer.
+    /// Defaults to `DXGI_FORMAT_R8G8B8A8_UNORM_SRGB`, which is what
+    /// [`Renderer::render`](crate::Renderer::render) expects with
+    /// [`ColorSpace::Linear`].
+    ///
+    /// The format is checked against [`SwapChainOptions::color_space`] when
+    /// the render target view is created, and a warning is logged if they
+    /// do not match: linear colors only display correctly through an `_SRGB`
+    /// or float view, and look too dark with washed-out translucency through
+    /// a non-`_SRGB` 8-bit view, e.g. `DXGI_FORMAT_R8G8B8A8_UNORM`, while
+    /// gamma-encoded colors look too bright through an `_SRGB` view. See
+    /// [`SwapChainOptions::strict_format_check`].
+    pub render_target_format: DXGI_FORMAT,
+
+    /// The color space the renderer drawing into the swap chain blends in,
+    /// i.e. its [`RenderOptions::color_space`](crate::RenderOptions::color_space),
+    /// which [`SwapChainOptions::render_target_format`] is checked against.
+    /// Defaults to [`ColorSpace::Linear`].
+    pub color_space: ColorSpace,
+
+    /// The number of back buffers, which must be at least `2`. Defaults to
+    /// `2`.
+    pub buffer_count: u32,
+
+    /// Whether to allow tearing when presenting without vertical sync, for
+    /// low latency on variable refresh rate displays. Defaults to `false`.
+    ///
+    /// This is only enabled if the system supports it, which requires
+    /// Windows 10 or later with a driver supporting it. See
+    /// [`SwapChain::tearing_enabled`].
+    pub allow_tearing: bool,
+
+    /// Whether a mismatching [`SwapChainOptions::render_target_format`] is
+    /// an error with `E_INVALIDARG` instead of a warning. Defaults to
+    /// `false`.
+    pub strict_format_check: bool,
+
+    /// The sync interval [`SwapChain::present`] presents with vertical sync,
+    /// i.e. the number of vertical blanks to wait for. Defaults to `1`.
+    ///
+    /// Set this to `2` or `3` to present at 1/2 or 1/3 of the refresh rate,
+    /// e.g. to cap the frame rate of a tool window on a high refresh rate
+    /// display. Values greater than `4`, the maximum DXGI accepts, are
+    /// clamped to `4` with a warning, and `0` is treated as `1`, as
+    /// presenting without vertical sync is requested with
+    /// [`SwapChain::present`] instead.
+    ///
+    /// With the flip model this helper uses, frames presented with an
+    /// interval greater than `1` are queued rather than dropped, so the
+    /// frame rate is capped without tearing. Tearing is only ever allowed
+    /// when presenting without vertical sync, as DXGI rejects
+    /// `DXGI_PRESENT_ALLOW_TEARING` with a non-zero sync interval.
+    pub present_interval: u32,
+}
+
+impl Default for SwapChainOptions {
+    fn default() -> Self {
+        Self {
+            format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            render_target_format: DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            color_space: ColorSpace::Linear,
+            buffer_count: 2,
+            allow_tearing: false,
+            strict_format_check: false,
+            present_interval: 1,
+        }
+    }
+}
+
+/// An optional helper managing a flip-model swap chain
+/// (`DXGI_SWAP_EFFECT_FLIP_DISCARD`) for a window, and the render target view
+/// of its back buffer. Available with the `swap-chain` feature.
+///
+/// As this crate cares only about rendering, this helper is kept minimal and
+/// you are free to manage your swap chain yourself instead.
+pub struct SwapChain {
+    device: ID3D11Device,
+    swap_chain: IDXGISwapChain1,
+    render_target: Option<ID3D11RenderTargetView>,
+    options: SwapChainOptions,
+    tearing_enabled: bool,
+}
+
+impl SwapChain {
+    /// Create a swap chain of `size` for the window `hwnd` using the DXGI
+    /// factory the device was created from.
+    pub fn new(
+        device: &ID3D11Device,
+        hwnd: HWND,
+        size: (u32, u32),
+        mut options: SwapChainOptions,
+    ) -> Result<Self> {
+        options.present_interval =
+            Self::clamp_present_interval(options.present_interval);
+        let factory: IDXGIFactory2 =
+            unsafe { device.cast::<IDXGIDevice>()?.GetAdapter()?.GetParent() }?;
+        let tearing_enabled =
+            options.allow_tearing && Self::tearing_supported(&factory);
+
+        let swap_chain = unsafe {
+            factory.CreateSwapChainForHwnd(
+                device,
+                hwnd,
+                &DXGI_SWAP_CHAIN_DESC1 {
+                    Width: size.0,
+                    Height: size.1,
+                    Format: options.format,
+                    Stereo: BOOL(0),
+                    SampleDesc: DXGI_SAMPLE_DESC {
+                        Count: 1,
+                        Quality: 0,
+                    },
+                    BufferUsage: DXGI_USAGE_RENDER_TARGET_OUTPUT,
+                    BufferCount: options.buffer_count,
+                    Scaling: DXGI_SCALING_STRETCH,
+                    SwapEffect: DXGI_SWAP_EFFECT_FLIP_DISCARD,
+                    AlphaMode: DXGI_ALPHA_MODE_UNSPECIFIED,
+                    Flags: Self::flags(tearing_enabled).0 as _,
+                },
+                None,
+                None,
+            )
+        }?;
+        let mut this = Self {
+            device: device.clone(),
+            swap_chain,
+            render_target: None,
+            options,
+            tearing_enabled,
+        };
+        this.render_target = Some(this.create_render_target()?);
+        Ok(this)
+    }
+
+    /// Get the underlying swap chain.
+    pub fn swap_chain(&self) -> &IDXGISwapChain1 {
+        &self.swap_chain
+    }
+
+    /// Get the render target view of the current back buffer, or `None` if
+    /// it could not be recreated after a failed [`SwapChain::resize`].
+    pub fn render_target(&self) -> Option<&ID3D11RenderTargetView> {
+        self.render_target.as_ref()
+    }
+
+    /// Whether tearing was requested with
+    /// [`SwapChainOptions::allow_tearing`] and is supported by the system.
+    pub fn tearing_enabled(&self) -> bool {
+        self.tearing_enabled
+    }
+
+    /// Resize the back buffers to `size`, recreating the render target view.
+    pub fn resize(&mut self, size: (u32, u32)) -> Result<()> {
+        // all references to the back buffers must be released before
+        // resizing them.
+        self.render_target = None;
+        unsafe {
+            self.swap_chain.ResizeBuffers(
+                self.options.buffer_count,
+                size.0,
+                size.1,
+                self.options.format,
+                Self::flags(self.tearing_enabled),
+            )
+        }?;
+        self.render_target = Some(self.create_render_target()?);
+        Ok(())
+    }
+
+    /// Present the back buffer, waiting for vertical sync if `vsync` is
+    /// `true`, for as many vertical blanks as
+    /// [`SwapChainOptions::present_interval`] specifies. Otherwise the back
+    /// buffer is presented immediately, allowing tearing if
+    /// [`SwapChain::tearing_enabled`] is `true`.
+    ///
+    /// Tearing only takes effect in windowed mode. It is not allowed in
+    /// exclusive fullscreen mode, which this helper never enters.
+    pub fn present(&self, vsync: bool) -> Result<()> {
+        let (sync_interval, flags) = if vsync {
+            (self.options.present_interval, DXGI_PRESENT(0))
+        } else if self.tearing_enabled {
+            (0, DXGI_PRESENT_ALLOW_TEARING)
+        } else {
+            (0, DXGI_PRESENT(0))
+        };
+        unsafe { self.swap_chain.Present(sync_interval, flags) }.ok()
+    }
+
+    /// Clamp `interval` into the sync intervals DXGI accepts.
+    fn clamp_present_interval(interval: u32) -> u32 {
+        if interval > 4 {
+            warn!(
+                concat!(
+                    "the present interval {} exceeds the maximum of 4. ",
+                    "it will be clamped to 4."
+                ),
+                interval
+            );
+        }
+        interval.clamp(1, 4)
+    }
+
+    fn create_render_target(&self) -> Result<ID3D11RenderTargetView> {
+        self.check_render_target_format()?;
+        let back_buffer: ID3D11Texture2D =
+            unsafe { self.swap_chain.GetBuffer(0) }?;
+        let mut render_target = None;
+        unsafe {
+            self.device.CreateRenderTargetView(
+                &back_buffer,
+                Some(&D3D11_RENDER_TARGET_VIEW_DESC {
+                    Format: self.options.render_target_format,
+                    ViewDimension: D3D11_RTV_DIMENSION_TEXTURE2D,
+                    ..Default::default()
+                }),
+                Some(&mut render_target),
+            )
+        }?;
+        Ok(render_target.unwrap())
+    }
+
+    fn check_render_target_format(&self) -> Result<()> {
+        let format = self.options.render_target_format;
+        let color_space = self.options.color_space;
+        if format_matches(format, color_space) {
+            return Ok(());
+        }
+        if self.options.strict_format_check {
+            return Err(Error::new(
+                E_INVALIDARG,
+                match color_space {
+                    ColorSpace::Linear => concat!(
+                        "the render target format must be an _SRGB or float ",
+                        "format for the linear color space."
+                    ),
+                    ColorSpace::Gamma => concat!(
+                        "the render target format must not be an _SRGB ",
+                        "format for the gamma color space."
+                    ),
+                },
+            ));
+        }
+        match color_space {
+            ColorSpace::Linear => warn!(
+                concat!(
+                    "the render target format {:?} is not an _SRGB format, ",
+                    "while egui is blended in the linear color space. egui ",
+                    "will look too dark. use the _SRGB variant of the format ",
+                    "instead."
+                ),
+                format
+            ),
+            ColorSpace::Gamma => warn!(
+                concat!(
+                    "the render target format {:?} is an _SRGB format, while ",
+                    "egui is blended in the gamma color space. egui will ",
+                    "look too bright. use the non-_SRGB variant of the ",
+                    "format instead."
+                ),
+                format
+            ),
+        }
+        Ok(())
+    }
+
+    fn flags(tearing_enabled: bool) -> DXGI_SWAP_CHAIN_FLAG {
+        if tearing_enabled {
+            DXGI_SWAP_CHAIN_FLAG_ALLOW_TEARING
+        } else {
+            DXGI_SWAP_CHAIN_FLAG(0)
+        }
+    }
+
+    fn tearing_supported(factory: &IDXGIFactory2) -> bool {
+        let Ok(factory) = factory.cast::<IDXGIFactory5>() else {
+            return false;
+        };
+        let mut supported = BOOL(0);
+        unsafe {
+            factory.CheckFeatureSupport(
+                DXGI_FEATURE_PRESENT_ALLOW_TEARING,
+                &mut supported as *mut BOOL as _,
+                std::mem::size_of::<BOOL>() as _,
+            )
+        }
+        .is_ok_and(|_| supported.as_bool())
+    }
+}
+
+/// Whether a render target view of `format` displays colors blended in
+/// `color_space` correctly. Only the 8-bit formats of swap chain back buffers
+/// are checked, and other formats are assumed to match.
+fn format_matches(format: DXGI_FORMAT, color_space: ColorSpace) -> bool {
+    let mismatching: &[_] = match color_space {
+        ColorSpace::Linear => &[
+            DXGI_FORMAT_R8G8B8A8_UNORM,
+            DXGI_FORMAT_B8G8R8A8_UNORM,
+            DXGI_FORMAT_B8G8R8X8_UNORM,
+        ],
+        ColorSpace::Gamma => &[
+            DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            DXGI_FORMAT_B8G8R8A8_UNORM_SRGB,
+            DXGI_FORMAT_B8G8R8X8_UNORM_SRGB,
+        ],
+    };
+    !mismatching.contains(&format)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linear_requires_srgb_or_float() {
+        let linear = ColorSpace::Linear;
+        assert!(format_matches(DXGI_FORMAT_R8G8B8A8_UNORM_SRGB, linear));
+        assert!(format_matches(DXGI_FORMAT_B8G8R8A8_UNORM_SRGB, linear));
+        assert!(format_matches(DXGI_FORMAT_R16G16B16A16_FLOAT, linear));
+        assert!(!format_matches(DXGI_FORMAT_R8G8B8A8_UNORM, linear));
+        assert!(!format_matches(DXGI_FORMAT_B8G8R8A8_UNORM, linear));
+    }
+
+    #[test]
+    fn gamma_rejects_srgb() {
+        let gamma = ColorSpace::Gamma;
+        assert!(format_matches(DXGI_FORMAT_R8G8B8A8_UNORM, gamma));
+        assert!(format_matches(DXGI_FORMAT_B8G8R8A8_UNORM, gamma));
+        assert!(!format_matches(DXGI_FORMAT_R8G8B8A8_UNORM_SRGB, gamma));
+        assert!(!format_matches(DXGI_FORMAT_B8G8R8X8_UNORM_SRGB, gamma));
+    }
+}
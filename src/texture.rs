@@ -8,156 +8,1074 @@
 //
 // Nekomaru, March 2024
 
-use std::{collections::HashMap, mem, slice};
+use crate::TextureUploadStrategy;
 
-use egui::{Color32, ImageData, TextureId, TexturesDelta};
+use std::{borrow::Cow, collections::BTreeMap, fmt, iter, mem};
+
+use egui::{
+    Color32, ColorImage, ImageData, TextureId, TextureOptions, TexturesDelta,
+};
 
 use windows::{
-    core::Result,
-    Win32::Graphics::{Direct3D11::*, Dxgi::Common::*},
+    core::{Error, Interface, Result},
+    Win32::Graphics::{
+        Direct3D::WKPDID_D3DDebugObjectName, Direct3D11::*, Dxgi::Common::*,
+    },
 };
 
-struct Texture {
+enum Texture {
+    Managed(ManagedTexture),
+    /// A texture standing in for a managed texture until `egui` uploads it,
+    /// which can not be updated partially.
+    Preloaded(ID3D11ShaderResourceView),
+    User(UserTexture),
+}
+
+struct ManagedTexture {
     tex: ID3D11Texture2D,
     srv: ID3D11ShaderResourceView,
-    pixels: Vec<Color32>,
+    /// A copy of the pixels kept on the CPU for partial updates of a dynamic
+    /// texture, which rewrite it as a whole. It is only kept for the font
+    /// atlas uploaded with [`TextureUploadStrategy::Dynamic`], as other
+    /// textures are updated in place, see [`TexturePool::create_texture`].
+    pixels: Option<Vec<Color32>>,
+    /// The staging texture `tex` is copied from, which is only kept for
+    /// textures uploaded with [`TextureUploadStrategy::Staging`].
+    staging: Option<ID3D11Texture2D>,
     width: usize,
+    height: usize,
+    /// The options `egui` requested when uploading the whole texture, which
+    /// are kept for partial updates.
+    options: TextureOptions,
+    /// A copy of the texture sampled without decoding sRGB, see
+    /// [`TexturePool::enable_gamma_views`].
+    gamma: Option<(ID3D11Texture2D, ID3D11ShaderResourceView)>,
+    /// The last frame counted by [`TexturePool::begin_frame`] the texture was
+    /// sampled in, or is expected to be sampled in if it was just uploaded.
+    last_used: u64,
+}
+
+/// A texture managed by `egui` created outside of the pool, to be inserted
+/// into it with [`TexturePool::insert_pending`].
+pub(crate) struct PendingTexture(ManagedTexture);
+
+impl ManagedTexture {
+    fn bytes(&self) -> usize {
+        let copies =
+            1 + self.gamma.is_some() as usize + self.staging.is_some() as usize;
+        self.width * self.height * mem::size_of::<Color32>() * copies
+    }
+}
+
+struct UserTexture {
+    srv: ID3D11ShaderResourceView,
+    label: Option<String>,
+    premultiplied: bool,
+}
+
+impl Texture {
+    fn srv(&self) -> &ID3D11ShaderResourceView {
+        match self {
+            Self::Managed(t) => &t.srv,
+            Self::Preloaded(srv) => srv,
+            Self::User(t) => &t.srv,
+        }
+    }
+
+    fn is_managed(&self) -> bool {
+        matches!(self, Self::Managed(_) | Self::Preloaded(_))
+    }
+
+    fn is_user(&self) -> bool {
+        matches!(self, Self::User(_))
+    }
 }
 
+/// Callback invoked with the id, width and height of a managed texture right
+/// after it is created.
+pub type TextureCreatedCallback =
+    Box<dyn FnMut(TextureId, usize, usize) + Send + Sync>;
+
+/// Callback invoked with the id of a managed texture right after it is freed.
+pub type TextureFreedCallback = Box<dyn FnMut(TextureId) + Send + Sync>;
+
+/// Callback invoked with the id of a managed texture and the error when
+/// creating or updating it fails, deciding whether rendering is aborted.
+/// See [`Renderer::set_on_texture_upload_failed`](crate::Renderer::set_on_texture_upload_failed).
+pub type TextureUploadFailedCallback =
+    Box<dyn FnMut(TextureId, &Error) -> TextureUploadPolicy + Send + Sync>;
+
+/// What to do when uploading a texture managed by `egui` fails.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TextureUploadPolicy {
+    /// Abort rendering and return the error.
+    #[default]
+    Abort,
+    /// Skip the texture and continue rendering. A texture replaced as a
+    /// whole keeps its old contents, if any, and a partial update is
+    /// dropped.
+    Skip,
+}
+
+/// The kind of a texture in a [`TexturePool`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TextureKind {
+    /// A texture created and updated by `egui`, e.g. the font atlas.
+    Managed,
+    /// A texture registered by the user with
+    /// [`Renderer::register_user_texture`](crate::Renderer::register_user_texture).
+    User,
+}
+
+/// A breakdown of the textures in a [`TexturePool`]. See
+/// [`TexturePool::memory_report`].
+#[derive(Clone, Debug, Default)]
+pub struct MemoryReport {
+    /// The id, width, height and size in bytes of each managed texture.
+    pub managed: Vec<(TextureId, u32, u32, usize)>,
+    /// The number of user textures, whose memory is owned by the user and
+    /// not accounted for here.
+    pub user_count: usize,
+    /// The size in bytes of the pixels kept on the CPU for partial updates,
+    /// which is only the font atlas uploaded with
+    /// [`TextureUploadStrategy::Dynamic`](crate::TextureUploadStrategy::Dynamic).
+    pub cpu_bytes: usize,
+}
+
+impl MemoryReport {
+    /// Get the total size in bytes of all managed textures.
+    pub fn managed_bytes(&self) -> usize {
+        self.managed.iter().map(|&(_, _, _, bytes)| bytes).sum()
+    }
+}
+
+/// The pool of all textures that can be sampled by a
+/// [`Renderer`](crate::Renderer), including both textures managed by `egui`
+/// and user textures. Get one with
+/// [`Renderer::texture_pool`](crate::Renderer::texture_pool).
+///
+/// There is no way to reserve capacity for many textures up front, as the
+/// pool is ordered by id in a B-tree, which allocates its nodes as it grows
+/// and never rehashes. Registering hundreds of user textures at startup thus
+/// costs the same as registering them over time.
 pub struct TexturePool {
     device: ID3D11Device,
-    pool: HashMap<TextureId, Texture>,
+    // ordered by id, so that iterating the pool (e.g. when freeing all
+    // managed textures) is deterministic across runs.
+    pool: BTreeMap<TextureId, Texture>,
+    on_created: Option<TextureCreatedCallback>,
+    on_freed: Option<TextureFreedCallback>,
+    on_upload_failed: Option<TextureUploadFailedCallback>,
+    next_user_id: u64,
+    gamma_views: bool,
+    frame: u64,
+}
+
+impl fmt::Debug for TexturePool {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let managed = self.pool.values().filter(|t| t.is_managed()).count();
+        f.debug_struct("TexturePool")
+            .field("managed", &managed)
+            .field("user", &(self.pool.len() - managed))
+            .finish_non_exhaustive()
+    }
 }
 
 impl TexturePool {
-    pub fn new(device: &ID3D11Device) -> Self {
+    pub(crate) fn new(device: &ID3D11Device) -> Self {
         Self {
             device: device.clone(),
-            pool: HashMap::new(),
+            pool: BTreeMap::new(),
+            on_created: None,
+            on_freed: None,
+            on_upload_failed: None,
+            next_user_id: 0,
+            gamma_views: false,
+            frame: 0,
+        }
+    }
+
+    pub(crate) fn set_on_created(
+        &mut self,
+        callback: Option<TextureCreatedCallback>,
+    ) {
+        self.on_created = callback;
+    }
+
+    pub(crate) fn set_on_freed(
+        &mut self,
+        callback: Option<TextureFreedCallback>,
+    ) {
+        self.on_freed = callback;
+    }
+
+    pub(crate) fn set_on_upload_failed(
+        &mut self,
+        callback: Option<TextureUploadFailedCallback>,
+    ) {
+        self.on_upload_failed = callback;
+    }
+
+    /// Decide whether the failure `err` to upload `tid` aborts rendering,
+    /// which it always does if the device has been removed.
+    fn handle_upload_failure(
+        &mut self,
+        tid: TextureId,
+        err: Error,
+    ) -> Result<()> {
+        let device_removed =
+            unsafe { self.device.GetDeviceRemovedReason() }.is_err();
+        let policy = match &mut self.on_upload_failed {
+            Some(on_upload_failed) if !device_removed => {
+                on_upload_failed(tid, &err)
+            },
+            _ => TextureUploadPolicy::Abort,
+        };
+        match policy {
+            TextureUploadPolicy::Abort => Err(err),
+            TextureUploadPolicy::Skip => {
+                warn!("failed to upload texture {tid:?}: {err}. it will be skipped.");
+                Ok(())
+            },
         }
     }
 
-    pub fn get_srv(&self, tid: TextureId) -> Option<ID3D11ShaderResourceView> {
-        self.pool.get(&tid).map(|t| t.srv.clone())
+    /// Get the number of textures in the pool.
+    pub fn len(&self) -> usize {
+        self.pool.len()
     }
 
-    pub fn update(
+    /// Whether the pool contains no textures.
+    pub fn is_empty(&self) -> bool {
+        self.pool.is_empty()
+    }
+
+    /// Whether `tid` refers to a texture in the pool.
+    pub fn contains(&self, tid: TextureId) -> bool {
+        self.pool.contains_key(&tid)
+    }
+
+    /// Get the kind of the texture `tid` refers to, or `None` if there is no
+    /// such texture in the pool.
+    pub fn kind(&self, tid: TextureId) -> Option<TextureKind> {
+        self.pool.get(&tid).map(|t| match t {
+            Texture::Managed(_) | Texture::Preloaded(_) => TextureKind::Managed,
+            Texture::User(_) => TextureKind::User,
+        })
+    }
+
+    /// Get a breakdown of the textures in the pool, e.g. for an in-UI memory
+    /// inspector. Managed textures are sorted by id.
+    ///
+    /// The sizes reported are those of the pixel data uploaded to the GPU,
+    /// 4 bytes per pixel, doubled for textures which are also kept in a
+    /// non-sRGB format for [`Renderer::render_to_srgb_target`](crate::Renderer::render_to_srgb_target),
+    /// and counting the staging texture kept for each texture uploaded with
+    /// [`TextureUploadStrategy::Staging`](crate::TextureUploadStrategy::Staging).
+    /// The pixels kept on the CPU for partial updates are reported separately
+    /// in [`MemoryReport::cpu_bytes`].
+    pub fn memory_report(&self) -> MemoryReport {
+        let mut report = MemoryReport::default();
+        for (&tid, t) in &self.pool {
+            match t {
+                Texture::Managed(t) => {
+                    report.managed.push((
+                        tid,
+                        t.width as _,
+                        t.height as _,
+                        t.bytes(),
+                    ));
+                    report.cpu_bytes += t
+                        .pixels
+                        .as_ref()
+                        .map_or(0, |p| p.len() * mem::size_of::<Color32>());
+                },
+                Texture::Preloaded(srv) => {
+                    let (width, height) = srv_size(srv);
+                    report.managed.push((
+                        tid,
+                        width,
+                        height,
+                        (width * height) as usize * mem::size_of::<Color32>(),
+                    ))
+                },
+                Texture::User(_) => report.user_count += 1,
+            }
+        }
+        report
+    }
+
+    pub(crate) fn get_srv(
+        &self,
+        tid: TextureId,
+    ) -> Option<ID3D11ShaderResourceView> {
+        self.pool.get(&tid).map(|t| t.srv().clone())
+    }
+
+    /// Get the options `egui` requested for sampling the managed texture
+    /// `tid`, or `None` for user textures. Preloaded textures are sampled
+    /// with the default options.
+    pub(crate) fn texture_options(
+        &self,
+        tid: TextureId,
+    ) -> Option<TextureOptions> {
+        match self.pool.get(&tid)? {
+            Texture::Managed(t) => Some(t.options),
+            Texture::Preloaded(_) => Some(TextureOptions::default()),
+            Texture::User(_) => None,
+        }
+    }
+
+    /// Get the options of all managed textures, in order of their ids.
+    pub(crate) fn managed_texture_options(
+        &self,
+    ) -> impl Iterator<Item = TextureOptions> + '_ {
+        self.pool
+            .keys()
+            .filter_map(|&tid| self.texture_options(tid))
+    }
+
+    /// Same as [`TexturePool::get_srv`], but returns a view sampling managed
+    /// textures without decoding sRGB if gamma views are enabled.
+    pub(crate) fn get_gamma_srv(
+        &self,
+        tid: TextureId,
+    ) -> Option<ID3D11ShaderResourceView> {
+        match self.pool.get(&tid) {
+            Some(Texture::Managed(ManagedTexture {
+                gamma: Some((_, srv)),
+                ..
+            })) => Some(srv.clone()),
+            _ => self.get_srv(tid),
+        }
+    }
+
+    /// Keep a copy of each managed texture in a non-sRGB format from now on,
+    /// so that they can be sampled with their gamma-encoded colors as is.
+    pub(crate) fn enable_gamma_views(
         &mut self,
         ctx: &ID3D11DeviceContext,
-        delta: TexturesDelta,
     ) -> Result<()> {
-        for (tid, delta) in delta.set {
+        if self.gamma_views {
+            return Ok(());
+        }
+        for texture in self.pool.values_mut() {
+            if let Texture::Managed(tex) = texture {
+                tex.gamma = Some(match &tex.pixels {
+                    Some(pixels) if tex.staging.is_none() => {
+                        Self::create_dynamic_texture(
+                            &self.device,
+                            pixels,
+                            tex.width,
+                            DXGI_FORMAT_R8G8B8A8_UNORM,
+                        )?
+                    },
+                    _ => Self::copy_texture(
+                        &self.device,
+                        ctx,
+                        &tex.tex,
+                        DXGI_FORMAT_R8G8B8A8_UNORM,
+                    )?,
+                });
+            }
+        }
+        self.gamma_views = true;
+        Ok(())
+    }
+
+    /// Drop the copies kept by [`TexturePool::enable_gamma_views`], and stop
+    /// keeping them for textures created from now on.
+    pub(crate) fn disable_gamma_views(&mut self) {
+        if !self.gamma_views {
+            return;
+        }
+        for texture in self.pool.values_mut() {
+            if let Texture::Managed(tex) = texture {
+                tex.gamma = None;
+            }
+        }
+        self.gamma_views = false;
+    }
+
+    pub(crate) fn register_user_texture(
+        &mut self,
+        srv: ID3D11ShaderResourceView,
+        label: Option<&str>,
+        premultiplied: bool,
+    ) -> TextureId {
+        if let Some(label) = label {
+            // the debug name is only a diagnostic aid, so failing to set it
+            // should not fail the registration.
+            let _ = unsafe {
+                srv.SetPrivateData(
+                    &WKPDID_D3DDebugObjectName,
+                    label.len() as _,
+                    Some(label.as_ptr() as _),
+                )
+            };
+        }
+        let tid = TextureId::User(self.next_user_id);
+        self.next_user_id += 1;
+        self.pool.insert(
+            tid,
+            Texture::User(UserTexture {
+                srv,
+                label: label.map(str::to_owned),
+                premultiplied,
+            }),
+        );
+        tid
+    }
+
+    /// Make the user texture `tid` sample `srv` instead, keeping its other
+    /// properties. Does nothing if `tid` is not a user texture.
+    pub(crate) fn replace_user_texture(
+        &mut self,
+        tid: TextureId,
+        srv: ID3D11ShaderResourceView,
+    ) {
+        if let Some(Texture::User(t)) = self.pool.get_mut(&tid) {
+            t.srv = srv;
+        }
+    }
+
+    pub(crate) fn is_straight_alpha(&self, tid: TextureId) -> bool {
+        matches!(
+            self.pool.get(&tid),
+            Some(Texture::User(UserTexture {
+                premultiplied: false,
+                ..
+            }))
+        )
+    }
+
+    pub(crate) fn preload(
+        &mut self,
+        tid: TextureId,
+        srv: ID3D11ShaderResourceView,
+    ) {
+        self.pool.insert(tid, Texture::Preloaded(srv));
+    }
+
+    pub(crate) fn unregister_user_texture(
+        &mut self,
+        tid: TextureId,
+    ) -> Option<ID3D11ShaderResourceView> {
+        if !self.pool.get(&tid).is_some_and(Texture::is_user) {
+            return None;
+        }
+        match self.pool.remove(&tid) {
+            Some(Texture::User(t)) => Some(t.srv),
+            _ => unreachable!(),
+        }
+    }
+
+    pub(crate) fn user_textures(
+        &self,
+    ) -> impl Iterator<Item = (TextureId, &ID3D11ShaderResourceView, Option<&str>)>
+    {
+        self.pool.iter().filter_map(|(&tid, t)| match t {
+            Texture::User(t) => Some((tid, &t.srv, t.label.as_deref())),
+            Texture::Managed(_) | Texture::Preloaded(_) => None,
+        })
+    }
+
+    pub(crate) fn clear_managed(&mut self) {
+        let freed: Vec<_> = self
+            .pool
+            .iter()
+            .filter(|(_, t)| t.is_managed())
+            .map(|(&tid, _)| tid)
+            .collect();
+        for tid in freed {
+            self.pool.remove(&tid);
+            if let Some(on_freed) = &mut self.on_freed {
+                on_freed(tid);
+            }
+        }
+    }
+
+    /// Apply `delta` to the pool, returning whether any texture was created,
+    /// updated or freed, as opposed to all requests being ignored.
+    pub(crate) fn update(
+        &mut self,
+        ctx: &ID3D11DeviceContext,
+        delta: &TexturesDelta,
+        strategy: TextureUploadStrategy,
+    ) -> Result<bool> {
+        let mut changed = false;
+        for &(tid, ref delta) in &delta.set {
             if delta.is_whole() {
-                self.pool.insert(
-                    tid,
-                    Self::create_texture(&self.device, delta.image)?,
-                );
-                // the old texture is returned and dropped here, freeing
-                // all its gpu resource.
-            } else if let Some(tex) = self.pool.get_mut(&tid) {
-                Self::update_partial(
+                match Self::create_texture(
+                    &self.device,
+                    ctx,
+                    &delta.image,
+                    delta.options,
+                    self.gamma_views,
+                    strategy,
+                ) {
+                    Ok(texture) => {
+                        changed = true;
+                        self.insert_managed(tid, texture, delta.image.size());
+                    },
+                    Err(err) => self.handle_upload_failure(tid, err)?,
+                }
+            } else if let Some(Texture::Managed(tex)) = self.pool.get_mut(&tid)
+            {
+                match Self::update_partial(
                     ctx,
                     tex,
-                    delta.image,
+                    &delta.image,
                     delta.pos.unwrap(),
-                )?;
+                ) {
+                    Ok(updated) => changed |= updated,
+                    Err(err) => self.handle_upload_failure(tid, err)?,
+                }
+            } else if let Some(Texture::Preloaded(_)) = self.pool.get(&tid) {
+                warn!(
+                    concat!(
+                        "egui wants to partially update the preloaded texture ",
+                        "{:?}, which probably does not match the current font ",
+                        "configuration. this request will be ignored."
+                    ),
+                    tid
+                );
             } else {
-                log::warn!("egui wants to update a non-existing texture {tid:?}. this request will be ignored.");
+                warn!("egui wants to update a non-existing texture {tid:?}. this request will be ignored.");
+            }
+        }
+        for tid in &delta.free {
+            // user textures are never freed by `egui`.
+            if self.pool.get(tid).is_some_and(Texture::is_managed) {
+                changed = true;
+                self.pool.remove(tid);
+                if let Some(on_freed) = &mut self.on_freed {
+                    on_freed(*tid);
+                }
+            }
+        }
+        Ok(changed)
+    }
+
+    fn insert_managed(
+        &mut self,
+        tid: TextureId,
+        mut texture: Texture,
+        [width, height]: [usize; 2],
+    ) {
+        // a texture is usually uploaded in the frame it is first sampled in,
+        // so it must not be evicted before that frame is drawn.
+        if let Texture::Managed(t) = &mut texture {
+            t.last_used = self.frame;
+        }
+        let old = self.pool.insert(tid, texture);
+        // the old texture is returned and dropped here, freeing all its gpu
+        // resource.
+        if old.is_some() {
+            if let Some(on_freed) = &mut self.on_freed {
+                on_freed(tid);
+            }
+        }
+        if let Some(on_created) = &mut self.on_created {
+            on_created(tid, width, height);
+        }
+    }
+
+    /// Count a new frame, in which textures are marked as sampled with
+    /// [`TexturePool::mark_used`].
+    pub(crate) fn begin_frame(&mut self) {
+        self.frame += 1;
+    }
+
+    /// Mark the textures `tids` as sampled in the current frame.
+    pub(crate) fn mark_used(&mut self, tids: impl Iterator<Item = TextureId>) {
+        for tid in tids {
+            if let Some(Texture::Managed(t)) = self.pool.get_mut(&tid) {
+                t.last_used = self.frame;
             }
         }
-        for tid in delta.free {
+    }
+
+    /// Free the least recently sampled managed textures not sampled in the
+    /// current frame counted by [`TexturePool::begin_frame`] until the managed
+    /// textures fit in `budget` bytes or no texture is left to free. The font
+    /// atlas is never freed. Returns the ids of the textures freed.
+    pub(crate) fn evict(&mut self, budget: usize) -> Vec<TextureId> {
+        let mut bytes: usize = self
+            .pool
+            .values()
+            .filter_map(|t| match t {
+                Texture::Managed(t) => Some(t.bytes()),
+                _ => None,
+            })
+            .sum();
+        let mut evicted = Vec::new();
+        if bytes <= budget {
+            return evicted;
+        }
+        let mut candidates: Vec<_> = self
+            .pool
+            .iter()
+            .filter_map(|(&tid, t)| match t {
+                Texture::Managed(t)
+                    if t.last_used < self.frame
+                        && tid != TextureId::default() =>
+                {
+                    Some((t.last_used, tid, t.bytes()))
+                },
+                _ => None,
+            })
+            .collect();
+        candidates.sort_unstable();
+        for (_, tid, size) in candidates {
+            if bytes <= budget {
+                break;
+            }
             self.pool.remove(&tid);
+            evicted.push(tid);
+            bytes -= size;
+            if let Some(on_freed) = &mut self.on_freed {
+                on_freed(tid);
+            }
+        }
+        if bytes > budget {
+            warn!(
+                concat!(
+                    "the managed textures take {} bytes after evicting all ",
+                    "unused ones, exceeding the budget of {} bytes."
+                ),
+                bytes, budget,
+            );
         }
+        evicted
+    }
+
+    /// Create a texture for `image` whose pixels are uploaded on `ctx`, e.g.
+    /// a deferred context, with `UpdateSubresource`. No copy of the pixels is
+    /// kept on the CPU, as partial updates are uploaded the same way.
+    pub(crate) fn record_color_texture(
+        device: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        image: &ColorImage,
+        options: TextureOptions,
+    ) -> Result<PendingTexture> {
+        let [width, height] = image.size;
+        let mut tex = None;
+        unsafe {
+            device.CreateTexture2D(
+                &D3D11_TEXTURE2D_DESC {
+                    Width: width as _,
+                    Height: height as _,
+                    MipLevels: 1,
+                    ArraySize: 1,
+                    Format: DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+                    SampleDesc: DXGI_SAMPLE_DESC {
+                        Count: 1,
+                        Quality: 0,
+                    },
+                    Usage: D3D11_USAGE_DEFAULT,
+                    BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as _,
+                    ..Default::default()
+                },
+                None,
+                Some(&mut tex),
+            )
+        }?;
+        let tex = tex.unwrap();
+        unsafe {
+            ctx.UpdateSubresource(
+                &tex,
+                0,
+                None,
+                image.pixels.as_ptr() as _,
+                (width * mem::size_of::<Color32>()) as _,
+                0,
+            )
+        };
+
+        let mut srv = None;
+        unsafe { device.CreateShaderResourceView(&tex, None, Some(&mut srv)) }?;
+        Ok(PendingTexture(ManagedTexture {
+            tex,
+            srv: srv.unwrap(),
+            pixels: None,
+            staging: None,
+            width,
+            height,
+            options,
+            gamma: None,
+            last_used: 0,
+        }))
+    }
+
+    /// Insert a texture created with [`TexturePool::record_color_texture`]
+    /// whose upload has been executed on `ctx`.
+    pub(crate) fn insert_pending(
+        &mut self,
+        ctx: &ID3D11DeviceContext,
+        tid: TextureId,
+        PendingTexture(mut texture): PendingTexture,
+    ) -> Result<()> {
+        if self.gamma_views {
+            texture.gamma = Some(Self::copy_texture(
+                &self.device,
+                ctx,
+                &texture.tex,
+                DXGI_FORMAT_R8G8B8A8_UNORM,
+            )?);
+        }
+        let size = [texture.width, texture.height];
+        self.insert_managed(tid, Texture::Managed(texture), size);
         Ok(())
     }
 
+    /// Update a part of `old`, returning whether it was updated.
     fn update_partial(
         ctx: &ID3D11DeviceContext,
-        old: &mut Texture,
-        image: ImageData,
+        old: &mut ManagedTexture,
+        image: &ImageData,
         [nx, ny]: [usize; 2],
-    ) -> Result<()> {
-        let subr = unsafe {
-            let mut output = D3D11_MAPPED_SUBRESOURCE::default();
-            ctx.Map(
-                &old.tex,
-                0,
-                D3D11_MAP_WRITE_DISCARD,
-                0,
-                Some(&mut output),
-            )?;
-            output
-        };
-        match image {
-            ImageData::Font(f) => {
-                let data = unsafe {
-                    let slice = slice::from_raw_parts_mut(
-                        subr.pData as *mut Color32,
-                        old.pixels.len(),
-                    );
-                    slice.as_mut_ptr().copy_from_nonoverlapping(
-                        old.pixels.as_ptr(),
-                        old.pixels.len(),
-                    );
-                    slice
+    ) -> Result<bool> {
+        let [width, height] = image.size();
+        if nx.saturating_add(width) > old.width
+            || ny.saturating_add(height) > old.height
+        {
+            warn!(
+                concat!(
+                    "egui wants to partially update a region of {}x{} at ",
+                    "({}, {}) of a texture of {}x{}, which does not fit in ",
+                    "the texture. this request will be ignored."
+                ),
+                width, height, nx, ny, old.width, old.height,
+            );
+            return Ok(false);
+        }
+        let new: Cow<[Color32]> = match image {
+            ImageData::Color(c) => Cow::Borrowed(&c.pixels),
+            ImageData::Font(f) => Cow::Owned(f.srgba_pixels(None).collect()),
+        };
+        let region = D3D11_BOX {
+            left: nx as _,
+            top: ny as _,
+            front: 0,
+            right: (nx + width) as _,
+            bottom: (ny + height) as _,
+            back: 1,
+        };
+        let gamma_tex = old.gamma.as_ref().map(|(tex, _)| tex);
+
+        if let Some(staging) = &old.staging {
+            let subr = unsafe {
+                let mut output = D3D11_MAPPED_SUBRESOURCE::default();
+                ctx.Map(staging, 0, D3D11_MAP_WRITE, 0, Some(&mut output))?;
+                output
+            };
+            for (y, row) in new.chunks_exact(width).enumerate() {
+                unsafe {
+                    let dst = (subr.pData as *mut u8)
+                        .add((ny + y) * subr.RowPitch as usize)
+                        as *mut Color32;
+                    dst.add(nx)
+                        .copy_from_nonoverlapping(row.as_ptr(), row.len());
+                }
+            }
+            unsafe { ctx.Unmap(staging, 0) };
+
+            // only the changed region is copied, into the gamma copy too.
+            for tex in iter::once(&old.tex).chain(gamma_tex) {
+                unsafe {
+                    ctx.CopySubresourceRegion(
+                        tex,
+                        0,
+                        nx as _,
+                        ny as _,
+                        0,
+                        staging,
+                        0,
+                        Some(&region),
+                    )
                 };
+            }
+            return Ok(true);
+        }
 
-                let new: Vec<Color32> = f
-                    .pixels
-                    .iter()
-                    .map(|a| {
-                        Color32::from_rgba_premultiplied(
-                            255,
-                            255,
-                            255,
-                            (a * 255.) as u8,
-                        )
-                    })
-                    .collect();
-
-                for y in 0..f.height() {
-                    for x in 0..f.width() {
-                        let whole = (ny + y) * old.width + nx + x;
-                        let frac = y * f.width() + x;
-                        old.pixels[whole] = new[frac];
-                        data[whole] = new[frac];
-                    }
+        let Some(pixels) = &mut old.pixels else {
+            // the textures are in `D3D11_USAGE_DEFAULT` without a copy on the
+            // cpu, so only the changed region is uploaded into them.
+            for tex in iter::once(&old.tex).chain(gamma_tex) {
+                unsafe {
+                    ctx.UpdateSubresource(
+                        tex,
+                        0,
+                        Some(&region),
+                        new.as_ptr() as _,
+                        (width * mem::size_of::<Color32>()) as _,
+                        0,
+                    )
+                };
+            }
+            return Ok(true);
+        };
+        for (y, row) in new.chunks_exact(width).enumerate() {
+            let whole = (ny + y) * old.width + nx;
+            pixels[whole..whole + row.len()].copy_from_slice(row);
+        }
+
+        #[cfg(debug_assertions)]
+        if unsafe { ctx.GetType() } == D3D11_DEVICE_CONTEXT_DEFERRED {
+            warn!(concat!(
+                "egui wants to partially update a texture on a deferred ",
+                "context. the update maps a dynamic texture with ",
+                "D3D11_MAP_WRITE_DISCARD, which only takes effect when the ",
+                "command list is executed and may corrupt the texture if it ",
+                "is used before then. update textures on the immediate ",
+                "context instead."
+            ));
+        }
+
+        // the textures are mapped with `D3D11_MAP_WRITE_DISCARD`, so they are
+        // rewritten as a whole, row by row as rows may be padded.
+        for tex in iter::once(&old.tex).chain(gamma_tex) {
+            let subr = unsafe {
+                let mut output = D3D11_MAPPED_SUBRESOURCE::default();
+                ctx.Map(tex, 0, D3D11_MAP_WRITE_DISCARD, 0, Some(&mut output))?;
+                output
+            };
+            for (y, row) in pixels.chunks_exact(old.width).enumerate() {
+                unsafe {
+                    let dst = (subr.pData as *mut u8)
+                        .add(y * subr.RowPitch as usize)
+                        as *mut Color32;
+                    dst.copy_from_nonoverlapping(row.as_ptr(), row.len());
                 }
-            },
-            _ => unreachable!(),
+            }
+            unsafe { ctx.Unmap(tex, 0) };
         }
-        unsafe { ctx.Unmap(&old.tex, 0) };
-        Ok(())
+        Ok(true)
     }
 
+    /// Create a texture for `data` uploaded with `strategy`.
+    ///
+    /// Any texture may be updated partially by `egui`, e.g. with
+    /// `TextureHandle::set_partial`, but only the font atlas is updated
+    /// often. Color images are thus never copied on the CPU: with
+    /// [`TextureUploadStrategy::Dynamic`] they are created in
+    /// `D3D11_USAGE_DEFAULT` and updated in place with `UpdateSubresource`,
+    /// and with [`TextureUploadStrategy::Staging`] the staging texture holds
+    /// their pixels. Only a dynamic font atlas keeps a copy of its pixels, as
+    /// it is rewritten as a whole on each update.
     fn create_texture(
         device: &ID3D11Device,
-        data: ImageData,
+        ctx: &ID3D11DeviceContext,
+        data: &ImageData,
+        options: TextureOptions,
+        gamma_view: bool,
+        strategy: TextureUploadStrategy,
     ) -> Result<Texture> {
-        let width = data.width();
-
-        let pixels = match &data {
-            ImageData::Color(c) => c.pixels.clone(),
-            ImageData::Font(f) => f
-                .pixels
-                .iter()
-                .map(|a| {
-                    Color32::from_rgba_premultiplied(
-                        255,
-                        255,
-                        255,
-                        (a * 255.) as u8,
-                    )
-                })
-                .collect(),
+        let [width, height] = data.size();
+        let pixels: Cow<[Color32]> = match data {
+            ImageData::Color(c) => Cow::Borrowed(&c.pixels),
+            ImageData::Font(f) => Cow::Owned(f.srgba_pixels(None).collect()),
+        };
+
+        let (tex, srv, staging) = match strategy {
+            TextureUploadStrategy::Dynamic
+                if matches!(data, ImageData::Color(_)) =>
+            {
+                let (tex, srv) = Self::create_default_texture(
+                    device,
+                    &pixels,
+                    width,
+                    DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+                )?;
+                (tex, srv, None)
+            },
+            TextureUploadStrategy::Dynamic => {
+                let (tex, srv) = Self::create_dynamic_texture(
+                    device,
+                    &pixels,
+                    width,
+                    DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+                )?;
+                (tex, srv, None)
+            },
+            TextureUploadStrategy::Staging => {
+                let staging = Self::create_staging_texture(
+                    device,
+                    &pixels,
+                    width,
+                    DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+                )?;
+                let (tex, srv) = Self::copy_texture(
+                    device,
+                    ctx,
+                    &staging,
+                    DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+                )?;
+                (tex, srv, Some(staging))
+            },
+        };
+        let pixels = match (&staging, pixels) {
+            (None, Cow::Owned(pixels)) => Some(pixels),
+            _ => None,
+        };
+        let gamma = match (gamma_view, &pixels) {
+            (false, _) => None,
+            (true, Some(pixels)) => Some(Self::create_dynamic_texture(
+                device,
+                pixels,
+                width,
+                DXGI_FORMAT_R8G8B8A8_UNORM,
+            )?),
+            (true, None) => Some(Self::copy_texture(
+                device,
+                ctx,
+                &tex,
+                DXGI_FORMAT_R8G8B8A8_UNORM,
+            )?),
+        };
+
+        Ok(Texture::Managed(ManagedTexture {
+            tex,
+            srv,
+            staging,
+            pixels,
+            width,
+            height,
+            options,
+            gamma,
+            last_used: 0,
+        }))
+    }
+
+    /// Copy `tex` on the GPU into a new texture of `format`, which must be
+    /// in the same format family. The copy can not be updated partially,
+    /// except by copying into it again.
+    fn copy_texture(
+        device: &ID3D11Device,
+        ctx: &ID3D11DeviceContext,
+        tex: &ID3D11Texture2D,
+        format: DXGI_FORMAT,
+    ) -> Result<(ID3D11Texture2D, ID3D11ShaderResourceView)> {
+        let mut desc = D3D11_TEXTURE2D_DESC::default();
+        unsafe { tex.GetDesc(&mut desc) };
+        let desc = D3D11_TEXTURE2D_DESC {
+            Format: format,
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as _,
+            CPUAccessFlags: 0,
+            ..desc
+        };
+
+        let mut copy = None;
+        unsafe { device.CreateTexture2D(&desc, None, Some(&mut copy)) }?;
+        let copy = copy.unwrap();
+        unsafe { ctx.CopyResource(&copy, tex) };
+
+        let mut srv = None;
+        unsafe {
+            device.CreateShaderResourceView(&copy, None, Some(&mut srv))
+        }?;
+        Ok((copy, srv.unwrap()))
+    }
+
+    /// Create a staging texture holding `pixels`, which can be mapped for
+    /// writing and copied from but not sampled.
+    fn create_staging_texture(
+        device: &ID3D11Device,
+        pixels: &[Color32],
+        width: usize,
+        format: DXGI_FORMAT,
+    ) -> Result<ID3D11Texture2D> {
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: width as _,
+            Height: (pixels.len() / width) as _,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: format,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_STAGING,
+            BindFlags: 0,
+            CPUAccessFlags: D3D11_CPU_ACCESS_WRITE.0 as _,
+            ..Default::default()
         };
 
+        let subresource_data = D3D11_SUBRESOURCE_DATA {
+            pSysMem: pixels.as_ptr() as _,
+            SysMemPitch: (width * mem::size_of::<Color32>()) as u32,
+            SysMemSlicePitch: 0,
+        };
+
+        let mut tex = None;
+        unsafe {
+            device.CreateTexture2D(
+                &desc,
+                Some(&subresource_data),
+                Some(&mut tex),
+            )
+        }?;
+        Ok(tex.unwrap())
+    }
+
+    /// Create a texture in `D3D11_USAGE_DEFAULT` holding `pixels`, which can
+    /// be updated with `UpdateSubresource`.
+    fn create_default_texture(
+        device: &ID3D11Device,
+        pixels: &[Color32],
+        width: usize,
+        format: DXGI_FORMAT,
+    ) -> Result<(ID3D11Texture2D, ID3D11ShaderResourceView)> {
         let desc = D3D11_TEXTURE2D_DESC {
-            Width: data.width() as _,
-            Height: data.height() as _,
+            Width: width as _,
+            Height: (pixels.len() / width) as _,
             MipLevels: 1,
             ArraySize: 1,
-            Format: DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+            Format: format,
+            SampleDesc: DXGI_SAMPLE_DESC {
+                Count: 1,
+                Quality: 0,
+            },
+            Usage: D3D11_USAGE_DEFAULT,
+            BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as _,
+            ..Default::default()
+        };
+
+        let subresource_data = D3D11_SUBRESOURCE_DATA {
+            pSysMem: pixels.as_ptr() as _,
+            SysMemPitch: (width * mem::size_of::<Color32>()) as u32,
+            SysMemSlicePitch: 0,
+        };
+
+        let mut tex = None;
+        unsafe {
+            device.CreateTexture2D(
+                &desc,
+                Some(&subresource_data),
+                Some(&mut tex),
+            )
+        }?;
+        let tex = tex.unwrap();
+
+        let mut srv = None;
+        unsafe { device.CreateShaderResourceView(&tex, None, Some(&mut srv)) }?;
+        Ok((tex, srv.unwrap()))
+    }
+
+    fn create_dynamic_texture(
+        device: &ID3D11Device,
+        pixels: &[Color32],
+        width: usize,
+        format: DXGI_FORMAT,
+    ) -> Result<(ID3D11Texture2D, ID3D11ShaderResourceView)> {
+        let desc = D3D11_TEXTURE2D_DESC {
+            Width: width as _,
+            Height: (pixels.len() / width) as _,
+            MipLevels: 1,
+            ArraySize: 1,
+            Format: format,
             SampleDesc: DXGI_SAMPLE_DESC {
                 Count: 1,
                 Quality: 0,
@@ -186,13 +1104,19 @@ impl TexturePool {
 
         let mut srv = None;
         unsafe { device.CreateShaderResourceView(&tex, None, Some(&mut srv)) }?;
-        let srv = srv.unwrap();
-
-        Ok(Texture {
-            tex,
-            srv,
-            width,
-            pixels,
-        })
+        Ok((tex, srv.unwrap()))
     }
 }
+
+/// Get the size of the 2D texture `srv` views, or zero if it views some other
+/// kind of resource.
+fn srv_size(srv: &ID3D11ShaderResourceView) -> (u32, u32) {
+    let Ok(tex) = unsafe { srv.GetResource() }
+        .and_then(|res| res.cast::<ID3D11Texture2D>())
+    else {
+        return (0, 0);
+    };
+    let mut desc = D3D11_TEXTURE2D_DESC::default();
+    unsafe { tex.GetDesc(&mut desc) };
+    (desc.Width, desc.Height)
+}
@@ -0,0 +1,120 @@
+use crate::{DisplayRotation, Renderer};
+
+use egui::Pos2;
+
+use windows::{core::Result, Win32::Graphics::Direct3D11::*};
+
+/// An affine transform of positions, mapping `(x, y)` to
+/// `(m[0] * x + m[1] * y + m[2], m[3] * x + m[4] * y + m[5])`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Transform([f32; 6]);
+
+impl Transform {
+    pub const IDENTITY: Self = Self([1., 0., 0., 0., 1., 0.]);
+
+    /// Get the transform from positions in points in the screen as seen by
+    /// `egui` into normalized device coordinates of a frame of `frame_size`
+    /// pixels, with `rotation` applied.
+    pub fn to_ndc(
+        pixels_per_point: f32,
+        rotation: DisplayRotation,
+        frame_size: (f32, f32),
+    ) -> Self {
+        let s = pixels_per_point;
+        let (w, h) = frame_size;
+        // the transform into physical pixels of the render target.
+        let [a, b, c, d, e, f] = match rotation {
+            DisplayRotation::None => [s, 0., 0., 0., s, 0.],
+            DisplayRotation::Rotate90 => [0., -s, w, s, 0., 0.],
+            DisplayRotation::Rotate180 => [-s, 0., w, 0., -s, h],
+            DisplayRotation::Rotate270 => [0., s, 0., -s, 0., h],
+        };
+        let (sx, sy) = (2. / w, -2. / h);
+        Self([sx * a, sx * b, sx * c - 1., sy * d, sy * e, sy * f + 1.])
+    }
+
+    pub fn apply(&self, pos: Pos2) -> Pos2 {
+        let [a, b, c, d, e, f] = self.0;
+        Pos2::new(a * pos.x + b * pos.y + c, d * pos.x + e * pos.y + f)
+    }
+
+    /// Get the transform as a row-major 4x4 matrix multiplied to row vectors
+    /// `(x, y, z, 1)`.
+    fn to_matrix(self) -> [[f32; 4]; 4] {
+        let [a, b, c, d, e, f] = self.0;
+        [
+            [a, d, 0., 0.],
+            [b, e, 0., 0.],
+            [0., 0., 1., 0.],
+            [c, f, 0., 1.],
+        ]
+    }
+}
+
+impl Renderer {
+    /// Set a custom vertex shader to draw meshes with, or `None` to use the
+    /// built-in vertex shader, which is the default.
+    ///
+    /// Positions of vertices are passed in points as tessellated by `egui`,
+    /// and transformed into normalized device coordinates on the GPU. The
+    /// transform (including
+    /// [`RenderOptions::display_rotation`](crate::RenderOptions::display_rotation))
+    /// is computed once per frame and bound to constant buffer slot 0 of the
+    /// vertex shader stage as a `row_major float4x4`, to be multiplied to
+    /// the row vector `float4(position, 0.0, 1.0)`.
+    ///
+    /// The vertex shader must have the same input signature as the built-in
+    /// one (see [`Renderer::set_pixel_shader`]). The built-in vertex shader is
+    /// `vs_main_transform` in `shaders/egui.hlsl` in the repository of this
+    /// crate. The internal render target used by some options (see
+    /// [`Renderer::render`]) is always blitted with a pass-through vertex
+    /// shader.
+    pub fn set_vertex_shader(
+        &mut self,
+        vertex_shader: Option<ID3D11VertexShader>,
+    ) {
+        self.custom_vertex_shader = vertex_shader;
+    }
+
+    /// Upload `transform` into the constant buffer of the vertex shader and
+    /// bind it.
+    pub(crate) fn bind_transform(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        transform: Transform,
+    ) -> Result<()> {
+        if self.transform_buffer.is_none() {
+            let mut buffer = None;
+            unsafe {
+                self.device.CreateBuffer(
+                    &D3D11_BUFFER_DESC {
+                        ByteWidth: size_of::<[[f32; 4]; 4]>() as _,
+                        Usage: D3D11_USAGE_DYNAMIC,
+                        BindFlags: D3D11_BIND_CONSTANT_BUFFER.0 as _,
+                        CPUAccessFlags: D3D11_CPU_ACCESS_WRITE.0 as _,
+                        ..D3D11_BUFFER_DESC::default()
+                    },
+                    None,
+                    Some(&mut buffer),
+                )
+            }?;
+            self.transform_buffer = buffer;
+        }
+        let buffer = self.transform_buffer.as_ref().unwrap();
+        unsafe {
+            let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+            device_context.Map(
+                buffer,
+                0,
+                D3D11_MAP_WRITE_DISCARD,
+                0,
+                Some(&mut mapped),
+            )?;
+            (mapped.pData as *mut [[f32; 4]; 4]).write(transform.to_matrix());
+            device_context.Unmap(buffer, 0);
+            device_context
+                .VSSetConstantBuffers(0, Some(&[Some(buffer.clone())]));
+        }
+        Ok(())
+    }
+}
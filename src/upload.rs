@@ -0,0 +1,137 @@
+use crate::{texture::PendingTexture, Renderer, TexturePool};
+
+use egui::{ImageData, TextureId, TexturesDelta};
+
+use windows::{
+    core::{Error, Result},
+    Win32::{
+        Foundation::{BOOL, E_INVALIDARG},
+        Graphics::Direct3D11::*,
+    },
+};
+
+/// A handle recording uploads of textures managed by `egui` on a deferred
+/// context, e.g. on a worker thread, to keep uploads of large images off the
+/// thread rendering `egui`. Get one with [`Renderer::texture_uploader`].
+#[derive(Clone)]
+pub struct TextureUploader {
+    device: ID3D11Device,
+}
+
+/// Texture uploads recorded with [`TextureUploader::record`], to be applied
+/// with [`Renderer::apply_texture_uploads`].
+pub struct TextureUploads {
+    command_list: Option<ID3D11CommandList>,
+    textures: Vec<(TextureId, PendingTexture)>,
+    remaining: TexturesDelta,
+}
+
+impl TextureUploader {
+    /// Record the uploads of the whole color images in `textures_delta` on
+    /// `device_context`, and finish them into a command list.
+    ///
+    /// The rest of the delta, i.e. the font atlas, partial updates and the
+    /// textures to free, is small or depends on the textures in the pool, and
+    /// is kept to be applied along with the command list. Textures uploaded
+    /// this way keep no copy of their pixels on the CPU, and partial updates
+    /// to them are uploaded with `UpdateSubresource`.
+    ///
+    /// `device_context` must be a deferred context of the device the
+    /// [`Renderer`] was created with, or an error with `E_INVALIDARG` is
+    /// returned. As with any deferred context, it must not be used by
+    /// several threads at once, and commands already recorded on it end up
+    /// in the same command list.
+    pub fn record(
+        &self,
+        device_context: &ID3D11DeviceContext,
+        textures_delta: TexturesDelta,
+    ) -> Result<TextureUploads> {
+        if unsafe { device_context.GetType() } != D3D11_DEVICE_CONTEXT_DEFERRED
+        {
+            return Err(Error::new(
+                E_INVALIDARG,
+                "texture uploads must be recorded on a deferred context.",
+            ));
+        }
+        let mut textures = Vec::new();
+        let mut remaining = TexturesDelta {
+            set: Vec::new(),
+            free: textures_delta.free,
+        };
+        for (tid, delta) in textures_delta.set {
+            match &delta.image {
+                ImageData::Color(image) if delta.is_whole() => {
+                    textures.push((
+                        tid,
+                        TexturePool::record_color_texture(
+                            &self.device,
+                            device_context,
+                            image,
+                            delta.options,
+                        )?,
+                    ));
+                },
+                _ => remaining.set.push((tid, delta)),
+            }
+        }
+        let mut command_list = None;
+        if !textures.is_empty() {
+            unsafe {
+                device_context
+                    .FinishCommandList(BOOL(0), Some(&mut command_list))
+            }?;
+        }
+        Ok(TextureUploads {
+            command_list,
+            textures,
+            remaining,
+        })
+    }
+}
+
+impl Renderer {
+    /// Get a [`TextureUploader`] recording texture uploads for this renderer
+    /// on a deferred context, which can be sent to a worker thread.
+    ///
+    /// To upload textures asynchronously, take
+    /// [`RendererOutput::textures_delta`](crate::RendererOutput::textures_delta)
+    /// out of the output of `egui` (e.g. with [`std::mem::take`]) and record
+    /// it with [`TextureUploader::record`] on the worker thread. The result
+    /// must then be applied with [`Renderer::apply_texture_uploads`] before
+    /// rendering the output the delta was taken from, and the deltas of all
+    /// frames must be applied in the order `egui` produced them, as later
+    /// deltas may replace or free textures uploaded by earlier ones.
+    pub fn texture_uploader(&self) -> TextureUploader {
+        TextureUploader {
+            device: self.device.clone(),
+        }
+    }
+
+    /// Apply texture uploads recorded with [`TextureUploader::record`]: the
+    /// command list is executed on `device_context`, which should be the
+    /// immediate context, preserving its state, and the uploaded textures
+    /// are inserted into the pool along with the rest of the delta.
+    pub fn apply_texture_uploads(
+        &mut self,
+        device_context: &ID3D11DeviceContext,
+        uploads: TextureUploads,
+    ) -> Result<()> {
+        let TextureUploads {
+            command_list,
+            textures,
+            remaining,
+        } = uploads;
+        if let Some(command_list) = command_list {
+            unsafe {
+                device_context.ExecuteCommandList(&command_list, BOOL(1))
+            };
+        }
+        self.damage
+            .invalidate_textures(textures.iter().map(|(tid, _)| *tid));
+        for (tid, texture) in textures {
+            self.texture_pool
+                .insert_pending(device_context, tid, texture)?;
+        }
+        self.apply_textures_delta(device_context, &remaining)
+    }
+}
@@ -0,0 +1,359 @@
+//! Helpers for applications using native Win32 APIs for window management
+//! instead of a windowing library like `winit`. Available with the `win32`
+//! feature.
+
+use egui::{
+    CursorIcon, Event, Key, Modifiers, MouseWheelUnit, PointerButton, Pos2,
+    RawInput, Rect, ViewportId,
+};
+
+use std::mem;
+
+use windows::{
+    core::{Result, PCWSTR},
+    Win32::{
+        Foundation::{HWND, LPARAM, WPARAM},
+        UI::{Input::KeyboardAndMouse::*, WindowsAndMessaging::*},
+    },
+};
+
+// defined in `Win32_UI_Controls` and `Win32_System_SystemServices`, which
+// are not worth enabling for a few constants.
+const WM_MOUSELEAVE: u32 = 0x02a3;
+const MK_SHIFT: usize = 0x0004;
+const MK_CONTROL: usize = 0x0008;
+
+/// Collects the [`egui::RawInput`] of a window from its window messages, for
+/// applications running their own window procedure instead of using
+/// `egui_winit`.
+///
+/// Pass each message the window procedure receives to
+/// [`InputCollector::handle_message`], and take the input collected for each
+/// frame with [`InputCollector::take_input`] before running `egui`.
+/// Positions are converted from physical pixels into points with the scale
+/// factor, which should be updated with [`InputCollector::set_scale_factor`]
+/// on `WM_DPICHANGED`.
+///
+/// Only mouse, keyboard, text and focus input is translated. Reading the
+/// clipboard is left to *you*, so `Ctrl+V` should be answered with an
+/// [`egui::Event::Paste`] pushed to the input, while `Ctrl+C` and `Ctrl+X`
+/// are translated into [`egui::Event::Copy`] and [`egui::Event::Cut`].
+pub struct InputCollector {
+    raw_input: RawInput,
+    scale_factor: f32,
+    tracking_mouse_leave: bool,
+    high_surrogate: Option<u16>,
+}
+
+impl InputCollector {
+    /// Create a collector for a window whose scale factor is `scale_factor`,
+    /// e.g. `GetDpiForWindow(hwnd) as f32 / 96.0`.
+    pub fn new(scale_factor: f32) -> Self {
+        Self {
+            raw_input: RawInput {
+                focused: true,
+                ..RawInput::default()
+            },
+            scale_factor,
+            tracking_mouse_leave: false,
+            high_surrogate: None,
+        }
+    }
+
+    /// Set the scale factor of the window, e.g. on `WM_DPICHANGED`.
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        self.scale_factor = scale_factor;
+    }
+
+    /// Translate a window message into input for `egui`. Returns whether
+    /// the message was translated. The message should still be passed to
+    /// `DefWindowProcW` afterwards as usual, so that e.g. `Alt+F4` keeps
+    /// working.
+    pub fn handle_message(
+        &mut self,
+        hwnd: HWND,
+        msg: u32,
+        wparam: WPARAM,
+        lparam: LPARAM,
+    ) -> bool {
+        let modifiers = self.raw_input.modifiers;
+        let event = match msg {
+            WM_MOUSEMOVE => {
+                if !self.tracking_mouse_leave {
+                    let mut track = TRACKMOUSEEVENT {
+                        cbSize: mem::size_of::<TRACKMOUSEEVENT>() as _,
+                        dwFlags: TME_LEAVE,
+                        hwndTrack: hwnd,
+                        dwHoverTime: 0,
+                    };
+                    self.tracking_mouse_leave =
+                        unsafe { TrackMouseEvent(&mut track) }.is_ok();
+                }
+                Event::PointerMoved(self.pointer_pos(lparam))
+            },
+            WM_MOUSELEAVE => {
+                self.tracking_mouse_leave = false;
+                Event::PointerGone
+            },
+            WM_LBUTTONDOWN | WM_LBUTTONDBLCLK | WM_LBUTTONUP
+            | WM_RBUTTONDOWN | WM_RBUTTONDBLCLK | WM_RBUTTONUP
+            | WM_MBUTTONDOWN | WM_MBUTTONDBLCLK | WM_MBUTTONUP
+            | WM_XBUTTONDOWN | WM_XBUTTONDBLCLK | WM_XBUTTONUP => {
+                let (button, pressed) = match msg {
+                    WM_LBUTTONDOWN | WM_LBUTTONDBLCLK => {
+                        (PointerButton::Primary, true)
+                    },
+                    WM_LBUTTONUP => (PointerButton::Primary, false),
+                    WM_RBUTTONDOWN | WM_RBUTTONDBLCLK => {
+                        (PointerButton::Secondary, true)
+                    },
+                    WM_RBUTTONUP => (PointerButton::Secondary, false),
+                    WM_MBUTTONDOWN | WM_MBUTTONDBLCLK => {
+                        (PointerButton::Middle, true)
+                    },
+                    WM_MBUTTONUP => (PointerButton::Middle, false),
+                    _ => {
+                        let button = if hiword(wparam.0) == XBUTTON1 {
+                            PointerButton::Extra1
+                        } else {
+                            PointerButton::Extra2
+                        };
+                        (button, msg != WM_XBUTTONUP)
+                    },
+                };
+                // keep receiving mouse messages while a button is held down
+                // outside of the window, e.g. when dragging a slider.
+                unsafe {
+                    if pressed {
+                        SetCapture(hwnd);
+                    } else {
+                        let _ = ReleaseCapture();
+                    }
+                }
+                Event::PointerButton {
+                    pos: self.pointer_pos(lparam),
+                    button,
+                    pressed,
+                    modifiers: Modifiers {
+                        shift: wparam.0 & MK_SHIFT != 0,
+                        ctrl: wparam.0 & MK_CONTROL != 0,
+                        command: wparam.0 & MK_CONTROL != 0,
+                        ..modifiers
+                    },
+                }
+            },
+            WM_MOUSEWHEEL | WM_MOUSEHWHEEL => {
+                let delta = hiword(wparam.0) as i16 as f32 / WHEEL_DELTA as f32;
+                Event::MouseWheel {
+                    unit: MouseWheelUnit::Line,
+                    // a positive horizontal delta scrolls to the right,
+                    // which moves the content to the left.
+                    delta: if msg == WM_MOUSEWHEEL {
+                        egui::vec2(0., delta)
+                    } else {
+                        egui::vec2(-delta, 0.)
+                    },
+                    modifiers,
+                }
+            },
+            WM_KEYDOWN | WM_SYSKEYDOWN | WM_KEYUP | WM_SYSKEYUP => {
+                let pressed = msg == WM_KEYDOWN || msg == WM_SYSKEYDOWN;
+                let vk = VIRTUAL_KEY(wparam.0 as u16);
+                let modifiers = &mut self.raw_input.modifiers;
+                match vk {
+                    VK_SHIFT => modifiers.shift = pressed,
+                    VK_CONTROL => {
+                        modifiers.ctrl = pressed;
+                        modifiers.command = pressed;
+                    },
+                    VK_MENU => modifiers.alt = pressed,
+                    _ => {},
+                }
+                let modifiers = *modifiers;
+                let Some(key) = key_from_vk(vk) else {
+                    return false;
+                };
+                if pressed && modifiers.command {
+                    match key {
+                        Key::C => self.raw_input.events.push(Event::Copy),
+                        Key::X => self.raw_input.events.push(Event::Cut),
+                        _ => {},
+                    }
+                }
+                Event::Key {
+                    key,
+                    physical_key: None,
+                    pressed,
+                    // bit 30 is set if the key was down before the message.
+                    repeat: pressed && lparam.0 & (1 << 30) != 0,
+                    modifiers,
+                }
+            },
+            WM_CHAR => {
+                let unit = wparam.0 as u16;
+                if (0xd800..0xdc00).contains(&unit) {
+                    self.high_surrogate = Some(unit);
+                    return true;
+                }
+                let units = match self.high_surrogate.take() {
+                    Some(high) => vec![high, unit],
+                    None => vec![unit],
+                };
+                let text: String = char::decode_utf16(units)
+                    .filter_map(|c| c.ok())
+                    .filter(|c| !c.is_control())
+                    .collect();
+                if text.is_empty() {
+                    return false;
+                }
+                Event::Text(text)
+            },
+            WM_SETFOCUS | WM_KILLFOCUS => {
+                let focused = msg == WM_SETFOCUS;
+                self.raw_input.focused = focused;
+                if !focused {
+                    // key releases are not received without focus.
+                    self.raw_input.modifiers = Modifiers::default();
+                }
+                Event::WindowFocused(focused)
+            },
+            _ => return false,
+        };
+        self.raw_input.events.push(event);
+        true
+    }
+
+    /// Take the input collected since the last call, for a window whose
+    /// client area is `client_size` physical pixels.
+    pub fn take_input(&mut self, client_size: (u32, u32)) -> RawInput {
+        let next = RawInput {
+            modifiers: self.raw_input.modifiers,
+            focused: self.raw_input.focused,
+            ..RawInput::default()
+        };
+        let mut raw_input = mem::replace(&mut self.raw_input, next);
+        let size = egui::vec2(client_size.0 as f32, client_size.1 as f32)
+            / self.scale_factor;
+        raw_input.screen_rect = Some(Rect::from_min_size(Pos2::ZERO, size));
+        raw_input
+            .viewports
+            .entry(ViewportId::ROOT)
+            .or_default()
+            .native_pixels_per_point = Some(self.scale_factor);
+        raw_input
+    }
+
+    fn pointer_pos(&self, lparam: LPARAM) -> Pos2 {
+        // coordinates are signed, as they are negative left of or above the
+        // window while the mouse is captured.
+        let x = lparam.0 as u16 as i16 as f32;
+        let y = hiword(lparam.0 as usize) as i16 as f32;
+        Pos2::new(x, y) / self.scale_factor
+    }
+}
+
+fn hiword(value: usize) -> u16 {
+    (value >> 16) as u16
+}
+
+fn key_from_vk(vk: VIRTUAL_KEY) -> Option<Key> {
+    Some(match vk {
+        // letters and digits share their codes with ASCII.
+        VIRTUAL_KEY(code @ (0x30..=0x39 | 0x41..=0x5a)) => {
+            return Key::from_name(&char::from(code as u8).to_string());
+        },
+        VIRTUAL_KEY(code) if (VK_NUMPAD0.0..=VK_NUMPAD9.0).contains(&code) => {
+            return Key::from_name(&(code - VK_NUMPAD0.0).to_string());
+        },
+        VIRTUAL_KEY(code) if (VK_F1.0..=VK_F20.0).contains(&code) => {
+            return Key::from_name(&format!("F{}", code - VK_F1.0 + 1));
+        },
+        VK_DOWN => Key::ArrowDown,
+        VK_LEFT => Key::ArrowLeft,
+        VK_RIGHT => Key::ArrowRight,
+        VK_UP => Key::ArrowUp,
+        VK_ESCAPE => Key::Escape,
+        VK_TAB => Key::Tab,
+        VK_BACK => Key::Backspace,
+        VK_RETURN => Key::Enter,
+        VK_SPACE => Key::Space,
+        VK_INSERT => Key::Insert,
+        VK_DELETE => Key::Delete,
+        VK_HOME => Key::Home,
+        VK_END => Key::End,
+        VK_PRIOR => Key::PageUp,
+        VK_NEXT => Key::PageDown,
+        VK_OEM_MINUS | VK_SUBTRACT => Key::Minus,
+        VK_OEM_PLUS => Key::Equals,
+        VK_ADD => Key::Plus,
+        VK_OEM_COMMA => Key::Comma,
+        VK_OEM_PERIOD | VK_DECIMAL => Key::Period,
+        VK_OEM_1 => Key::Semicolon,
+        VK_OEM_2 | VK_DIVIDE => Key::Slash,
+        VK_OEM_3 => Key::Backtick,
+        VK_OEM_4 => Key::OpenBracket,
+        VK_OEM_5 => Key::Backslash,
+        VK_OEM_6 => Key::CloseBracket,
+        VK_OEM_7 => Key::Quote,
+        _ => return None,
+    })
+}
+
+/// Get the system cursor best matching `icon`, or `None` for
+/// [`CursorIcon::None`], which hides the cursor. Icons without a matching
+/// system cursor (e.g. [`CursorIcon::ZoomIn`]) fall back to the arrow.
+pub fn cursor_for_icon(icon: CursorIcon) -> Result<Option<HCURSOR>> {
+    let Some(name) = cursor_name(icon) else {
+        return Ok(None);
+    };
+    unsafe { LoadCursorW(None, name) }.map(Some)
+}
+
+/// Set the cursor to the system cursor best matching `icon`, e.g. the
+/// [`egui::PlatformOutput::cursor_icon`] from [`split_output`](crate::split_output).
+///
+/// As Windows resets the cursor to the class cursor of the window whenever
+/// the mouse moves, you should call this in response to `WM_SETCURSOR` when
+/// the hit test is `HTCLIENT` and return `TRUE`, instead of once per frame.
+pub fn set_cursor_icon(icon: CursorIcon) -> Result<()> {
+    let cursor = cursor_for_icon(icon)?;
+    unsafe { SetCursor(cursor.unwrap_or_default()) };
+    Ok(())
+}
+
+fn cursor_name(icon: CursorIcon) -> Option<PCWSTR> {
+    Some(match icon {
+        CursorIcon::None => return None,
+        CursorIcon::Default
+        | CursorIcon::ContextMenu
+        | CursorIcon::Alias
+        | CursorIcon::Copy
+        | CursorIcon::ZoomIn
+        | CursorIcon::ZoomOut => IDC_ARROW,
+        CursorIcon::Help => IDC_HELP,
+        CursorIcon::PointingHand => IDC_HAND,
+        CursorIcon::Progress => IDC_APPSTARTING,
+        CursorIcon::Wait => IDC_WAIT,
+        CursorIcon::Cell | CursorIcon::Crosshair => IDC_CROSS,
+        CursorIcon::Text | CursorIcon::VerticalText => IDC_IBEAM,
+        CursorIcon::NoDrop | CursorIcon::NotAllowed => IDC_NO,
+        CursorIcon::Move
+        | CursorIcon::Grab
+        | CursorIcon::Grabbing
+        | CursorIcon::AllScroll => IDC_SIZEALL,
+        CursorIcon::ResizeHorizontal
+        | CursorIcon::ResizeEast
+        | CursorIcon::ResizeWest
+        | CursorIcon::ResizeColumn => IDC_SIZEWE,
+        CursorIcon::ResizeVertical
+        | CursorIcon::ResizeNorth
+        | CursorIcon::ResizeSouth
+        | CursorIcon::ResizeRow => IDC_SIZENS,
+        CursorIcon::ResizeNeSw
+        | CursorIcon::ResizeNorthEast
+        | CursorIcon::ResizeSouthWest => IDC_SIZENESW,
+        CursorIcon::ResizeNwSe
+        | CursorIcon::ResizeNorthWest
+        | CursorIcon::ResizeSouthEast => IDC_SIZENWSE,
+    })
+}
@@ -0,0 +1,961 @@
+//! Tests rendering with the WARP software rasterizer, so that they run
+//! without a GPU.
+
+use std::sync::{Arc, Mutex};
+
+use egui::{
+    epaint::{ClippedPrimitive, ClippedShape, ImageDelta, Primitive},
+    Color32, ColorImage, FontImage, ImageData, Mesh, Rect, TextureId,
+    TextureOptions, TexturesDelta,
+};
+use egui_directx11::{
+    BlendMode, BufferStrategy, ColorSpace, Renderer, StencilMask, TextureKind,
+    TextureUploadStrategy,
+};
+
+use windows::{
+    core::Result,
+    Win32::{
+        Foundation::{BOOL, HMODULE},
+        Graphics::{Direct3D::*, Direct3D11::*, Dxgi::Common::*},
+    },
+};
+
+const SIZE: u32 = 4;
+
+fn create_device() -> Result<(ID3D11Device, ID3D11DeviceContext)> {
+    let (mut device, mut device_context) = (None, None);
+    unsafe {
+        D3D11CreateDevice(
+            None,
+            D3D_DRIVER_TYPE_WARP,
+            HMODULE::default(),
+            D3D11_CREATE_DEVICE_FLAG(0),
+            Some(&[D3D_FEATURE_LEVEL_11_0]),
+            D3D11_SDK_VERSION,
+            Some(&mut device),
+            None,
+            Some(&mut device_context),
+        )
+    }?;
+    Ok((device.unwrap(), device_context.unwrap()))
+}
+
+fn create_texture(
+    device: &ID3D11Device,
+    format: DXGI_FORMAT,
+    bind_flags: D3D11_BIND_FLAG,
+) -> Result<ID3D11Texture2D> {
+    let mut tex = None;
+    unsafe {
+        device.CreateTexture2D(
+            &D3D11_TEXTURE2D_DESC {
+                Width: SIZE,
+                Height: SIZE,
+                MipLevels: 1,
+                ArraySize: 1,
+                Format: format,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Usage: D3D11_USAGE_DEFAULT,
+                BindFlags: bind_flags.0 as _,
+                ..Default::default()
+            },
+            None,
+            Some(&mut tex),
+        )
+    }?;
+    Ok(tex.unwrap())
+}
+
+fn create_render_target(
+    device: &ID3D11Device,
+) -> Result<(ID3D11Texture2D, ID3D11RenderTargetView)> {
+    let tex = create_texture(
+        device,
+        DXGI_FORMAT_R8G8B8A8_UNORM,
+        D3D11_BIND_RENDER_TARGET,
+    )?;
+    let mut rtv = None;
+    unsafe { device.CreateRenderTargetView(&tex, None, Some(&mut rtv)) }?;
+    Ok((tex, rtv.unwrap()))
+}
+
+/// Read back the center pixel of a texture created by
+/// `create_render_target`, away from anti-aliased edges.
+fn read_pixel(
+    device: &ID3D11Device,
+    device_context: &ID3D11DeviceContext,
+    tex: &ID3D11Texture2D,
+) -> Result<[u8; 4]> {
+    read_pixel_at(device, device_context, tex, SIZE / 2, SIZE / 2)
+}
+
+/// Read back the pixel at `(x, y)` of a texture created by
+/// `create_render_target`.
+fn read_pixel_at(
+    device: &ID3D11Device,
+    device_context: &ID3D11DeviceContext,
+    tex: &ID3D11Texture2D,
+    x: u32,
+    y: u32,
+) -> Result<[u8; 4]> {
+    let mut staging = None;
+    unsafe {
+        device.CreateTexture2D(
+            &D3D11_TEXTURE2D_DESC {
+                Width: SIZE,
+                Height: SIZE,
+                MipLevels: 1,
+                ArraySize: 1,
+                Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Usage: D3D11_USAGE_STAGING,
+                CPUAccessFlags: D3D11_CPU_ACCESS_READ.0 as _,
+                ..Default::default()
+            },
+            None,
+            Some(&mut staging),
+        )
+    }?;
+    let staging = staging.unwrap();
+    let mut mapped = D3D11_MAPPED_SUBRESOURCE::default();
+    unsafe {
+        device_context.CopyResource(&staging, tex);
+        device_context.Map(
+            &staging,
+            0,
+            D3D11_MAP_READ,
+            0,
+            Some(&mut mapped),
+        )?;
+    }
+    let offset = (y * mapped.RowPitch + x * 4) as usize;
+    let pixel = unsafe { *mapped.pData.cast::<u8>().add(offset).cast() };
+    unsafe { device_context.Unmap(&staging, 0) };
+    Ok(pixel)
+}
+
+/// White textures of a single texel for each of `tids`.
+fn white_textures_delta(tids: &[TextureId]) -> TexturesDelta {
+    let image = Arc::new(ColorImage::new([1, 1], Color32::WHITE));
+    TexturesDelta {
+        set: tids
+            .iter()
+            .map(|&tid| {
+                let image = ImageData::Color(image.clone());
+                (tid, ImageDelta::full(image, TextureOptions::NEAREST))
+            })
+            .collect(),
+        free: Vec::new(),
+    }
+}
+
+/// A white font atlas of a single texel, so that meshes sampling
+/// [`egui::epaint::WHITE_UV`] are drawn in their vertex colors.
+fn font_atlas_delta() -> TexturesDelta {
+    white_textures_delta(&[TextureId::default()])
+}
+
+/// A mesh covering the whole render target in `color`.
+fn full_rect(color: Color32) -> Vec<ClippedPrimitive> {
+    let mut mesh = Mesh::default();
+    let rect =
+        Rect::from_min_max((0., 0.).into(), (SIZE as f32, SIZE as f32).into());
+    mesh.add_colored_rect(rect, color);
+    vec![ClippedPrimitive {
+        clip_rect: Rect::EVERYTHING,
+        primitive: Primitive::Mesh(mesh),
+    }]
+}
+
+#[test]
+fn stencil_mask_clips_rendering() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (tex, rtv) = create_render_target(&device)?;
+    let depth_stencil = create_texture(
+        &device,
+        DXGI_FORMAT_D24_UNORM_S8_UINT,
+        D3D11_BIND_DEPTH_STENCIL,
+    )?;
+    let (mut dsv, mut dss) = (None, None);
+    let face = D3D11_DEPTH_STENCILOP_DESC {
+        StencilFailOp: D3D11_STENCIL_OP_KEEP,
+        StencilDepthFailOp: D3D11_STENCIL_OP_KEEP,
+        StencilPassOp: D3D11_STENCIL_OP_KEEP,
+        StencilFunc: D3D11_COMPARISON_EQUAL,
+    };
+    unsafe {
+        device.CreateDepthStencilView(&depth_stencil, None, Some(&mut dsv))?;
+        device.CreateDepthStencilState(
+            &D3D11_DEPTH_STENCIL_DESC {
+                DepthEnable: BOOL(0),
+                DepthWriteMask: D3D11_DEPTH_WRITE_MASK_ZERO,
+                DepthFunc: D3D11_COMPARISON_ALWAYS,
+                StencilEnable: BOOL(1),
+                StencilReadMask: 0xff,
+                StencilWriteMask: 0,
+                FrontFace: face,
+                BackFace: face,
+            },
+            Some(&mut dss),
+        )?;
+    }
+    let (dsv, dss) = (dsv.unwrap(), dss.unwrap());
+    unsafe {
+        device_context.ClearDepthStencilView(
+            &dsv,
+            D3D11_CLEAR_STENCIL.0 as _,
+            1.,
+            1,
+        )
+    };
+
+    let mut renderer = Renderer::new(&device)?;
+    let mut delta = font_atlas_delta();
+    for (stencil_ref, alpha) in [(0, 0), (1, 255)] {
+        renderer.set_stencil_mask(Some(StencilMask {
+            depth_stencil_view: dsv.clone(),
+            depth_stencil_state: dss.clone(),
+            stencil_ref,
+        }));
+        unsafe { device_context.ClearRenderTargetView(&rtv, &[0.; 4]) };
+        renderer.render_primitives(
+            &device_context,
+            &rtv,
+            std::mem::take(&mut delta),
+            &full_rect(Color32::WHITE),
+            1.,
+        )?;
+        let pixel = read_pixel(&device, &device_context, &tex)?;
+        assert_eq!(pixel[3], alpha, "stencil reference {stencil_ref}");
+    }
+    Ok(())
+}
+
+#[test]
+fn dropping_renderer_frees_managed_textures() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (_, rtv) = create_render_target(&device)?;
+    let freed = Arc::new(Mutex::new(Vec::new()));
+    let mut renderer = Renderer::new(&device)?;
+    renderer.set_on_texture_freed(Some(Box::new({
+        let freed = freed.clone();
+        move |tid| freed.lock().unwrap().push(tid)
+    })));
+    renderer.render_primitives(
+        &device_context,
+        &rtv,
+        font_atlas_delta(),
+        &full_rect(Color32::WHITE),
+        1.,
+    )?;
+    assert!(freed.lock().unwrap().is_empty());
+    drop(renderer);
+    assert_eq!(*freed.lock().unwrap(), [TextureId::default()]);
+    Ok(())
+}
+
+#[test]
+fn managed_textures_are_freed_in_id_order() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (_, rtv) = create_render_target(&device)?;
+    let freed = Arc::new(Mutex::new(Vec::new()));
+    let mut renderer = Renderer::new(&device)?;
+    renderer.set_on_texture_freed(Some(Box::new({
+        let freed = freed.clone();
+        move |tid| freed.lock().unwrap().push(tid)
+    })));
+    let tids = [5, 1, 3, 0].map(TextureId::Managed);
+    renderer.render_primitives(
+        &device_context,
+        &rtv,
+        white_textures_delta(&tids),
+        &full_rect(Color32::WHITE),
+        1.,
+    )?;
+    let report = renderer.texture_pool().memory_report();
+    let reported: Vec<_> =
+        report.managed.iter().map(|&(tid, ..)| tid).collect();
+    let sorted = [0, 1, 3, 5].map(TextureId::Managed);
+    assert_eq!(reported, sorted);
+    drop(renderer);
+    assert_eq!(*freed.lock().unwrap(), sorted);
+    Ok(())
+}
+
+#[test]
+fn texture_callbacks_report_ids_and_sizes() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (_, rtv) = create_render_target(&device)?;
+    let created = Arc::new(Mutex::new(Vec::new()));
+    let freed = Arc::new(Mutex::new(Vec::new()));
+    let mut renderer = Renderer::new(&device)?;
+    renderer.set_on_texture_created(Some(Box::new({
+        let created = created.clone();
+        move |tid, width, height| {
+            created.lock().unwrap().push((tid, width, height))
+        }
+    })));
+    renderer.set_on_texture_freed(Some(Box::new({
+        let freed = freed.clone();
+        move |tid| freed.lock().unwrap().push(tid)
+    })));
+    let tid = TextureId::Managed(1);
+    let replaced = TexturesDelta {
+        set: vec![(
+            tid,
+            ImageDelta::full(
+                ColorImage::new([2, 3], Color32::WHITE),
+                TextureOptions::NEAREST,
+            ),
+        )],
+        free: Vec::new(),
+    };
+    let removed = TexturesDelta {
+        set: Vec::new(),
+        free: vec![tid],
+    };
+    for delta in [
+        white_textures_delta(&[TextureId::default(), tid]),
+        replaced,
+        removed,
+    ] {
+        renderer.render_primitives(
+            &device_context,
+            &rtv,
+            delta,
+            &full_rect(Color32::WHITE),
+            1.,
+        )?;
+    }
+    assert_eq!(
+        *created.lock().unwrap(),
+        [(TextureId::default(), 1, 1), (tid, 1, 1), (tid, 2, 3)]
+    );
+    assert_eq!(*freed.lock().unwrap(), [tid, tid]);
+    Ok(())
+}
+
+#[test]
+fn texture_pool_reports_kinds_of_ids() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (_, rtv) = create_render_target(&device)?;
+    let mut renderer = Renderer::new(&device)?;
+    let tex = create_texture(
+        &device,
+        DXGI_FORMAT_R8G8B8A8_UNORM,
+        D3D11_BIND_SHADER_RESOURCE,
+    )?;
+    let mut srv = None;
+    unsafe { device.CreateShaderResourceView(&tex, None, Some(&mut srv)) }?;
+    let user = renderer.register_user_texture(srv.unwrap());
+    renderer.render_primitives(
+        &device_context,
+        &rtv,
+        font_atlas_delta(),
+        &full_rect(Color32::WHITE),
+        1.,
+    )?;
+    let managed = TextureId::default();
+    let absent = TextureId::Managed(7);
+    let pool = renderer.texture_pool();
+    assert!(pool.contains(user));
+    assert!(pool.contains(managed));
+    assert!(!pool.contains(absent));
+    assert_eq!(pool.kind(user), Some(TextureKind::User));
+    assert_eq!(pool.kind(managed), Some(TextureKind::Managed));
+    assert_eq!(pool.kind(absent), None);
+    Ok(())
+}
+
+#[test]
+fn built_in_mrt_shader_writes_coverage() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (color_tex, color_rtv) = create_render_target(&device)?;
+    let (mask_tex, mask_rtv) = create_render_target(&device)?;
+    let mut renderer = Renderer::new(&device)?;
+    let egui_ctx = egui::Context::default();
+    let full_output = egui_ctx.run(Default::default(), |_| {});
+    let mut output = egui_directx11::split_output(full_output).0;
+    output.textures_delta = font_atlas_delta();
+    output.shapes = vec![egui::epaint::ClippedShape {
+        clip_rect: Rect::EVERYTHING,
+        shape: egui::Shape::rect_filled(
+            Rect::from_min_max(
+                (0., 0.).into(),
+                (SIZE as f32, SIZE as f32).into(),
+            ),
+            0.,
+            Color32::from_rgba_premultiplied(0, 0, 128, 128),
+        ),
+    }];
+    output.pixels_per_point = 1.;
+    for rtv in [&color_rtv, &mask_rtv] {
+        unsafe { device_context.ClearRenderTargetView(rtv, &[0.; 4]) };
+    }
+    renderer.render_to_multiple_targets(
+        &device_context,
+        &[color_rtv, mask_rtv],
+        &egui_ctx,
+        output,
+        1.,
+    )?;
+    let color = read_pixel(&device, &device_context, &color_tex)?;
+    let mask = read_pixel(&device, &device_context, &mask_tex)?;
+    assert_eq!((color[0], color[3]), (0, 128));
+    assert_eq!(mask, [128; 4]);
+    Ok(())
+}
+
+#[test]
+fn color_images_are_updated_partially() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (tex, rtv) = create_render_target(&device)?;
+    for strategy in [
+        TextureUploadStrategy::Dynamic,
+        TextureUploadStrategy::Staging,
+    ] {
+        let mut renderer = Renderer::new(&device)?;
+        renderer.options_mut().texture_upload_strategy = strategy;
+        let red = Arc::new(ColorImage::new([1, 1], Color32::RED));
+        let partial = TexturesDelta {
+            set: vec![(
+                TextureId::default(),
+                ImageDelta::partial(
+                    [0, 0],
+                    ImageData::Color(red),
+                    TextureOptions::NEAREST,
+                ),
+            )],
+            free: Vec::new(),
+        };
+        for delta in [font_atlas_delta(), partial] {
+            renderer.render_primitives(
+                &device_context,
+                &rtv,
+                delta,
+                &full_rect(Color32::WHITE),
+                1.,
+            )?;
+        }
+        let pixel = read_pixel(&device, &device_context, &tex)?;
+        assert_eq!(pixel, [255, 0, 0, 255], "{strategy:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn over_large_partial_updates_are_ignored() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (tex, rtv) = create_render_target(&device)?;
+    for strategy in [
+        TextureUploadStrategy::Dynamic,
+        TextureUploadStrategy::Staging,
+    ] {
+        let mut renderer = Renderer::new(&device)?;
+        renderer.options_mut().texture_upload_strategy = strategy;
+        // a region of 2x2 at (0, 0) and one of 1x1 at (1, 0) of a 1x1 atlas.
+        let partial = |pos, size| TexturesDelta {
+            set: vec![(
+                TextureId::default(),
+                ImageDelta::partial(
+                    pos,
+                    ImageData::Color(Arc::new(ColorImage::new(
+                        size,
+                        Color32::RED,
+                    ))),
+                    TextureOptions::NEAREST,
+                ),
+            )],
+            free: Vec::new(),
+        };
+        for delta in [
+            font_atlas_delta(),
+            partial([0, 0], [2, 2]),
+            partial([1, 0], [1, 1]),
+        ] {
+            renderer.render_primitives(
+                &device_context,
+                &rtv,
+                delta,
+                &full_rect(Color32::WHITE),
+                1.,
+            )?;
+        }
+        let pixel = read_pixel(&device, &device_context, &tex)?;
+        assert_eq!(pixel, [255, 255, 255, 255], "{strategy:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn only_dynamic_font_atlases_are_kept_on_the_cpu() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (_, rtv) = create_render_target(&device)?;
+    for strategy in [
+        TextureUploadStrategy::Dynamic,
+        TextureUploadStrategy::Staging,
+    ] {
+        let mut renderer = Renderer::new(&device)?;
+        renderer.options_mut().texture_upload_strategy = strategy;
+        let image = ColorImage::new([1024, 1024], Color32::RED);
+        let font = FontImage::new([64, 64]);
+        let delta = TexturesDelta {
+            set: vec![
+                (
+                    TextureId::default(),
+                    ImageDelta::full(font, TextureOptions::NEAREST),
+                ),
+                (
+                    TextureId::Managed(1),
+                    ImageDelta::full(image, TextureOptions::NEAREST),
+                ),
+            ],
+            free: Vec::new(),
+        };
+        renderer.render_primitives(
+            &device_context,
+            &rtv,
+            delta,
+            &full_rect(Color32::WHITE),
+            1.,
+        )?;
+        let report = renderer.texture_pool().memory_report();
+        let expected = match strategy {
+            TextureUploadStrategy::Dynamic => 64 * 64 * 4,
+            TextureUploadStrategy::Staging => 0,
+        };
+        assert_eq!(report.cpu_bytes, expected, "{strategy:?}");
+    }
+    Ok(())
+}
+
+/// A rectangle covering the whole render target sampling `tid`.
+fn textured_rect(tid: TextureId) -> Vec<ClippedShape> {
+    let mut mesh = Mesh::with_texture(tid);
+    let rect =
+        Rect::from_min_max((0., 0.).into(), (SIZE as f32, SIZE as f32).into());
+    mesh.add_rect_with_uv(rect, rect, Color32::WHITE);
+    vec![ClippedShape {
+        clip_rect: Rect::EVERYTHING,
+        shape: egui::Shape::mesh(mesh),
+    }]
+}
+
+#[test]
+fn textures_are_evicted_per_frame() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (_, rtv) = create_render_target(&device)?;
+    let freed = Arc::new(Mutex::new(Vec::new()));
+    let mut renderer = Renderer::new(&device)?;
+    renderer.options_mut().texture_budget = Some(0);
+    renderer.set_on_texture_freed(Some(Box::new({
+        let freed = freed.clone();
+        move |tid| freed.lock().unwrap().push(tid)
+    })));
+    let egui_ctx = egui::Context::default();
+    let image = TextureId::Managed(1);
+    let mut delta = white_textures_delta(&[TextureId::default(), image]);
+    for frame in 0..3 {
+        renderer.begin_frame(&device_context, &std::mem::take(&mut delta))?;
+        assert_eq!(
+            *freed.lock().unwrap(),
+            if frame < 2 { vec![] } else { vec![image] },
+            "frame {frame}"
+        );
+        // the image is only sampled by the first draw of the first frame,
+        // which must not count as a frame of its own.
+        let draws = if frame == 0 {
+            [image, TextureId::default()]
+        } else {
+            [TextureId::default(); 2]
+        };
+        for tid in draws {
+            renderer.draw(
+                &device_context,
+                &rtv,
+                &egui_ctx,
+                textured_rect(tid),
+                1.,
+                1.,
+            )?;
+        }
+        renderer.end_frame(&device_context)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn rounded_clip_masks_corners_of_listed_clip_rects() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (tex, rtv) = create_render_target(&device)?;
+    let mut renderer = Renderer::new(&device)?;
+    let mut delta = font_atlas_delta();
+    let full =
+        Rect::from_min_max((0., 0.).into(), (SIZE as f32, SIZE as f32).into());
+    let other = Rect::from_min_max((1., 1.).into(), (2., 2.).into());
+    for (clip_rect, rounded) in [(full, true), (other, false)] {
+        renderer.options_mut().rounded_clip = vec![(clip_rect, SIZE as f32)];
+        unsafe { device_context.ClearRenderTargetView(&rtv, &[0.; 4]) };
+        let mut primitives = full_rect(Color32::WHITE);
+        primitives[0].clip_rect = full;
+        renderer.render_primitives(
+            &device_context,
+            &rtv,
+            std::mem::take(&mut delta),
+            &primitives,
+            1.,
+        )?;
+        let center = read_pixel(&device, &device_context, &tex)?;
+        let corner = read_pixel_at(&device, &device_context, &tex, 0, 0)?;
+        assert_eq!(center[3], 255);
+        assert_eq!(corner[3] < 255, rounded, "{clip_rect:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn recommended_target_format_follows_color_space() -> Result<()> {
+    let (device, _) = create_device()?;
+    let mut renderer = Renderer::new(&device)?;
+    assert_eq!(
+        renderer.recommended_target_format(),
+        DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+    );
+    renderer.set_color_space(ColorSpace::Gamma);
+    assert_eq!(
+        renderer.recommended_target_format(),
+        DXGI_FORMAT_R8G8B8A8_UNORM,
+    );
+    renderer.set_color_space(ColorSpace::Linear);
+    assert_eq!(
+        renderer.recommended_target_format(),
+        DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+    );
+    Ok(())
+}
+
+#[test]
+fn straight_alpha_output_unpremultiplies_colors() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (tex, rtv) = create_render_target(&device)?;
+    let mut renderer = Renderer::new(&device)?;
+    let mut delta = font_atlas_delta();
+    let mut pixels = Vec::new();
+    for straight in [false, true] {
+        renderer.options_mut().straight_alpha_output = straight;
+        unsafe { device_context.ClearRenderTargetView(&rtv, &[0.; 4]) };
+        renderer.render_primitives(
+            &device_context,
+            &rtv,
+            std::mem::take(&mut delta),
+            &full_rect(Color32::from_rgba_premultiplied(0, 0, 128, 128)),
+            1.,
+        )?;
+        pixels.push(read_pixel(&device, &device_context, &tex)?);
+    }
+    let [premultiplied, straight] = pixels[..] else {
+        unreachable!()
+    };
+    assert_eq!(straight[3], premultiplied[3]);
+    let expected = premultiplied[2] as f32 * 255. / premultiplied[3] as f32;
+    assert!((straight[2] as f32 - expected).abs() <= 2., "{straight:?}");
+    Ok(())
+}
+
+#[test]
+fn dual_source_blending_approximates_gamma_space_blending() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (tex, rtv) = create_render_target(&device)?;
+    let mut renderer = Renderer::new(&device)?;
+    let mut delta = font_atlas_delta();
+    for alpha in [64u8, 128, 192] {
+        // black blended over white in gamma space, decoded to linear.
+        let gamma = 1. - alpha as f32 / 255.;
+        let expected = gamma.powf(2.2) * 255.;
+        let mut errors = Vec::new();
+        for dual_source in [false, true] {
+            renderer.options_mut().dual_source_blending = dual_source;
+            unsafe { device_context.ClearRenderTargetView(&rtv, &[1.; 4]) };
+            renderer.render_primitives(
+                &device_context,
+                &rtv,
+                std::mem::take(&mut delta),
+                &full_rect(Color32::from_black_alpha(alpha)),
+                1.,
+            )?;
+            let pixel = read_pixel(&device, &device_context, &tex)?;
+            assert_eq!(pixel[3], 255);
+            errors.push((pixel[0] as f32 - expected).abs());
+        }
+        assert!(errors[1] < errors[0], "{alpha}: {errors:?}");
+        assert!(errors[1] <= 3., "{alpha}: {errors:?}");
+    }
+    Ok(())
+}
+
+#[test]
+fn mip_lod_bias_selects_coarser_mips() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (tex, rtv) = create_render_target(&device)?;
+    let mut renderer = Renderer::new(&device)?;
+
+    // a red level of the size of the render target, and a green one below.
+    let red = vec![Color32::RED; (SIZE * SIZE) as usize];
+    let green = vec![Color32::GREEN; (SIZE * SIZE / 4) as usize];
+    let levels = [(&red, SIZE), (&green, SIZE / 2)].map(|(pixels, width)| {
+        D3D11_SUBRESOURCE_DATA {
+            pSysMem: pixels.as_ptr() as _,
+            SysMemPitch: width * 4,
+            SysMemSlicePitch: 0,
+        }
+    });
+    let mut mipped = None;
+    unsafe {
+        device.CreateTexture2D(
+            &D3D11_TEXTURE2D_DESC {
+                Width: SIZE,
+                Height: SIZE,
+                MipLevels: 2,
+                ArraySize: 1,
+                Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+                SampleDesc: DXGI_SAMPLE_DESC {
+                    Count: 1,
+                    Quality: 0,
+                },
+                Usage: D3D11_USAGE_DEFAULT,
+                BindFlags: D3D11_BIND_SHADER_RESOURCE.0 as _,
+                ..Default::default()
+            },
+            Some(levels.as_ptr()),
+            Some(&mut mipped),
+        )
+    }?;
+    let mut srv = None;
+    unsafe {
+        device.CreateShaderResourceView(&mipped.unwrap(), None, Some(&mut srv))
+    }?;
+    let tid = renderer.register_user_texture_premultiplied(srv.unwrap());
+
+    let mut mesh = Mesh::with_texture(tid);
+    let rect =
+        Rect::from_min_max((0., 0.).into(), (SIZE as f32, SIZE as f32).into());
+    let uv = Rect::from_min_max((0., 0.).into(), (1., 1.).into());
+    mesh.add_rect_with_uv(rect, uv, Color32::WHITE);
+    let primitives = [ClippedPrimitive {
+        clip_rect: Rect::EVERYTHING,
+        primitive: Primitive::Mesh(mesh),
+    }];
+    let mut delta = font_atlas_delta();
+    for (bias, expected) in [(0., [255, 0, 0, 255]), (1., [0, 255, 0, 255])] {
+        renderer.set_mip_lod_bias(bias)?;
+        renderer.render_primitives(
+            &device_context,
+            &rtv,
+            std::mem::take(&mut delta),
+            &primitives,
+            1.,
+        )?;
+        let pixel = read_pixel(&device, &device_context, &tex)?;
+        assert_eq!(pixel, expected, "bias {bias}");
+    }
+    Ok(())
+}
+
+#[test]
+fn single_dynamic_buffers_draw_many_meshes_per_frame() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (tex, rtv) = create_render_target(&device)?;
+    let mut renderer = Renderer::new(&device)?;
+    renderer.options_mut().buffer_strategy = BufferStrategy::SingleDynamic;
+    let color = |i: u32| {
+        let c = |bit| if i & bit != 0 { 255 } else { 0 };
+        Color32::from_rgb(c(1), c(2), c(4))
+    };
+    let mut delta = font_atlas_delta();
+    // each pixel is covered by its own mesh, all of them bump-allocated from
+    // the same buffers, and the second frame reuses them from the start.
+    for frame in 0..2 {
+        let primitives: Vec<_> = (0..SIZE * SIZE)
+            .map(|i| {
+                let (x, y) = ((i % SIZE) as f32, (i / SIZE) as f32);
+                let mut mesh = Mesh::default();
+                let rect =
+                    Rect::from_min_max((x, y).into(), (x + 1., y + 1.).into());
+                mesh.add_colored_rect(rect, color(i + frame));
+                ClippedPrimitive {
+                    clip_rect: Rect::EVERYTHING,
+                    primitive: Primitive::Mesh(mesh),
+                }
+            })
+            .collect();
+        renderer.render_primitives(
+            &device_context,
+            &rtv,
+            std::mem::take(&mut delta),
+            &primitives,
+            1.,
+        )?;
+        for i in 0..SIZE * SIZE {
+            let pixel = read_pixel_at(
+                &device,
+                &device_context,
+                &tex,
+                i % SIZE,
+                i / SIZE,
+            )?;
+            let [r, g, b, a] = color(i + frame).to_array();
+            assert_eq!(pixel, [r, g, b, a], "frame {frame}, mesh {i}");
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn output_alpha_accumulates_coverage() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (tex, rtv) = create_render_target(&device)?;
+    let mut renderer = Renderer::new(&device)?;
+    unsafe { device_context.ClearRenderTargetView(&rtv, &[0.; 4]) };
+    let alphas = [64u8, 128, 192];
+    let primitives: Vec<_> = alphas
+        .iter()
+        .flat_map(|&alpha| full_rect(Color32::from_white_alpha(alpha)))
+        .collect();
+    renderer.render_primitives(
+        &device_context,
+        &rtv,
+        font_atlas_delta(),
+        &primitives,
+        1.,
+    )?;
+    // 1 - (1 - a_0) * (1 - a_1) * ..., as over-compositing expects.
+    let transmittance: f32 =
+        alphas.iter().map(|&a| 1. - a as f32 / 255.).product();
+    let expected = (1. - transmittance) * 255.;
+    let pixel = read_pixel(&device, &device_context, &tex)?;
+    assert!((pixel[3] as f32 - expected).abs() <= 1., "{pixel:?}");
+    Ok(())
+}
+
+#[test]
+fn batches_of_user_textures_get_contiguous_ids() -> Result<()> {
+    let (device, _) = create_device()?;
+    let mut renderer = Renderer::new(&device)?;
+    let srvs = (0..6)
+        .map(|_| {
+            let tex = create_texture(
+                &device,
+                DXGI_FORMAT_R8G8B8A8_UNORM,
+                D3D11_BIND_SHADER_RESOURCE,
+            )?;
+            let mut srv = None;
+            unsafe {
+                device.CreateShaderResourceView(&tex, None, Some(&mut srv))
+            }?;
+            Ok(srv.unwrap())
+        })
+        .collect::<Result<Vec<_>>>()?;
+    // a texture registered before the batch, so that it does not start at 0.
+    let single = renderer.register_user_texture(srvs[0].clone());
+    let ids = renderer.register_user_textures(&srvs[1..]);
+    assert_eq!(ids.len(), 5);
+    let TextureId::User(first) = ids[0] else {
+        panic!("{:?} is not a user texture", ids[0]);
+    };
+    assert_ne!(TextureId::User(first), single);
+    for (n, &id) in ids.iter().enumerate() {
+        assert_eq!(id, TextureId::User(first + n as u64));
+    }
+    Ok(())
+}
+
+#[test]
+fn meshes_over_the_limit_are_skipped() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (tex, rtv) = create_render_target(&device)?;
+    let mut renderer = Renderer::new(&device)?;
+    renderer.options_mut().max_meshes = Some(3);
+    unsafe { device_context.ClearRenderTargetView(&rtv, &[0.; 4]) };
+    // a mesh for each of the first five pixels of the top row and below.
+    let primitives: Vec<_> = (0..5)
+        .map(|i| {
+            let (x, y) = ((i % SIZE) as f32, (i / SIZE) as f32);
+            let mut mesh = Mesh::default();
+            let rect =
+                Rect::from_min_max((x, y).into(), (x + 1., y + 1.).into());
+            mesh.add_colored_rect(rect, Color32::WHITE);
+            ClippedPrimitive {
+                clip_rect: Rect::EVERYTHING,
+                primitive: Primitive::Mesh(mesh),
+            }
+        })
+        .collect();
+    renderer.render_primitives(
+        &device_context,
+        &rtv,
+        font_atlas_delta(),
+        &primitives,
+        1.,
+    )?;
+    let stats = renderer.stats();
+    assert_eq!(stats.meshes_drawn, 3);
+    assert_eq!(stats.meshes_skipped, 2);
+    for i in 0..5 {
+        let pixel =
+            read_pixel_at(&device, &device_context, &tex, i % SIZE, i / SIZE)?;
+        let expected = if i < 3 { 255 } else { 0 };
+        assert_eq!(pixel, [expected; 4], "mesh {i}");
+    }
+    Ok(())
+}
+
+#[test]
+fn empty_deltas_leave_textures_unchanged() -> Result<()> {
+    let (device, device_context) = create_device()?;
+    let (_, rtv) = create_render_target(&device)?;
+    let mut renderer = Renderer::new(&device)?;
+    let mut changed = Vec::new();
+    let mut reports = Vec::new();
+    for delta in [font_atlas_delta(), TexturesDelta::default()] {
+        renderer.render_primitives(
+            &device_context,
+            &rtv,
+            delta,
+            &full_rect(Color32::WHITE),
+            1.,
+        )?;
+        changed.push(renderer.stats().textures_changed);
+        reports.push(renderer.texture_pool().memory_report().managed);
+    }
+    assert_eq!(changed, [true, false]);
+    assert_eq!(reports[0], reports[1]);
+    assert_eq!(renderer.texture_pool().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn state_descriptions_follow_options() -> Result<()> {
+    let (device, _) = create_device()?;
+    let mut renderer = Renderer::new(&device)?;
+    assert_eq!(renderer.blend_desc().RenderTarget[0].BlendEnable, BOOL(1));
+    assert_eq!(renderer.rasterizer_desc().DepthClipEnable, BOOL(0));
+    renderer.options_mut().blend_mode = BlendMode::Opaque;
+    renderer.options_mut().depth_clip = true;
+    assert_eq!(renderer.blend_desc().RenderTarget[0].BlendEnable, BOOL(0));
+    assert_eq!(renderer.rasterizer_desc().DepthClipEnable, BOOL(1));
+
+    renderer.set_texture_filter(D3D11_FILTER_MIN_MAG_MIP_POINT)?;
+    renderer.set_mip_lod_bias(-0.5)?;
+    let sampler = renderer.sampler_desc();
+    assert_eq!(sampler.Filter, D3D11_FILTER_MIN_MAG_MIP_POINT);
+    assert_eq!(sampler.MipLODBias, -0.5);
+    assert_eq!(sampler.MaxLOD, D3D11_FLOAT32_MAX);
+    Ok(())
+}